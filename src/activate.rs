@@ -0,0 +1,249 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::error::*;
+
+/// Shells supported by `dmenv env`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl std::str::FromStr for Shell {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            other => Err(new_error(format!(
+                "unknown shell: '{}' (should be 'bash', 'zsh' or 'fish')",
+                other
+            ))),
+        }
+    }
+}
+
+impl Shell {
+    /// Guess the shell from the value of the `SHELL` environment
+    /// variable, defaulting to bash
+    pub fn detect(shell_var: Option<&str>) -> Self {
+        let name = shell_var
+            .and_then(|s| Path::new(s).file_name())
+            .map(|n| n.to_string_lossy().to_string());
+        match name.as_deref() {
+            Some("zsh") => Shell::Zsh,
+            Some("fish") => Shell::Fish,
+            _ => Shell::Bash,
+        }
+    }
+}
+
+/// A change to make to the environment
+#[derive(Debug, Clone, PartialEq)]
+pub enum EnvChange {
+    Set(String, OsString),
+    /// Prepend a directory to a variable containing a list of paths, like PATH
+    Prepend(String, PathBuf),
+    Unset(String),
+}
+
+/// What it takes to activate a virtualenv, like the `activate`
+/// scripts generated by `venv` do
+#[derive(Debug)]
+pub struct Activation {
+    pub venv_path: PathBuf,
+    pub bin_path: PathBuf,
+    /// Name shown in the prompt, usually the name of the project
+    pub prompt: String,
+}
+
+impl Activation {
+    pub fn changes(&self) -> Vec<EnvChange> {
+        vec![
+            EnvChange::Set(
+                "VIRTUAL_ENV".to_string(),
+                self.venv_path.clone().into_os_string(),
+            ),
+            EnvChange::Prepend("PATH".to_string(), self.bin_path.clone()),
+            EnvChange::Unset("PYTHONHOME".to_string()),
+            EnvChange::Set(
+                "VIRTUAL_ENV_PROMPT".to_string(),
+                self.prompt_prefix().into(),
+            ),
+        ]
+    }
+
+    pub fn prompt_prefix(&self) -> String {
+        format!("({}) ", self.prompt)
+    }
+
+    /// Compute the new value of every changed variable, given a function
+    /// returning the current value of a variable. `None` means the
+    /// variable must be removed.
+    // Note: `get_var` is a parameter so that this can be tested without
+    // touching the environment of the test process
+    pub fn apply<F>(&self, get_var: F) -> Result<Vec<(String, Option<OsString>)>, Error>
+    where
+        F: Fn(&str) -> Option<OsString>,
+    {
+        let mut res = vec![];
+        for change in self.changes() {
+            let entry = match change {
+                EnvChange::Set(name, value) => (name, Some(value)),
+                EnvChange::Unset(name) => (name, None),
+                EnvChange::Prepend(name, path) => {
+                    let mut paths = vec![path];
+                    if let Some(current) = get_var(&name) {
+                        paths.extend(std::env::split_paths(&current));
+                    }
+                    let value = std::env::join_paths(paths).map_err(|e| {
+                        new_error(format!("could not compute new value of {}: {}", name, e))
+                    })?;
+                    (name, Some(value))
+                }
+            };
+            res.push(entry);
+        }
+        Ok(res)
+    }
+
+    /// Shell code performing the same changes, meant to be `eval`ed
+    pub fn script(&self, shell: Shell) -> String {
+        let mut lines = vec![];
+        for change in self.changes() {
+            let line = match (shell, change) {
+                (Shell::Fish, EnvChange::Set(name, value)) => {
+                    format!(
+                        "set -gx {} {}",
+                        name,
+                        quote(shell, &value.to_string_lossy())
+                    )
+                }
+                (Shell::Fish, EnvChange::Prepend(name, path)) => format!(
+                    "set -gx {} {} ${}",
+                    name,
+                    quote(shell, &path.to_string_lossy()),
+                    name
+                ),
+                (Shell::Fish, EnvChange::Unset(name)) => format!("set -e {}", name),
+                (_, EnvChange::Set(name, value)) => {
+                    format!("export {}={}", name, quote(shell, &value.to_string_lossy()))
+                }
+                (_, EnvChange::Prepend(name, path)) => format!(
+                    "export {}={}:\"${}\"",
+                    name,
+                    quote(shell, &path.to_string_lossy()),
+                    name
+                ),
+                (_, EnvChange::Unset(name)) => format!("unset {}", name),
+            };
+            lines.push(line);
+        }
+        let prefix = quote(shell, &self.prompt_prefix());
+        match shell {
+            Shell::Fish => {
+                // Note: keep the original prompt, so that the prefix is
+                // only added once when this is evaluated several times
+                let save_prompt = concat!(
+                    "functions -q _dmenv_old_fish_prompt; ",
+                    "or functions -c fish_prompt _dmenv_old_fish_prompt"
+                );
+                lines.push(save_prompt.to_string());
+                lines.push(format!(
+                    "function fish_prompt; printf '%s' {}; _dmenv_old_fish_prompt; end",
+                    prefix
+                ));
+            }
+            _ => lines.push(format!("PS1={}\"${{PS1:-}}\"", prefix)),
+        }
+        let mut res = lines.join("\n");
+        res.push('\n');
+        res
+    }
+}
+
+/// Quote a value so that the shell uses it verbatim
+fn quote(shell: Shell, value: &str) -> String {
+    match shell {
+        Shell::Fish => format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'")),
+        _ => format!("'{}'", value.replace('\'', "'\\''")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn activation() -> Activation {
+        Activation {
+            venv_path: PathBuf::from("/work/foo/.venv/dev/3.7.4"),
+            bin_path: PathBuf::from("/work/foo/.venv/dev/3.7.4/bin"),
+            prompt: "foo".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_detect_shell() {
+        assert_eq!(Shell::detect(Some("/usr/bin/fish")), Shell::Fish);
+        assert_eq!(Shell::detect(Some("/bin/zsh")), Shell::Zsh);
+        assert_eq!(Shell::detect(Some("/bin/sh")), Shell::Bash);
+        assert_eq!(Shell::detect(None), Shell::Bash);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_apply() {
+        let changes = activation()
+            .apply(|name| match name {
+                "PATH" => Some("/usr/bin:/bin".into()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(
+            changes,
+            &[
+                (
+                    "VIRTUAL_ENV".to_string(),
+                    Some("/work/foo/.venv/dev/3.7.4".into())
+                ),
+                (
+                    "PATH".to_string(),
+                    Some("/work/foo/.venv/dev/3.7.4/bin:/usr/bin:/bin".into())
+                ),
+                ("PYTHONHOME".to_string(), None),
+                ("VIRTUAL_ENV_PROMPT".to_string(), Some("(foo) ".into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bash_script() {
+        let script = activation().script(Shell::Bash);
+        assert_eq!(
+            script,
+            r#"export VIRTUAL_ENV='/work/foo/.venv/dev/3.7.4'
+export PATH='/work/foo/.venv/dev/3.7.4/bin':"$PATH"
+unset PYTHONHOME
+export VIRTUAL_ENV_PROMPT='(foo) '
+PS1='(foo) '"${PS1:-}"
+"#
+        );
+    }
+
+    #[test]
+    fn test_fish_script() {
+        let script = activation().script(Shell::Fish);
+        assert!(script.contains("set -gx PATH '/work/foo/.venv/dev/3.7.4/bin' $PATH\n"));
+        assert!(script.contains("set -e PYTHONHOME\n"));
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote(Shell::Bash, "it's"), r#"'it'\''s'"#);
+        assert_eq!(quote(Shell::Fish, "it's"), r#"'it\'s'"#);
+    }
+}
@@ -1,19 +1,56 @@
 use crate::cli::commands;
 use crate::error::*;
+use crate::project::ProjectKind;
 use crate::ui::*;
 use crate::Context;
+use crate::PostInstallAction;
 
-/// Runs `python setup.py` develop. Also called by `install` (unless InstallOptions.develop is false)
-// Note: `lock()` will use `pip install --editable .` to achieve the same effect
+/// Install the project itself in the virtualenv, without its dependencies.
+/// Also called by `install` (unless InstallOptions.develop is false)
+//
+// Notes:
+// * `lock()` will use `pip install --editable .[dev]` to achieve the same effect
+// * Projects using `pyproject.toml` are installed with `pip install --editable`,
+//   which relies on the build backend supporting PEP 660
+// * So are the members of a workspace sharing the virtualenv
 pub fn develop(context: &Context) -> Result<(), Error> {
     let Context {
-        paths, venv_runner, ..
+        paths,
+        settings,
+        venv_runner,
+        ..
     } = context;
-    commands::expect_venv(&context)?;
-    print_info_2("Running setup_py.py develop");
-    if !&paths.setup_py.exists() {
-        return Err(Error::MissingSetupPy {});
+    // Note: even with `--no-deps`, pip may need to fetch the
+    // build dependencies of the project
+    let pip_options = settings.pip_options();
+    commands::expect_venv(context)?;
+    commands::check_venv_fingerprint(context, PostInstallAction::None)?;
+    if let Some(members) = commands::shared_members(context) {
+        print_info_2("Installing workspace members in editable mode");
+        let members: Vec<_> = members.iter().map(|p| p.to_string_lossy()).collect();
+        let mut cmd = vec!["python", "-m", "pip", "install", "--no-deps"];
+        for member in &members {
+            cmd.push("--editable");
+            cmd.push(member);
+        }
+        cmd.extend(pip_options.iter().map(String::as_str));
+        return venv_runner.run(&cmd);
+    }
+    match ProjectKind::expect(&paths.project)? {
+        ProjectKind::SetupPy => {
+            print_info_2("Running setup.py develop");
+            venv_runner.run(&["python", "setup.py", "develop", "--no-deps"])
+        }
+        ProjectKind::Pyproject => {
+            print_info_2("Installing project in editable mode");
+            #[rustfmt::skip]
+            let mut cmd = vec![
+                "python", "-m", "pip", "install",
+                "--no-deps",
+                "--editable", ".",
+            ];
+            cmd.extend(pip_options.iter().map(String::as_str));
+            venv_runner.run(&cmd)
+        }
     }
-
-    venv_runner.run(&["python", "setup.py", "develop", "--no-deps"])
 }
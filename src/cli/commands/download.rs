@@ -0,0 +1,285 @@
+use crate::cli::commands;
+use crate::dependencies::LockedDependency;
+use crate::error::*;
+use crate::lock::Lock;
+use crate::markers::Environment;
+use crate::operations;
+use crate::project;
+use crate::ui::*;
+use crate::wheelhouse;
+use crate::Context;
+
+/// Name of the file, in the virtualenv, containing the lines of the lock
+/// that apply to the platform wheels are downloaded for
+const TARGET_REQUIREMENTS_FILENAME: &str = "dmenv-download-requirements.txt";
+
+/// Download the archives of every package in the lock into the wheelhouse,
+/// along with pip and the requirements needed to build the project, so
+/// that `dmenv install --offline` can be used without network access
+//
+// Notes:
+//  * `pip download` evaluates the markers of the lock with the interpreter
+//    of the virtualenv, even when downloading for another platform. So in
+//    this case, the lines of the lock are filtered beforehand, and passed
+//    without their markers
+//  * pip only accepts wheels when downloading for another platform or
+//    another version of Python
+//  * the lock contains the hashes of the archives for every platform,
+//    see `compute_hashes()`, so they are checked for every target
+pub fn download(
+    context: &Context,
+    platforms: &[String],
+    python_version: Option<&str>,
+) -> Result<(), Error> {
+    let Context {
+        paths,
+        python_info,
+        settings,
+        venv_runner,
+        ..
+    } = context;
+    let lock_path = &paths.lock;
+    if !lock_path.exists() {
+        return Err(Error::MissingLock {
+            expected_path: lock_path.to_path_buf(),
+        });
+    }
+    commands::ensure_venv(context)?;
+    print_info_1(&format!(
+        "Downloading packages into {}",
+        paths.project.join(&settings.wheelhouse).display()
+    ));
+
+    // Note: like in `install_from_lock()`, the command runs from the
+    // project, so the relative lock name is used
+    let lock_name = lock_path
+        .file_name()
+        .unwrap_or_else(|| panic!("self.path.lock has no filename component"))
+        .to_string_lossy();
+    let has_hashes = operations::lock::has_hashes(lock_path)?;
+    let lock = operations::lock::read(lock_path)?;
+    let current_environment = Environment::from_python_info(python_info);
+    let projects = commands::shared_members(context).unwrap_or_else(|| vec![paths.project.clone()]);
+    let mut tools = vec!["pip".to_string()];
+    for project_path in &projects {
+        for requirement in project::build_requirements(project_path)? {
+            if !tools.contains(&requirement) {
+                tools.push(requirement);
+            }
+        }
+    }
+    let download_options = settings.download_options();
+    let requirements_path = paths.venv.join(TARGET_REQUIREMENTS_FILENAME);
+
+    // Note: check every platform tag before downloading anything
+    let targets = targets(platforms, python_version)
+        .into_iter()
+        .map(|t| Ok((t.environment(&current_environment)?, t)))
+        .collect::<Result<Vec<_>, Error>>()?;
+    for (environment, target) in targets {
+        let options = target.pip_options();
+        let mut base_cmd = vec!["python", "-m", "pip", "download"];
+        base_cmd.extend(&["--dest", &settings.wheelhouse]);
+        base_cmd.extend(options.iter().map(String::as_str));
+        base_cmd.extend(download_options.iter().map(String::as_str));
+
+        // Note: the lock already contains every dependency
+        let requirements = if target.is_current() {
+            lock_name.to_string()
+        } else {
+            print_info_2(&format!("Downloading wheels for {}", options.join(" ")));
+            let contents = target_requirements(&lock, &environment);
+            std::fs::write(&requirements_path, contents)
+                .map_err(|e| new_write_error(e, &requirements_path))?;
+            requirements_path.to_string_lossy().to_string()
+        };
+        let mut cmd = base_cmd.clone();
+        cmd.extend(&["--no-deps", "--requirement", &requirements]);
+        if has_hashes {
+            cmd.push("--require-hashes");
+        }
+        venv_runner.run(&cmd)?;
+
+        let mut cmd = base_cmd.clone();
+        cmd.extend(tools.iter().map(String::as_str));
+        venv_runner.run(&cmd)?;
+    }
+    Ok(())
+}
+
+/// A platform and a version of Python to download wheels for, the
+/// ones of the virtualenv being used when not set
+#[derive(Debug)]
+struct Target<'a> {
+    platform: Option<&'a str>,
+    python_version: Option<&'a str>,
+}
+
+impl<'a> Target<'a> {
+    fn is_current(&self) -> bool {
+        self.platform.is_none() && self.python_version.is_none()
+    }
+
+    /// Options to pass to `pip download`
+    fn pip_options(&self) -> Vec<String> {
+        let mut res = vec![];
+        if let Some(platform) = self.platform {
+            res.push("--platform".to_string());
+            res.push(platform.to_string());
+        }
+        if let Some(version) = self.python_version {
+            res.push("--python-version".to_string());
+            res.push(version.to_string());
+        }
+        if !self.is_current() {
+            res.push("--only-binary".to_string());
+            res.push(":all:".to_string());
+        }
+        res
+    }
+
+    /// Values of the marker variables for this target, starting from
+    /// the ones of the virtualenv
+    // Note: like pip, accept versions of Python like `38` as well as `3.8`
+    fn environment(&self, current: &Environment) -> Result<Environment, Error> {
+        let mut res = current.clone();
+        if let Some(platform) = self.platform {
+            let (sys_platform, machine) =
+                wheelhouse::parse_platform_tag(platform).ok_or_else(|| {
+                    Error::UnknownPlatformTag {
+                        tag: platform.to_string(),
+                    }
+                })?;
+            let (system, os_name) = match sys_platform {
+                "win32" => ("Windows", "nt"),
+                "darwin" => ("Darwin", "posix"),
+                _ => ("Linux", "posix"),
+            };
+            res.set("sys_platform", sys_platform)
+                .set("platform_system", system)
+                .set("os_name", os_name)
+                .set("platform_machine", &machine)
+                .set("platform_release", "");
+        }
+        if let Some(version) = self.python_version {
+            let version = if version.contains('.') || version.len() < 2 {
+                version.to_string()
+            } else {
+                format!("{}.{}", &version[..1], &version[1..])
+            };
+            res.set("python_version", &version)
+                .set("python_full_version", &version);
+        }
+        Ok(res)
+    }
+}
+
+fn targets<'a>(platforms: &'a [String], python_version: Option<&'a str>) -> Vec<Target<'a>> {
+    if platforms.is_empty() {
+        return vec![Target {
+            platform: None,
+            python_version,
+        }];
+    }
+    platforms
+        .iter()
+        .map(|p| Target {
+            platform: Some(p),
+            python_version,
+        })
+        .collect()
+}
+
+/// The lines of the lock that apply to the given environment,
+/// without their markers
+fn target_requirements(lock: &Lock, environment: &Environment) -> String {
+    let mut res = String::new();
+    for dep in lock.dependencies().filter(|d| d.applies_to(environment)) {
+        let line = match dep {
+            LockedDependency::Git(_) => dep.line().trim_end().to_string(),
+            LockedDependency::Simple(s) => {
+                let mut line = format!("{}=={}", s.name, s.version.value);
+                for hash in &s.hashes {
+                    line.push_str(&format!(" \\\n    --hash={}", hash));
+                }
+                line
+            }
+        };
+        res.push_str(&line);
+        res.push('\n');
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::lock::parse;
+
+    #[test]
+    fn test_targets() {
+        let current = targets(&[], None);
+        assert!(current[0].is_current());
+        assert!(current[0].pip_options().is_empty());
+        let platforms = ["win_amd64".to_string(), "macosx_10_9_x86_64".to_string()];
+        let options: Vec<_> = targets(&platforms, Some("3.8"))
+            .iter()
+            .map(|t| t.pip_options())
+            .collect();
+        assert_eq!(
+            options,
+            &[
+                &[
+                    "--platform",
+                    "win_amd64",
+                    "--python-version",
+                    "3.8",
+                    "--only-binary",
+                    ":all:"
+                ],
+                &[
+                    "--platform",
+                    "macosx_10_9_x86_64",
+                    "--python-version",
+                    "3.8",
+                    "--only-binary",
+                    ":all:"
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_target_requirements() {
+        let lock = parse(
+            "attrs==19.1.0 \\\n    --hash=sha256:abc\n\
+             colorama==0.4.1 ; sys_platform == 'win32'\n\
+             dataclasses==0.6 ; python_version < '3.7'\n",
+        )
+        .unwrap();
+        let mut current = Environment::new();
+        current
+            .set("sys_platform", "linux")
+            .set("python_version", "3.8");
+        let target = Target {
+            platform: Some("win_amd64"),
+            python_version: Some("36"),
+        };
+        let environment = target.environment(&current).unwrap();
+        assert_eq!(
+            target_requirements(&lock, &environment),
+            "attrs==19.1.0 \\\n    --hash=sha256:abc\ncolorama==0.4.1\ndataclasses==0.6\n"
+        );
+        assert_eq!(
+            target_requirements(&lock, &current),
+            "attrs==19.1.0 \\\n    --hash=sha256:abc\n"
+        );
+
+        let target = Target {
+            platform: Some("solaris"),
+            python_version: None,
+        };
+        assert!(target.environment(&current).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+use crate::dependencies::FrozenDependency;
+use crate::error::*;
+use crate::lock::Hashes;
+use crate::paths::get_hashes_cache_path;
+use crate::ui::*;
+use crate::Context;
+
+const HASHES_SCRIPT: &str = include_str!("../../hashes.py");
+
+/// Name of the file, in the virtualenv, the script is written to
+const HASHES_SCRIPT_FILENAME: &str = "dmenv-hashes.py";
+
+/// Compute the sha256 hashes of every artifact of each dependency,
+/// so that the lock can be installed on any platform.
+//
+// Notes:
+//  * hashes are read from the indexes and the `--find-links` locations
+//    by the hashes.py script, see there for the details. It is written
+//    in the virtualenv, so that it runs with its interpreter
+//  * they are cached in dmenv's cache directory, one file per name and
+//    version, and per set of pip options, so that changing the index
+//    does not re-use hashes read from another one
+pub fn compute_hashes(context: &Context, deps: &[FrozenDependency]) -> Result<Hashes, Error> {
+    let Context {
+        paths,
+        settings,
+        venv_runner,
+        ..
+    } = context;
+    print_info_2("Computing hashes");
+    let pip_options = settings.pip_options();
+    let cache_path = get_hashes_cache_path()?.join(cache_key(&pip_options));
+    let script_path = paths.venv.join(HASHES_SCRIPT_FILENAME);
+    std::fs::write(&script_path, HASHES_SCRIPT).map_err(|e| new_write_error(e, &script_path))?;
+    let script_path = script_path.to_string_lossy();
+    let mut res = Hashes::new();
+    for dep in deps {
+        let cache_file = cache_path
+            .join(dep.name.to_lowercase())
+            .join(format!("{}.txt", dep.version));
+        let hashes = match read_cached_hashes(&cache_file)? {
+            Some(hashes) => hashes,
+            None => {
+                let mut cmd = vec![&script_path, dep.name.as_str(), &dep.version];
+                cmd.extend(pip_options.iter().map(String::as_str));
+                let output = venv_runner.get_output(&cmd)?;
+                let hashes = parse_hashes(&output);
+                write_cached_hashes(&cache_file, &hashes)?;
+                hashes
+            }
+        };
+        res.insert(&dep.name, &dep.version, hashes);
+    }
+    Ok(res)
+}
+
+/// Name of the cache sub-directory for the given pip options
+fn cache_key(pip_options: &[String]) -> String {
+    let digest = Sha256::digest(pip_options.join("\n").as_bytes());
+    format!("{:x}", digest)[..16].to_string()
+}
+
+/// Get the hashes from the output of hashes.py, sorted so that
+/// the lock does not change between runs
+fn parse_hashes(output: &str) -> Vec<String> {
+    let mut res: Vec<_> = output
+        .lines()
+        .map(str::trim)
+        .filter(|l| l.starts_with("sha256:"))
+        .map(String::from)
+        .collect();
+    res.sort();
+    res.dedup();
+    res
+}
+
+// Note: an empty cache file is the same as a missing one
+fn read_cached_hashes(path: &Path) -> Result<Option<Vec<String>>, Error> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = std::fs::read_to_string(path).map_err(|e| new_read_error(e, path))?;
+    let hashes = parse_hashes(&contents);
+    if hashes.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(hashes))
+}
+
+fn write_cached_hashes(path: &Path, hashes: &[String]) -> Result<(), Error> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| new_write_error(e, parent))?;
+    }
+    let mut contents = hashes.join("\n");
+    contents.push('\n');
+    std::fs::write(path, contents).map_err(|e| new_write_error(e, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_key_depends_on_the_index() {
+        let pypi = cache_key(&[]);
+        let other = cache_key(&["--index-url".to_string(), "https://example.com".to_string()]);
+        assert_ne!(pypi, other);
+        assert_eq!(pypi, cache_key(&[]));
+    }
+
+    #[test]
+    fn test_parse_hashes() {
+        assert_eq!(
+            parse_hashes("sha256:def\nsha256:abc\n\nsha256:abc\n"),
+            &["sha256:abc", "sha256:def"]
+        );
+    }
+
+    // Note: the artifacts for every platform are hashed, not only
+    // the ones pip would pick for the current interpreter
+    #[cfg(unix)]
+    #[test]
+    fn test_hashes_script_with_find_links() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let wheelhouse = tmp_dir.path();
+        std::fs::write(wheelhouse.join("Foo_Bar-1.0-cp38-cp38-win_amd64.whl"), "a").unwrap();
+        std::fs::write(
+            wheelhouse.join("foo_bar-1.0-cp38-cp38-manylinux1_x86_64.whl"),
+            "b",
+        )
+        .unwrap();
+        std::fs::write(wheelhouse.join("foo-bar-1.0.tar.gz"), "c").unwrap();
+        std::fs::write(wheelhouse.join("foo_bar-1.1-py3-none-any.whl"), "d").unwrap();
+        let output = std::process::Command::new("python3")
+            .args(["-c", HASHES_SCRIPT, "foo.bar", "1.0"])
+            .args(["--no-index", "--find-links"])
+            .arg(wheelhouse)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let hashes = parse_hashes(&String::from_utf8_lossy(&output.stdout));
+        let mut expected: Vec<_> = ["a", "b", "c"]
+            .iter()
+            .map(|c| format!("sha256:{:x}", Sha256::digest(c.as_bytes())))
+            .collect();
+        expected.sort();
+        assert_eq!(hashes, expected);
+    }
+}
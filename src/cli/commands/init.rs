@@ -9,6 +9,7 @@ pub fn init(
     version: &str,
     author: &Option<String>,
     setup_cfg: bool,
+    pyproject: bool,
 ) -> Result<(), Error> {
     let init_path = if let Some(p) = project_path {
         PathBuf::from(p)
@@ -20,8 +21,11 @@ pub fn init(
     if !setup_cfg {
         init_options.no_setup_cfg();
     };
+    if pyproject {
+        init_options.pyproject();
+    }
     if let Some(author) = author {
-        init_options.author(&author);
+        init_options.author(author);
     }
     operations::init(&init_path, &init_options)
 }
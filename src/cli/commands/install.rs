@@ -1,6 +1,10 @@
 use crate::commands;
 use crate::error::*;
+use crate::markers::Environment;
+use crate::operations;
+use crate::settings::{DEV_PROFILE, PROD_PROFILE};
 use crate::ui::*;
+use crate::wheelhouse;
 use crate::Context;
 use crate::PostInstallAction;
 
@@ -8,10 +12,10 @@ pub fn install(context: &Context, post_install_action: PostInstallAction) -> Res
     let Context {
         settings, paths, ..
     } = context;
-    if settings.production {
-        print_info_1("Preparing project for production")
-    } else {
-        print_info_1("Preparing project for development")
+    match settings.profile.as_str() {
+        PROD_PROFILE => print_info_1("Preparing project for production"),
+        DEV_PROFILE => print_info_1("Preparing project for development"),
+        other => print_info_1(&format!("Preparing project for the '{}' profile", other)),
     };
     let lock_path = &paths.lock;
     if !lock_path.exists() {
@@ -22,6 +26,8 @@ pub fn install(context: &Context, post_install_action: PostInstallAction) -> Res
 
     commands::ensure_venv(context)?;
     install_from_lock(context)?;
+    commands::install_local_dependencies(context)?;
+    commands::record_fingerprint(context)?;
 
     match post_install_action {
         PostInstallAction::RunSetupPyDevelop => commands::develop(context)?,
@@ -30,11 +36,15 @@ pub fn install(context: &Context, post_install_action: PostInstallAction) -> Res
     Ok(())
 }
 
-fn install_from_lock(context: &Context) -> Result<(), Error> {
+pub fn install_from_lock(context: &Context) -> Result<(), Error> {
     let Context {
-        paths, venv_runner, ..
+        paths,
+        settings,
+        venv_runner,
+        ..
     } = context;
     let lock_path = &paths.lock;
+    check_wheelhouse(context)?;
     print_info_2(&format!(
         "Installing dependencies from {}",
         lock_path.display()
@@ -48,6 +58,37 @@ fn install_from_lock(context: &Context) -> Result<(), Error> {
         .unwrap_or_else(|| panic!("self.path.lock has no filename component"));
 
     let as_str = lock_name.to_string_lossy();
-    let cmd = &["python", "-m", "pip", "install", "--requirement", &as_str];
-    venv_runner.run(cmd)
+    let index_options = settings.pip_options();
+    let mut cmd = vec!["python", "-m", "pip", "install", "--requirement", &as_str];
+    cmd.extend(index_options.iter().map(String::as_str));
+    if operations::lock::has_hashes(lock_path)? {
+        cmd.push("--require-hashes");
+    }
+    venv_runner.run(&cmd)
+}
+
+/// In offline mode, make sure the wheelhouse contains every package
+/// of the lock, so that we can tell which ones are missing instead
+/// of letting pip fail on the first one
+pub fn check_wheelhouse(context: &Context) -> Result<(), Error> {
+    let Context {
+        paths,
+        python_info,
+        settings,
+        ..
+    } = context;
+    if !settings.offline || !paths.lock.exists() {
+        return Ok(());
+    }
+    let lock = operations::lock::read(&paths.lock)?;
+    let environment = Environment::from_python_info(python_info);
+    let wheelhouse = paths.project.join(&settings.wheelhouse);
+    let missing = wheelhouse::missing(&wheelhouse, &lock, &environment)?;
+    if missing.is_empty() {
+        return Ok(());
+    }
+    Err(Error::MissingFromWheelhouse {
+        wheelhouse,
+        missing,
+    })
 }
@@ -1,9 +1,17 @@
+use std::path::PathBuf;
+
 use crate::cli::commands;
+use crate::dependencies::FrozenDependency;
 use crate::error::*;
+use crate::lock::{Hashes, Snapshot};
+use crate::markers::Environment;
 use crate::operations;
+use crate::paths::Paths;
+use crate::run::VenvRunner;
 use crate::ui::*;
 use crate::BumpType;
 use crate::Context;
+use crate::DryRun;
 use crate::Metadata;
 use crate::UpdateLockOptions;
 
@@ -11,7 +19,7 @@ use crate::UpdateLockOptions;
 //
 // Notes:
 //
-// * Abort if `setup.py` is not found
+// * Abort if neither `setup.py` nor `pyproject.toml` is found
 // * Create the virtualenv if required
 // * Always upgrade pip :
 //    * If that fails, we know if the virtualenv is broken
@@ -19,19 +27,134 @@ use crate::UpdateLockOptions;
 //      (such as `--local`, `--exclude-editable`) we use in the other functions
 // * The path of the lock file is computed by PathsResolver.
 //     See PathsResolver.paths() for details
-pub fn update_lock(context: &Context, update_options: UpdateLockOptions) -> Result<(), Error> {
+// * Hashes are computed if `--hashes` is used, or if the lock
+//   already contains hashes
+// * With `--offline`, packages of the existing lock must be in the wheelhouse
+pub fn update_lock(
+    context: &Context,
+    update_options: UpdateLockOptions,
+    dry_run: DryRun,
+) -> Result<(), Error> {
     print_info_1("Updating lock");
     let Context { paths, .. } = context;
-    if !&paths.setup_py.exists() {
-        return Err(Error::MissingSetupPy {});
-    }
-    commands::ensure_venv(&context)?;
-    commands::upgrade_pip(&context)?;
-    commands::install_editable(&context)?;
-    let metadata = commands::metadata(&context);
-    let frozen_deps = commands::get_frozen_deps(&context)?;
+    commands::expect_project(context)?;
+    commands::check_wheelhouse(context)?;
+    commands::ensure_venv(context)?;
+    commands::upgrade_pip(context)?;
+    commands::install_editable(context)?;
+    let metadata = commands::metadata(context);
+    let frozen_deps = commands::get_frozen_deps(context)?;
     let lock_path = &paths.lock;
-    operations::lock::update(lock_path, frozen_deps, update_options, &metadata)
+    let hashes = if update_options.hashes || operations::lock::has_hashes(lock_path)? {
+        operations::lock::check_hashable(lock_path)?;
+        commands::compute_hashes(context, &frozen_deps)?
+    } else {
+        Hashes::new()
+    };
+    let environment = Environment::from_python_info(&context.python_info);
+    operations::lock::update(
+        lock_path,
+        frozen_deps,
+        update_options,
+        &environment,
+        &hashes,
+        dry_run,
+        &metadata,
+    )?;
+    // Note: the virtualenv now matches the lock we just wrote
+    if dry_run == DryRun::Off {
+        commands::record_fingerprint(context)?;
+    }
+    Ok(())
+}
+
+/// Check that the lock is up to date, without writing anything
+//
+// Notes:
+//
+// * Dependencies are installed in a throwaway virtualenv, so that
+//   the result does not depend on the state of the project's virtualenv
+// * Like in `tidy()`, the installation is constrained by the existing lock,
+//   so that only changes in the project dependencies cause the lock to change
+pub fn check_lock(context: &Context, update_options: UpdateLockOptions) -> Result<(), Error> {
+    print_info_1("Checking lock");
+    let Context { paths, .. } = context;
+    commands::expect_project(context)?;
+    // Note: include the project name, so that members of a workspace
+    // can be checked in parallel
+    let project_name = paths.project.file_name().unwrap_or_default();
+    let venv_path = std::env::temp_dir().join(format!(
+        "dmenv-check-{}-{}",
+        std::process::id(),
+        project_name.to_string_lossy()
+    ));
+    let check_context = Context {
+        paths: Paths {
+            venv: venv_path.clone(),
+            ..paths.clone()
+        },
+        python_info: context.python_info.clone(),
+        settings: context.settings.clone(),
+        venv_runner: VenvRunner::new(&paths.project, &venv_path),
+        workspace: context.workspace.clone(),
+    };
+    let res = check_in_venv(&check_context, update_options);
+    commands::clean_venv(check_context)?;
+    res
+}
+
+fn check_in_venv(context: &Context, update_options: UpdateLockOptions) -> Result<(), Error> {
+    let Context { paths, .. } = context;
+    commands::create_venv(context)?;
+    commands::upgrade_pip(context)?;
+    if paths.lock.exists() {
+        commands::install_editable_with_constraint(context)?;
+    } else {
+        commands::install_editable(context)?;
+    }
+    let frozen_deps = commands::get_frozen_deps(context)?;
+    let hashes = if update_options.hashes || operations::lock::has_hashes(&paths.lock)? {
+        operations::lock::check_hashable(&paths.lock)?;
+        commands::compute_hashes(context, &frozen_deps)?
+    } else {
+        Hashes::new()
+    };
+    let environment = Environment::from_python_info(&context.python_info);
+    let metadata = commands::metadata(context);
+    operations::lock::check(
+        &paths.lock,
+        frozen_deps,
+        update_options,
+        &environment,
+        &hashes,
+        &metadata,
+    )
+}
+
+/// Merge snapshots written by `dmenv freeze` on several
+/// platforms or Python versions into the lock file
+// Note: this does not need the virtualenv, so that the lock for
+// all the targets can be generated from a single machine
+pub fn merge_lock(
+    context: &Context,
+    snapshot_paths: &[PathBuf],
+    dry_run: DryRun,
+) -> Result<(), Error> {
+    print_info_1("Merging snapshots into lock");
+    let metadata = commands::metadata(context);
+    let Context { paths, .. } = context;
+    operations::lock::merge(&paths.lock, snapshot_paths, dry_run, &metadata)
+}
+
+/// Print the output of `pip freeze`, along with the values of the
+/// marker variables for the current interpreter
+pub fn freeze(context: &Context) -> Result<(), Error> {
+    commands::expect_venv_as_is(context)?;
+    let frozen_deps = commands::get_frozen_deps(context)?;
+    let environment = Environment::from_python_info(&context.python_info);
+    let snapshot = Snapshot::new(&environment, frozen_deps);
+    print!("{}", snapshot.dump());
+    Ok(())
 }
 
 /// Bump a dependency in the lock file
@@ -40,21 +163,54 @@ pub fn bump_in_lock(
     name: &str,
     version: &str,
     bump_type: BumpType,
+    dry_run: DryRun,
 ) -> Result<(), Error> {
     print_info_1(&format!("Bumping {} to {} ...", name, version));
-    let metadata = commands::metadata(&context);
+    let metadata = commands::metadata(context);
     let Context { paths, .. } = context;
-    operations::lock::bump(&paths.lock, name, version, bump_type, &metadata)
+    // Hashes of the previous version are dropped when bumping, so
+    // make sure to compute the new ones if the lock has hashes
+    let hashes = match bump_type {
+        BumpType::Simple if operations::lock::has_hashes(&paths.lock)? => {
+            commands::expect_venv(context)?;
+            commands::compute_hashes(context, &[FrozenDependency::new(name, version)])?
+        }
+        _ => Hashes::new(),
+    };
+    operations::lock::bump(
+        &paths.lock,
+        name,
+        version,
+        bump_type,
+        &hashes,
+        dry_run,
+        &metadata,
+    )
 }
 
 pub fn metadata(context: &Context) -> Metadata {
-    let Context { python_info, .. } = context;
+    let Context {
+        python_info,
+        settings,
+        ..
+    } = context;
     let dmenv_version = env!("CARGO_PKG_VERSION");
-    let python_platform = &python_info.platform;
-    let python_version = &python_info.version;
+    let python_platform = format!(
+        "{} {}",
+        &python_info.platform, &python_info.platform_machine
+    );
+    let python_version = format!(
+        "{} ({})",
+        &python_info.version, &python_info.platform_python_implementation
+    );
     Metadata {
         dmenv_version: dmenv_version.to_string(),
         python_platform: python_platform.to_string(),
         python_version: python_version.to_string(),
+        lock_options: if settings.record_index_options {
+            Some(settings.lock_options())
+        } else {
+            None
+        },
     }
 }
@@ -1,21 +1,42 @@
 mod develop;
+mod download;
+mod hashes;
 mod init;
 mod install;
 mod lock;
 mod pip;
+mod project;
 mod run;
 mod scripts;
+mod shell;
 mod show;
+mod sync;
+mod task;
 mod tidy;
 mod venv;
 
 pub use develop::develop;
+pub use download::download;
+pub use hashes::compute_hashes;
 pub use init::init;
-pub use install::install;
-pub use lock::{bump_in_lock, metadata, update_lock};
-pub use pip::{get_frozen_deps, install_editable, install_editable_with_constraint, upgrade_pip};
-pub use run::{run, run_and_die};
+pub use install::{check_wheelhouse, install, install_from_lock};
+pub use lock::{bump_in_lock, check_lock, freeze, merge_lock, metadata, update_lock};
+pub use pip::{
+    get_frozen_deps, install_editable, install_editable_with_constraint,
+    install_local_dependencies, upgrade_pip,
+};
+pub use project::{expect_project, project_files, shared_members};
+pub use run::{get_runner, python_cmd, run, run_and_die};
 pub use scripts::process_scripts;
-pub use show::{show_deps, show_outdated, show_venv_bin_path, show_venv_path};
+pub use shell::{print_env, shell};
+pub use show::{
+    show_config, show_deps, show_info, show_lock_drift, show_outdated, show_pythons,
+    show_venv_bin_path, show_venv_path,
+};
+pub use sync::sync;
+pub use task::{list_tasks, run_task};
 pub use tidy::tidy;
-pub use venv::{clean_venv, create_venv, ensure_venv, expect_venv};
+pub use venv::{
+    check_venv_fingerprint, clean_venv, create_venv, ensure_venv, expect_venv, expect_venv_as_is,
+    record_fingerprint,
+};
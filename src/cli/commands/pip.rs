@@ -1,13 +1,23 @@
+use std::path::PathBuf;
+
+use crate::cli::commands;
 use crate::dependencies::FrozenDependency;
 use crate::error::*;
+use crate::project::ProjectKind;
 use crate::ui::*;
 use crate::Context;
 
 pub fn upgrade_pip(context: &Context) -> Result<(), Error> {
-    let Context { venv_runner, .. } = context;
+    let Context {
+        settings,
+        venv_runner,
+        ..
+    } = context;
     print_info_2("Upgrading pip");
-    let cmd = &["python", "-m", "pip", "install", "pip", "--upgrade"];
-    venv_runner.run(cmd).map_err(|_| Error::UpgradePipError {})
+    let index_options = settings.pip_options();
+    let mut cmd = vec!["python", "-m", "pip", "install", "pip", "--upgrade"];
+    cmd.extend(index_options.iter().map(String::as_str));
+    venv_runner.run(&cmd).map_err(|_| Error::UpgradePipError {})
 }
 
 pub fn install_editable(context: &Context) -> Result<(), Error> {
@@ -16,14 +26,14 @@ pub fn install_editable(context: &Context) -> Result<(), Error> {
         venv_runner,
         ..
     } = context;
-    let mut message = "Installing deps from setup.py".to_string();
-    if settings.production {
-        message.push_str(" using 'prod' extra dependencies");
-    } else {
-        message.push_str(" using 'dev' extra dependencies");
-    }
+    let message = format!(
+        "Installing deps from {} using '{}' extra dependencies",
+        describe_project(context)?,
+        settings.profile().extras.join(", ")
+    );
     print_info_2(&message);
-    let cmd = get_install_editable_cmd(&context);
+    let cmd = get_install_editable_cmd(context);
+    let cmd: Vec<_> = cmd.iter().map(String::as_str).collect();
     venv_runner.run(&cmd)
 }
 
@@ -33,29 +43,104 @@ pub fn install_editable_with_constraint(context: &Context) -> Result<(), Error>
     } = context;
     let lock_path = &paths.lock;
     let message = format!(
-        "Installing deps from setup.py, constrained by {}",
+        "Installing deps from {}, constrained by {}",
+        describe_project(context)?,
         lock_path.display()
     );
     print_info_2(&message);
-    let lock_path_str = lock_path.to_string_lossy();
-    let mut cmd = get_install_editable_cmd(&context).to_vec();
-    cmd.extend(&["--constraint", &lock_path_str]);
+    let mut cmd = get_install_editable_cmd(context);
+    cmd.push("--constraint".to_string());
+    cmd.push(lock_path.to_string_lossy().to_string());
+    let cmd: Vec<_> = cmd.iter().map(String::as_str).collect();
     venv_runner.run(&cmd)
 }
 
-fn get_install_editable_cmd(context: &Context) -> [&str; 6] {
+/// Where the dependencies come from, for the messages of `install_editable()`
+fn describe_project(context: &Context) -> Result<String, Error> {
+    let Context { paths, .. } = context;
+    if let Some(members) = commands::shared_members(context) {
+        return Ok(format!("{} workspace members", members.len()));
+    }
+    let project_kind = ProjectKind::expect(&paths.project)?;
+    Ok(project_kind.file_name().to_string())
+}
+
+fn get_install_editable_cmd(context: &Context) -> Vec<String> {
     let Context { settings, .. } = context;
-    let extra = if settings.production {
-        ".[prod]"
-    } else {
-        ".[dev]"
+    let extras = settings.profile().extras;
+    let with_extras = |path: &str| {
+        if extras.is_empty() {
+            path.to_string()
+        } else {
+            format!("{}[{}]", path, extras.join(","))
+        }
+    };
+    let mut res: Vec<_> = ["python", "-m", "pip", "install"]
+        .iter()
+        .map(|x| x.to_string())
+        .collect();
+    // Note: with a shared virtualenv, the lock is generated from the
+    // union of the dependencies of all the members, using the same extras
+    let specs = match commands::shared_members(context) {
+        Some(members) => members
+            .iter()
+            .map(|p| with_extras(&p.to_string_lossy()))
+            .collect(),
+        None => vec![with_extras(".")],
     };
-    ["python", "-m", "pip", "install", "--editable", extra]
+    for spec in specs {
+        res.push("--editable".to_string());
+        res.push(spec);
+    }
+    // Note: this way, pip uses the local checkout of the workspace members
+    // the project depends on, instead of looking for them in the index
+    for path in local_dependencies(context) {
+        res.push("--editable".to_string());
+        res.push(path.to_string_lossy().to_string());
+    }
+    res.extend(settings.pip_options());
+    res
+}
+
+/// Install the workspace members the project depends on, in editable mode.
+//
+// Note: they are not in the lock, because `pip freeze` is called
+// with `--exclude-editable`, but their own dependencies are.
+pub fn install_local_dependencies(context: &Context) -> Result<(), Error> {
+    let Context {
+        settings,
+        venv_runner,
+        ..
+    } = context;
+    let paths = local_dependencies(context);
+    if paths.is_empty() {
+        return Ok(());
+    }
+    print_info_2("Installing workspace dependencies");
+    let mut cmd = vec!["python", "-m", "pip", "install", "--no-deps"];
+    let paths: Vec<_> = paths.iter().map(|p| p.to_string_lossy()).collect();
+    for path in &paths {
+        cmd.push("--editable");
+        cmd.push(path);
+    }
+    let pip_options = settings.pip_options();
+    cmd.extend(pip_options.iter().map(String::as_str));
+    venv_runner.run(&cmd)
+}
+
+fn local_dependencies(context: &Context) -> Vec<PathBuf> {
+    let Context {
+        paths, workspace, ..
+    } = context;
+    match workspace {
+        None => vec![],
+        Some(workspace) => workspace.local_dependencies(&paths.project),
+    }
 }
 
 /// Get the list of the *actual* deps in the virtualenv by calling `pip freeze`.
 pub fn get_frozen_deps(context: &Context) -> Result<Vec<FrozenDependency>, Error> {
-    let freeze_output = run_pip_freeze(&context)?;
+    let freeze_output = run_pip_freeze(context)?;
     // First, collect all the `pip freeze` lines into frozen dependencies
     let deps: Result<Vec<_>, _> = freeze_output
         .lines()
@@ -0,0 +1,45 @@
+use std::path::PathBuf;
+
+use crate::error::*;
+use crate::project::ProjectKind;
+use crate::Context;
+
+/// When the virtualenv is shared by the members of a workspace,
+/// return their paths
+//
+// Note: in this case, `paths.project` is the root of the workspace,
+// see `get_context()`
+pub fn shared_members(context: &Context) -> Option<Vec<PathBuf>> {
+    let Context {
+        paths, workspace, ..
+    } = context;
+    workspace
+        .as_ref()
+        .filter(|w| w.shared_venv && w.root == paths.project)
+        .map(|w| w.member_paths())
+}
+
+/// Make sure there is something to install in the virtualenv: either
+/// a `setup.py` or `pyproject.toml` file, or the members of a workspace
+/// sharing the virtualenv
+pub fn expect_project(context: &Context) -> Result<(), Error> {
+    let Context { paths, .. } = context;
+    if shared_members(context).is_some() {
+        return Ok(());
+    }
+    ProjectKind::expect(&paths.project)?;
+    Ok(())
+}
+
+/// Files describing the dependencies of the project, see `record_fingerprint()`
+pub fn project_files(context: &Context) -> Vec<PathBuf> {
+    let Context { paths, .. } = context;
+    let project_paths = shared_members(context).unwrap_or_else(|| vec![paths.project.clone()]);
+    let mut res = vec![];
+    for project_path in project_paths {
+        for name in &["setup.py", "setup.cfg", "pyproject.toml"] {
+            res.push(project_path.join(name));
+        }
+    }
+    res
+}
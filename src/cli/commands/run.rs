@@ -1,23 +1,94 @@
+use std::path::PathBuf;
+
 use crate::cli::commands;
+use crate::dotenv;
 use crate::error::*;
+use crate::run::VenvRunner;
 use crate::Context;
+use crate::PostInstallAction;
+
+/// Name of the file, in the project directory, read by `dmenv run`
+const DOTENV_FILENAME: &str = ".env";
 
 /// Run a program from the virtualenv, making sure it dies
 /// when we get killed and that the exit code is forwarded
-pub fn run_and_die<T: AsRef<str>>(context: &Context, cmd: &[T]) -> Result<(), Error> {
-    let Context { venv_runner, .. } = context;
-    commands::expect_venv(&context)?;
-    venv_runner.run_and_die(cmd)
+pub fn run_and_die<T: AsRef<str>>(
+    context: &Context,
+    cmd: &[T],
+    env_files: &[PathBuf],
+) -> Result<(), Error> {
+    commands::expect_venv(context)?;
+    commands::check_venv_fingerprint(context, PostInstallAction::RunSetupPyDevelop)?;
+    get_runner(context, env_files)?.run_and_die(cmd)
 }
 
 /// On Windows:
 ///   - same as run
+///
 /// On Linux:
 ///   - same as run, but create a new process instead of using execv()
 // Note: mostly for tests. We want to *check* the return code of
 // `dmenv run` and so we need a child process
-pub fn run<T: AsRef<str>>(context: &Context, cmd: &[T]) -> Result<(), Error> {
-    let Context { venv_runner, .. } = context;
-    commands::expect_venv(&context)?;
-    venv_runner.run(cmd)
+pub fn run<T: AsRef<str>>(
+    context: &Context,
+    cmd: &[T],
+    env_files: &[PathBuf],
+) -> Result<(), Error> {
+    commands::expect_venv(context)?;
+    commands::check_venv_fingerprint(context, PostInstallAction::RunSetupPyDevelop)?;
+    get_runner(context, env_files)?
+        .forwarding_signals()
+        .run(cmd)
+}
+
+/// The command to run for `dmenv run -m <module>` and `dmenv run -c <code>`,
+/// `args` being the rest of the command line. Return `args` as is otherwise
+pub fn python_cmd(module: Option<&str>, code: Option<&str>, args: &[String]) -> Vec<String> {
+    let mut res = vec![];
+    if let Some(module) = module {
+        res = vec!["python".to_string(), "-m".to_string(), module.to_string()];
+    }
+    if let Some(code) = code {
+        res = vec!["python".to_string(), "-c".to_string(), code.to_string()];
+    }
+    res.extend(args.iter().cloned());
+    res
+}
+
+/// Return a runner setting the variables from the `.env` file of the
+/// project, if any, then from the files given with `--env-file`
+//
+// Note: variables can refer to the ones set by the previous lines,
+// and to the environment of the virtualenv, so that something like
+// `PATH=${PATH}:/opt/bin` works
+pub fn get_runner(context: &Context, env_files: &[PathBuf]) -> Result<VenvRunner, Error> {
+    let Context {
+        paths, venv_runner, ..
+    } = context;
+    let mut files = vec![];
+    let dotenv_path = paths.project.join(DOTENV_FILENAME);
+    if dotenv_path.exists() {
+        files.push(dotenv_path);
+    }
+    files.extend(env_files.iter().cloned());
+    if files.is_empty() {
+        return Ok(venv_runner.clone());
+    }
+
+    let activated = venv_runner.env_vars()?;
+    let mut vars: Vec<(String, String)> = vec![];
+    for file in files {
+        let lookup = |name: &str| {
+            if let Some((_, value)) = vars.iter().rev().find(|(n, _)| n == name) {
+                return Some(value.to_string());
+            }
+            if let Some((_, value)) = activated.iter().find(|(n, _)| n == name) {
+                return value.as_ref().map(|v| v.to_string_lossy().to_string());
+            }
+            std::env::var(name).ok()
+        };
+        let new_vars = dotenv::read(&file, lookup)?;
+        vars.extend(new_vars);
+    }
+    Ok(venv_runner.with_env(vars))
 }
@@ -0,0 +1,64 @@
+use crate::activate::Shell;
+use crate::cli::commands;
+use crate::error::*;
+#[cfg(unix)]
+use crate::execv::execv;
+use crate::ui::*;
+use crate::Context;
+
+/// Spawn an interactive shell, with the virtualenv activated.
+//
+// Note: on Unix, like `dmenv run`, this uses execv() so that dmenv does
+// not stay around while the shell is running
+pub fn shell(context: &Context) -> Result<(), Error> {
+    commands::expect_venv(context)?;
+    let Context { venv_runner, .. } = context;
+    let activation = venv_runner.activation();
+    let changes = venv_runner.env_vars()?;
+    for (name, value) in changes {
+        match value {
+            Some(value) => std::env::set_var(name, value),
+            None => std::env::remove_var(name),
+        }
+    }
+    print_info_1(&format!(
+        "Spawning a shell in {}. Exit the shell to deactivate the virtualenv",
+        activation.venv_path.display()
+    ));
+
+    #[cfg(unix)]
+    {
+        let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+        // Note: PS1 is usually not exported, so this only works if the
+        // shell configuration does not set it
+        let default_prompt = match Shell::detect(Some(&shell)) {
+            Shell::Zsh => "%~ %# ",
+            _ => "\\w \\$ ",
+        };
+        let prompt = std::env::var("PS1").unwrap_or_else(|_| default_prompt.to_string());
+        std::env::set_var("PS1", format!("{}{}", activation.prompt_prefix(), prompt));
+        execv(&shell, &[&shell])
+    }
+
+    #[cfg(windows)]
+    {
+        let shell = std::env::var("COMSPEC").unwrap_or_else(|_| "cmd.exe".to_string());
+        let status = std::process::Command::new(&shell)
+            .status()
+            .map_err(|e| Error::WaitProcessError { io_error: e })?;
+        if !status.success() {
+            return Err(new_process_error(std::path::Path::new(&shell), status));
+        }
+        Ok(())
+    }
+}
+
+/// Print the shell code activating the virtualenv, so that
+/// it can be used with `eval "$(dmenv env)"`
+pub fn print_env(context: &Context, shell: Option<Shell>) -> Result<(), Error> {
+    commands::expect_venv_as_is(context)?;
+    let shell = shell.unwrap_or_else(|| Shell::detect(std::env::var("SHELL").ok().as_deref()));
+    let Context { venv_runner, .. } = context;
+    print!("{}", venv_runner.activation().script(shell));
+    Ok(())
+}
@@ -1,41 +1,391 @@
+use colored::*;
+use serde::{Deserialize, Serialize};
+
 use crate::cli::commands;
 use crate::error::*;
+use crate::interpreters;
+use crate::lock::{drift, Drift};
+use crate::markers::{compare_values, Environment};
+use crate::operations;
+use crate::python_info::PythonInfo;
+use crate::ui::*;
 use crate::Context;
+use crate::OutputFormat;
+use crate::PostInstallAction;
+
+// Note: the structs below define the schemas of the JSON output
+// of the `show:*` commands. They are used by other tools, so make
+// sure to only add new fields to them
+
+#[derive(Serialize)]
+struct InstalledPackage {
+    name: String,
+    version: String,
+    // Version of the line of the lock that applies to the current
+    // interpreter, if any
+    locked_version: Option<String>,
+    matches_lock: bool,
+}
+
+#[derive(Serialize)]
+struct OutdatedPackage {
+    name: String,
+    version: String,
+    latest_version: String,
+}
+
+#[derive(Serialize)]
+struct PythonJson {
+    binary: String,
+    version: String,
+    platform: String,
+    executable: String,
+    implementation: String,
+    sys_platform: String,
+    platform_machine: String,
+    pip_version: Option<String>,
+}
+
+impl PythonJson {
+    fn new(python_info: &PythonInfo) -> Self {
+        PythonJson {
+            binary: python_info.binary.to_string_lossy().to_string(),
+            version: python_info.version.to_string(),
+            platform: python_info.platform.to_string(),
+            executable: python_info.executable.to_string_lossy().to_string(),
+            implementation: python_info.platform_python_implementation.to_string(),
+            sys_platform: python_info.sys_platform.to_string(),
+            platform_machine: python_info.platform_machine.to_string(),
+            pip_version: python_info.pip_version.clone(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct InfoJson {
+    python: PythonJson,
+    venv_path: String,
+    lock_path: String,
+}
+
+#[derive(Serialize)]
+struct ConfigEntry {
+    key: String,
+    value: String,
+    source: String,
+}
+
+#[derive(Serialize)]
+struct DriftPackage {
+    name: String,
+    // None for packages that are not in the lock
+    locked_version: Option<String>,
+    // None for packages that are not installed
+    installed_version: Option<String>,
+}
+
+#[derive(Serialize, Default)]
+struct LockDriftJson {
+    lock_path: String,
+    missing: Vec<DriftPackage>,
+    extra: Vec<DriftPackage>,
+    wrong_version: Vec<DriftPackage>,
+}
+
+// What `pip list --format json` prints
+#[derive(Deserialize)]
+struct PipPackage {
+    name: String,
+    version: String,
+    #[serde(default)]
+    latest_version: Option<String>,
+}
+
+/// The JSON output is meant to be parsed, so don't re-create
+/// a stale virtualenv in this case, see `expect_venv_as_is()`
+fn expect_venv_for(context: &Context, format: OutputFormat) -> Result<(), Error> {
+    match format {
+        OutputFormat::Text => commands::expect_venv(context),
+        OutputFormat::Json => commands::expect_venv_as_is(context),
+    }
+}
 
 /// Show the dependencies inside the virtualenv.
 // Note: Run `pip list` so we get what's *actually* installed, not just
 // the contents of the lock file
-pub fn show_deps(context: &Context) -> Result<(), Error> {
-    let Context { venv_runner, .. } = context;
-    venv_runner.run(&["python", "-m", "pip", "list"])
+pub fn show_deps(context: &Context, format: OutputFormat) -> Result<(), Error> {
+    let Context {
+        paths,
+        python_info,
+        venv_runner,
+        ..
+    } = context;
+    expect_venv_for(context, format)?;
+    commands::check_venv_fingerprint(context, PostInstallAction::RunSetupPyDevelop)?;
+    if format == OutputFormat::Text {
+        return venv_runner.run(&["python", "-m", "pip", "list"]);
+    }
+    let installed = pip_list(context, &[])?;
+    let lock = operations::lock::read(&paths.lock)?;
+    let environment = Environment::from_python_info(python_info);
+    let packages: Vec<_> = installed
+        .into_iter()
+        .map(|PipPackage { name, version, .. }| {
+            let locked_version = lock
+                .dependencies()
+                .filter(|d| d.applies_to(&environment))
+                .find(|d| d.name().to_lowercase() == name.to_lowercase())
+                .map(|d| d.version());
+            let matches_lock = locked_version.as_ref() == Some(&version);
+            InstalledPackage {
+                name,
+                version,
+                locked_version,
+                matches_lock,
+            }
+        })
+        .collect();
+    print_json(&packages)
 }
 
-pub fn show_outdated(context: &Context) -> Result<(), Error> {
+pub fn show_outdated(context: &Context, format: OutputFormat) -> Result<(), Error> {
     let Context { venv_runner, .. } = context;
-    #[rustfmt::skip]
-    let cmd = &[
-        "python", "-m", "pip",
-        "list", "--outdated",
-        "--format", "columns",
-    ];
-    venv_runner.run(cmd)
+    expect_venv_for(context, format)?;
+    commands::check_venv_fingerprint(context, PostInstallAction::RunSetupPyDevelop)?;
+    if format == OutputFormat::Text {
+        #[rustfmt::skip]
+        let cmd = &[
+            "python", "-m", "pip",
+            "list", "--outdated",
+            "--format", "columns",
+        ];
+        return venv_runner.run(cmd);
+    }
+    let outdated = pip_list(context, &["--outdated"])?;
+    let packages: Vec<_> = outdated
+        .into_iter()
+        .map(|p| OutdatedPackage {
+            name: p.name,
+            version: p.version,
+            latest_version: p.latest_version.unwrap_or_default(),
+        })
+        .collect();
+    print_json(&packages)
+}
+
+/// Compare the output of `pip freeze` with the lines of the lock
+/// that apply to the current interpreter.
+// Note: return an error if they differ, so that this can be
+// used to make sure CI runs with the locked dependencies
+pub fn show_lock_drift(context: &Context, format: OutputFormat) -> Result<(), Error> {
+    let Context {
+        paths, python_info, ..
+    } = context;
+    commands::expect_venv_as_is(context)?;
+    if !paths.lock.exists() {
+        return Err(Error::MissingLock {
+            expected_path: paths.lock.to_path_buf(),
+        });
+    }
+    let lock = operations::lock::read(&paths.lock)?;
+    let frozen_deps = commands::get_frozen_deps(context)?;
+    let environment = Environment::from_python_info(python_info);
+    let drifts = drift(&lock, &frozen_deps, &environment);
+    match format {
+        OutputFormat::Text => print_drift_table(&drifts),
+        OutputFormat::Json => print_drift_json(&paths.lock.to_string_lossy(), &drifts)?,
+    }
+    if !drifts.is_empty() {
+        return Err(Error::LockDrift {
+            lock_path: paths.lock.to_path_buf(),
+        });
+    }
+    Ok(())
+}
+
+fn print_drift_table(drifts: &[Drift]) {
+    if drifts.is_empty() {
+        println!("{}", "ok!".green());
+        return;
+    }
+    let mut rows = vec![vec![
+        "status".to_string(),
+        "name".to_string(),
+        "locked".to_string(),
+        "installed".to_string(),
+    ]];
+    for drift in drifts {
+        let row = match drift {
+            Drift::Missing { name, locked } => vec!["missing", name, locked, ""],
+            Drift::Extra { name, installed } => vec!["extra", name, "", installed],
+            Drift::WrongVersion {
+                name,
+                locked,
+                installed,
+            } => vec!["wrong version", name, locked, installed],
+        };
+        rows.push(row.into_iter().map(|x| x.to_string()).collect());
+    }
+    print!("{}", format_table(&rows));
+}
+
+fn print_drift_json(lock_path: &str, drifts: &[Drift]) -> Result<(), Error> {
+    let mut res = LockDriftJson {
+        lock_path: lock_path.to_string(),
+        ..Default::default()
+    };
+    let package = |name: &str, locked: Option<&String>, installed: Option<&String>| DriftPackage {
+        name: name.to_string(),
+        locked_version: locked.cloned(),
+        installed_version: installed.cloned(),
+    };
+    for drift in drifts {
+        match drift {
+            Drift::Missing { name, locked } => res.missing.push(package(name, Some(locked), None)),
+            Drift::Extra { name, installed } => {
+                res.extra.push(package(name, None, Some(installed)))
+            }
+            Drift::WrongVersion {
+                name,
+                locked,
+                installed,
+            } => res
+                .wrong_version
+                .push(package(name, Some(locked), Some(installed))),
+        }
+    }
+    print_json(&res)
+}
+
+/// Show the Python interpreter used by dmenv, along with the
+/// virtualenv and lock paths
+//
+// See `PathsResolver.paths()` for details
+pub fn show_info(context: &Context, format: OutputFormat) -> Result<(), Error> {
+    let Context {
+        paths, python_info, ..
+    } = context;
+    match format {
+        OutputFormat::Text => {
+            println!(
+                "python: {} ({} {}, {} {})",
+                python_info.binary.display(),
+                python_info.platform_python_implementation,
+                python_info.version,
+                python_info.platform,
+                python_info.platform_machine
+            );
+            println!("virtualenv: {}", paths.venv.display());
+            println!("lock: {}", paths.lock.display());
+            Ok(())
+        }
+        OutputFormat::Json => print_json(&InfoJson {
+            python: PythonJson::new(python_info),
+            venv_path: paths.venv.to_string_lossy().to_string(),
+            lock_path: paths.lock.to_string_lossy().to_string(),
+        }),
+    }
+}
+
+/// Show the effective value of every setting, and where it comes from
+//
+// See `Settings::load()` for details
+pub fn show_config(context: &Context, format: OutputFormat) -> Result<(), Error> {
+    let Context { settings, .. } = context;
+    let entries: Vec<_> = settings
+        .entries()
+        .into_iter()
+        .map(|(key, value)| ConfigEntry {
+            key: key.to_string(),
+            value,
+            source: settings.source(key).to_string(),
+        })
+        .collect();
+    match format {
+        OutputFormat::Text => {
+            let mut rows = vec![vec![
+                "key".to_string(),
+                "value".to_string(),
+                "source".to_string(),
+            ]];
+            for ConfigEntry { key, value, source } in entries {
+                rows.push(vec![key, value, source]);
+            }
+            print!("{}", format_table(&rows));
+            Ok(())
+        }
+        OutputFormat::Json => print_json(&entries),
+    }
+}
+
+/// Show all the Python interpreters found by dmenv, newest first
+//
+// See `interpreters::discover()` for details
+pub fn show_pythons(format: OutputFormat) -> Result<(), Error> {
+    let mut pythons = interpreters::discover();
+    pythons.sort_by(|a, b| compare_values(&b.version, &a.version));
+    let pythons: Vec<_> = pythons.into_iter().map(|p| PythonJson::new(&p)).collect();
+    match format {
+        OutputFormat::Text => {
+            let mut rows = vec![];
+            for python in pythons {
+                rows.push(vec![
+                    python.implementation,
+                    python.version,
+                    format!("{} {}", python.platform, python.platform_machine),
+                    python.binary,
+                ]);
+            }
+            print!("{}", format_table(&rows));
+            Ok(())
+        }
+        OutputFormat::Json => print_json(&pythons),
+    }
 }
 
 /// Show the resolved virtualenv path.
 //
 // See `PathsResolver.paths()` for details
-pub fn show_venv_path(context: &Context) -> Result<(), Error> {
+pub fn show_venv_path(context: &Context, format: OutputFormat) -> Result<(), Error> {
     let Context { paths, .. } = context;
-    println!("{}", paths.venv.display());
+    match format {
+        OutputFormat::Text => println!("{}", paths.venv.display()),
+        OutputFormat::Json => {
+            let venv_path = paths.venv.to_string_lossy();
+            print_json(&serde_json::json!({ "venv_path": venv_path }))?
+        }
+    }
     Ok(())
 }
 
 /// Same has `show_venv_path`, but add the correct subfolder
 /// (`bin` on Linux and macOS, `Scripts` on Windows).
-pub fn show_venv_bin_path(context: &Context) -> Result<(), Error> {
+pub fn show_venv_bin_path(context: &Context, format: OutputFormat) -> Result<(), Error> {
     let Context { venv_runner, .. } = context;
-    commands::expect_venv(&context)?;
+    commands::expect_venv_as_is(context)?;
     let bin_path = venv_runner.binaries_path();
-    println!("{}", bin_path.display());
+    match format {
+        OutputFormat::Text => println!("{}", bin_path.display()),
+        OutputFormat::Json => {
+            let bin_path = bin_path.to_string_lossy();
+            print_json(&serde_json::json!({ "bin_path": bin_path }))?
+        }
+    }
+    Ok(())
+}
+
+fn pip_list(context: &Context, options: &[&str]) -> Result<Vec<PipPackage>, Error> {
+    let Context { venv_runner, .. } = context;
+    let mut cmd = vec!["python", "-m", "pip", "list", "--format", "json"];
+    cmd.extend(options);
+    let output = venv_runner.get_output(&cmd)?;
+    serde_json::from_str(&output)
+        .map_err(|e| new_error(format!("could not parse `pip list` output: {}", e)))
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<(), Error> {
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|e| new_error(format!("could not serialize to JSON: {}", e)))?;
+    println!("{}", json);
     Ok(())
 }
@@ -0,0 +1,93 @@
+use colored::*;
+
+use crate::cli::commands;
+use crate::error::*;
+use crate::lock::{drift, Drift};
+use crate::markers::Environment;
+use crate::operations;
+use crate::ui::*;
+use crate::Context;
+
+// Those are needed by dmenv itself, so never remove them
+// Note: the editable project is not listed in `pip freeze`
+// output, so it's never removed either
+const KEPT_PACKAGES: &[&str] = &["pip", "setuptools", "wheel"];
+
+/// Make the virtualenv match the lock, without re-creating it
+//
+// Notes:
+//  * Missing packages and packages at the wrong version are
+//    installed by running `pip install` on the whole lock, so that
+//    markers and hashes are handled by pip
+//  * Extra packages are removed with `pip uninstall`
+pub fn sync(context: &Context) -> Result<(), Error> {
+    let Context {
+        paths,
+        python_info,
+        venv_runner,
+        ..
+    } = context;
+    print_info_1(&format!("Syncing virtualenv with {}", paths.lock.display()));
+    commands::expect_venv(context)?;
+    if !paths.lock.exists() {
+        return Err(Error::MissingLock {
+            expected_path: paths.lock.to_path_buf(),
+        });
+    }
+    let lock = operations::lock::read(&paths.lock)?;
+    let frozen_deps = commands::get_frozen_deps(context)?;
+    let environment = Environment::from_python_info(python_info);
+    let drifts: Vec<_> = drift(&lock, &frozen_deps, &environment)
+        .into_iter()
+        .filter(|d| match d {
+            Drift::Extra { name, .. } => !KEPT_PACKAGES.contains(&name.to_lowercase().as_str()),
+            _ => true,
+        })
+        .collect();
+    if drifts.is_empty() {
+        print_progress(&"Already in sync".green().to_string());
+        return commands::record_fingerprint(context);
+    }
+
+    let extra: Vec<_> = drifts
+        .iter()
+        .filter_map(|d| match d {
+            Drift::Extra { name, .. } => Some(name.as_str()),
+            _ => None,
+        })
+        .collect();
+    if !extra.is_empty() {
+        print_info_2("Removing extra packages");
+        let mut cmd = vec!["python", "-m", "pip", "uninstall", "--yes"];
+        cmd.extend(&extra);
+        venv_runner.run(&cmd)?;
+    }
+
+    let needs_install = drifts.iter().any(|d| !matches!(d, Drift::Extra { .. }));
+    if needs_install {
+        commands::install_from_lock(context)?;
+    }
+
+    commands::record_fingerprint(context)?;
+
+    print_info_2("Summary");
+    print_summary(&drifts);
+    Ok(())
+}
+
+fn print_summary(drifts: &[Drift]) {
+    let mut rows = vec![];
+    for drift in drifts {
+        let row = match drift {
+            Drift::Missing { name, locked } => vec!["installed", name, "", locked],
+            Drift::Extra { name, installed } => vec!["removed", name, installed, ""],
+            Drift::WrongVersion {
+                name,
+                locked,
+                installed,
+            } => vec!["reinstalled", name, installed, locked],
+        };
+        rows.push(row.into_iter().map(|x| x.to_string()).collect());
+    }
+    print_progress(format_table(&rows).trim_end());
+}
@@ -0,0 +1,78 @@
+use crate::cli::commands;
+use crate::error::*;
+use crate::tasks::{split_command, Tasks};
+use crate::ui::*;
+use crate::Context;
+use crate::PostInstallAction;
+
+/// Run the given task, after the tasks it depends on. `args` are
+/// appended to the last command of the task.
+// Note: commands are run from the virtualenv, with the variables from
+// the `.env` file of the project set, like with `dmenv run`
+pub fn run_task(context: &Context, name: &str, args: &[String]) -> Result<(), Error> {
+    let Context {
+        paths, settings, ..
+    } = context;
+    let tasks = Tasks::load(&paths.project, &settings.tasks)?;
+    let plan = tasks.plan(name)?;
+    commands::expect_venv(context)?;
+    commands::check_venv_fingerprint(context, PostInstallAction::RunSetupPyDevelop)?;
+    let base_runner = commands::get_runner(context, &[])?;
+    for task_name in &plan {
+        let task = tasks.get(task_name)?;
+        let error = |details: String| new_error(format!("task '{}': {}", task_name, details));
+        print_info_1(&format!("Running task '{}'", task_name));
+        let env = task
+            .env
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let mut runner = base_runner.with_env(env);
+        if let Some(cwd) = &task.cwd {
+            runner = runner.in_dir(&paths.project.join(cwd));
+        }
+        let lines = task.run.lines();
+        if lines.is_empty() {
+            return Err(error("no command to run".to_string()));
+        }
+        for (i, line) in lines.iter().enumerate() {
+            let mut cmd = split_command(line).map_err(error)?;
+            if cmd.is_empty() {
+                return Err(error("empty command".to_string()));
+            }
+            if task_name == name && i == lines.len() - 1 {
+                cmd.extend(args.iter().cloned());
+            }
+            // Note: keep the error as is, so that dmenv exits with
+            // the same code as the failing command
+            if let Err(e) = runner.run(&cmd) {
+                print_error(&format!("task '{}': `{}` failed", task_name, line));
+                return Err(e);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// List the tasks defined for the project, along with their
+/// description or their commands
+pub fn list_tasks(context: &Context) -> Result<(), Error> {
+    let Context {
+        paths, settings, ..
+    } = context;
+    let tasks = Tasks::load(&paths.project, &settings.tasks)?;
+    let mut rows = vec![];
+    for (name, task) in tasks.iter() {
+        let summary = match &task.description {
+            Some(description) => description.to_string(),
+            None => task.run.lines().join(" && "),
+        };
+        rows.push(vec![name.to_string(), summary]);
+    }
+    if rows.is_empty() {
+        print_warning("No tasks defined");
+        return Ok(());
+    }
+    print!("{}", format_table(&rows));
+    Ok(())
+}
@@ -1,8 +1,9 @@
 use crate::cli::commands;
 use crate::cli::syntax::Command;
 use crate::error::*;
+use crate::markers::Environment;
 use crate::operations;
-use crate::{get_context, Context};
+use crate::{get_context, Context, DryRun};
 
 // Re-generate a clean lock:
 //   - clean the virtualenv
@@ -12,15 +13,18 @@ use crate::{get_context, Context};
 //     in `self.install_editable_with_constraint()`
 //  - re-generate the lock by only keeping existing dependencies:
 //    see `operations::lock::tidy()`
-pub fn tidy(cmd: &Command, context: Context) -> Result<(), Error> {
+pub fn tidy(cmd: &Command, context: Context, dry_run: DryRun) -> Result<(), Error> {
     commands::clean_venv(context)?;
     // Re-create a context since we've potenntially just
     // deleted the python we used to clean the previous virtualenv
-    let context = get_context(&cmd)?;
+    let context = get_context(cmd)?;
     commands::create_venv(&context)?;
     commands::install_editable_with_constraint(&context)?;
     let metadata = commands::metadata(&context);
     let frozen_deps = commands::get_frozen_deps(&context)?;
-    let Context { paths, .. } = context;
-    operations::lock::tidy(&paths.lock, frozen_deps, &metadata)
+    let Context {
+        paths, python_info, ..
+    } = context;
+    let environment = Environment::from_python_info(&python_info);
+    operations::lock::tidy(&paths.lock, frozen_deps, &environment, dry_run, &metadata)
 }
@@ -1,18 +1,22 @@
+use crate::cli::commands;
 use crate::error::*;
 use crate::operations;
 use crate::ui::*;
 use crate::Context;
+use crate::PostInstallAction;
 
 pub fn ensure_venv(context: &Context) -> Result<(), Error> {
     let Context { paths, .. } = context;
-    if paths.venv.exists() {
-        print_info_2(&format!(
-            "Using existing virtualenv: {}",
-            paths.venv.display()
-        ));
-    } else {
-        create_venv(context)?;
+    if !paths.venv.exists() {
+        return create_venv(context);
+    }
+    if check_stale_venv(context)? {
+        return create_venv(context);
     }
+    print_info_2(&format!(
+        "Using existing virtualenv: {}",
+        paths.venv.display()
+    ));
     Ok(())
 }
 
@@ -45,7 +49,124 @@ pub fn clean_venv(context: Context) -> Result<(), Error> {
 // virtualenv to exist, like `show_deps` or `run`:
 // this ensures that error messages printed when the
 // virtualenv does not exist are consistent.
+//
+// When a stale virtualenv is re-created, dependencies are
+// re-installed from the lock, if there is one
 pub fn expect_venv(context: &Context) -> Result<(), Error> {
     let Context { paths, .. } = context;
-    operations::venv::expect(&paths.venv)
+    operations::venv::expect(&paths.venv)?;
+    if !check_stale_venv(context)? {
+        return Ok(());
+    }
+    if paths.lock.exists() {
+        commands::install(context, PostInstallAction::RunSetupPyDevelop)
+    } else {
+        create_venv(context)
+    }
+}
+
+/// Same as `expect_venv()`, but return an error instead of re-creating
+/// a stale virtualenv.
+// Note: used by commands whose output is parsed, redirected or evaluated,
+// like `freeze`, `env` or `show:deps --format json`: re-installing the
+// dependencies would print to stdout
+pub fn expect_venv_as_is(context: &Context) -> Result<(), Error> {
+    let Context {
+        paths, python_info, ..
+    } = context;
+    operations::venv::expect(&paths.venv)?;
+    match operations::venv::check_base_python(&paths.venv, python_info)? {
+        None => Ok(()),
+        Some(details) => Err(Error::StaleVenv {
+            path: paths.venv.to_path_buf(),
+            details,
+        }),
+    }
+}
+
+/// Check that the lock and the project files did not change since
+/// they were last installed in the virtualenv.
+/// If they did, either print a warning, or sync the virtualenv
+/// and run `post_sync_action`, depending on the `auto_sync` setting
+//
+// Notes:
+// * Called by commands that use the virtualenv without changing it, like
+//   `run` or `show:deps`
+// * Virtualenvs with no recorded fingerprint are not checked
+pub fn check_venv_fingerprint(
+    context: &Context,
+    post_sync_action: PostInstallAction,
+) -> Result<(), Error> {
+    let Context {
+        paths, settings, ..
+    } = context;
+    if !paths.lock.exists() {
+        return Ok(());
+    }
+    let recorded = match operations::venv::read_fingerprint(&paths.venv)? {
+        None => return Ok(()),
+        Some(recorded) => recorded,
+    };
+    if recorded == project_fingerprint(context)? {
+        return Ok(());
+    }
+    if !settings.auto_sync {
+        print_warning(&format!(
+            "{} or the project files have changed since the last install.\n\
+             Run `dmenv sync` or `dmenv install` to update the virtualenv, or set `auto_sync` to true",
+            paths.lock.display()
+        ));
+        return Ok(());
+    }
+    // Note: the output of the command must not be mixed with the one
+    // of `sync`, as it may be parsed or redirected
+    with_progress_on_stderr(|| {
+        commands::sync(context)?;
+        match post_sync_action {
+            PostInstallAction::RunSetupPyDevelop => commands::develop(context),
+            PostInstallAction::None => Ok(()),
+        }
+    })
+}
+
+/// Record the fingerprint of the lock and the project files in the
+/// virtualenv, see `check_venv_fingerprint()`
+pub fn record_fingerprint(context: &Context) -> Result<(), Error> {
+    let Context { paths, .. } = context;
+    let fingerprint = project_fingerprint(context)?;
+    operations::venv::write_fingerprint(&paths.venv, &fingerprint)
+}
+
+fn project_fingerprint(context: &Context) -> Result<String, Error> {
+    let Context { paths, .. } = context;
+    let mut files = vec![paths.lock.to_path_buf()];
+    files.extend(commands::project_files(context));
+    operations::venv::fingerprint(&files)
+}
+
+/// Check whether the interpreter used to create the virtualenv has
+/// changed since then.
+/// If it has, either remove the virtualenv and return true so that
+/// it gets re-created, or return an error, depending on the
+/// `recreate_if_stale` setting
+fn check_stale_venv(context: &Context) -> Result<bool, Error> {
+    let Context {
+        paths,
+        python_info,
+        settings,
+        ..
+    } = context;
+    let details = match operations::venv::check_base_python(&paths.venv, python_info)? {
+        None => return Ok(false),
+        Some(details) => details,
+    };
+    if !settings.recreate_if_stale {
+        return Err(Error::StaleVenv {
+            path: paths.venv.to_path_buf(),
+            details,
+        });
+    }
+    print_warning(&format!("Virtualenv is stale: {}", details));
+    operations::venv::clean(paths.venv.to_path_buf())?;
+    Ok(true)
 }
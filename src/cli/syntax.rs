@@ -1,48 +1,157 @@
-use regex::Regex;
+use std::path::PathBuf;
 use structopt::StructOpt;
 
+use crate::activate::Shell;
 use crate::error::*;
+use crate::markers::{Marker, Value};
+use crate::OutputFormat;
 
-#[derive(StructOpt, Debug)]
+#[derive(StructOpt, Debug, Clone)]
 #[structopt(
     name = "dmenv",
     about = "Simple and practical virtualenv manager for Python"
 )]
 pub struct Command {
-    #[structopt(long = "python", help = "python binary")]
+    #[structopt(
+        long = "python",
+        help = "Python binary, or Python version to look for (like `3.8`)"
+    )]
     pub python_binary: Option<String>,
 
     #[structopt(long = "project", help = "path to use as the project directory")]
     pub project_path: Option<String>,
 
-    #[structopt(long = "production", help = "Ignore dev dependencies")]
+    #[structopt(
+        long = "production",
+        help = "Ignore dev dependencies. Same as `--profile prod`",
+        raw(conflicts_with = r#""profile""#)
+    )]
     pub production: bool,
 
+    #[structopt(
+        long = "profile",
+        help = "Profile to use, defining the extras to install, the lock and the virtualenv"
+    )]
+    pub profile: Option<String>,
+
     #[structopt(
         long = "--system-site-packages",
         help = "Give the virtual environment access to the system site-packages dir"
     )]
     pub system_site_packages: bool,
 
+    #[structopt(
+        long = "recreate-if-stale",
+        help = "Re-create the virtualenv if the interpreter used to create it has changed"
+    )]
+    pub recreate_if_stale: bool,
+
+    #[structopt(long = "index-url", help = "Base URL of the Python package index")]
+    pub index_url: Option<String>,
+
+    #[structopt(
+        long = "extra-index-url",
+        help = "Extra URL of a package index to use. Can be used several times",
+        raw(number_of_values = "1")
+    )]
+    pub extra_index_urls: Vec<String>,
+
+    #[structopt(
+        long = "find-links",
+        help = "Local directory or URL of a page to look for archives in. Can be used several times",
+        raw(number_of_values = "1")
+    )]
+    pub find_links: Vec<String>,
+
+    #[structopt(
+        long = "trusted-host",
+        help = "Host to trust even without valid HTTPS. Can be used several times",
+        raw(number_of_values = "1")
+    )]
+    pub trusted_hosts: Vec<String>,
+
+    #[structopt(
+        long = "constraint",
+        help = "Constraints file to use when installing packages. Can be used several times",
+        raw(number_of_values = "1")
+    )]
+    pub constraints: Vec<String>,
+
+    #[structopt(
+        long = "record-index-options",
+        help = "Write the index options at the top of the lock"
+    )]
+    pub record_index_options: bool,
+
+    #[structopt(
+        long = "wheelhouse",
+        help = "Directory where `dmenv download` stores archives, relative to the project"
+    )]
+    pub wheelhouse: Option<String>,
+
+    #[structopt(
+        long = "workspace",
+        help = "Run the command in every member of the workspace. Works with lock, install, tidy and show:lock-drift"
+    )]
+    pub workspace: bool,
+
+    #[structopt(
+        long = "parallel",
+        help = "With --workspace, process the members in parallel",
+        raw(requires = r#""workspace""#)
+    )]
+    pub parallel: bool,
+
     #[structopt(subcommand)]
     pub sub_cmd: SubCommand,
 }
 
-#[derive(StructOpt, Debug)]
+#[derive(StructOpt, Debug, Clone)]
 pub enum SubCommand {
     #[structopt(name = "clean", about = "Clean existing virtualenv")]
     Clean {},
 
-    #[structopt(name = "develop", about = "Run setup.py develop")]
+    #[structopt(
+        name = "develop",
+        about = "Install the project in the virtualenv, in editable mode"
+    )]
     Develop {},
 
+    #[structopt(
+        name = "download",
+        about = "Download every package of the lock into the wheelhouse"
+    )]
+    Download {
+        #[structopt(
+            long = "platform",
+            help = "Download wheels for the given platform tag instead of the current one. Can be used several times",
+            raw(number_of_values = "1")
+        )]
+        platforms: Vec<String>,
+
+        #[structopt(
+            long = "python-version",
+            help = "Download wheels for the given Python version, like `3.8`, instead of the current one"
+        )]
+        python_version: Option<String>,
+    },
+
     #[structopt(name = "create", about = "Create an empty vritualenv")]
     Create {},
 
     #[structopt(name = "install", about = "Install all dependencies")]
     Install {
-        #[structopt(long = "--no-develop", help = "Do not run setup.py develop")]
+        #[structopt(
+            long = "--no-develop",
+            help = "Do not install the project itself, just its dependencies"
+        )]
         no_develop: bool,
+
+        #[structopt(
+            long = "offline",
+            help = "Install packages from the wheelhouse only, without using any index"
+        )]
+        offline: bool,
     },
 
     #[structopt(name = "bump-in-lock", about = "Bump a dependency in the lock file")]
@@ -55,8 +164,27 @@ pub enum SubCommand {
 
         #[structopt(help = "version")]
         version: String,
+
+        #[structopt(
+            long = "dry-run",
+            help = "Show the changes, but leave the lock untouched"
+        )]
+        dry_run: bool,
+
+        #[structopt(
+            long = "diff",
+            help = "With --dry-run, show the changes as a unified diff",
+            raw(requires = r#""dry_run""#)
+        )]
+        diff: bool,
     },
 
+    #[structopt(
+        name = "freeze",
+        about = "Write a snapshot of the virtualenv, for use with `dmenv lock --matrix`"
+    )]
+    Freeze {},
+
     #[structopt(name = "init", about = "Initialize a new project")]
     Init {
         #[structopt(help = "Project name")]
@@ -73,6 +201,13 @@ pub enum SubCommand {
             help = "Keep all code in the `setup.py` file, do not use `setup.cfg`"
         )]
         no_setup_cfg: bool,
+
+        #[structopt(
+            long = "pyproject",
+            help = "Write a `pyproject.toml` file instead of `setup.py`",
+            raw(conflicts_with = r#""no_setup_cfg""#)
+        )]
+        pyproject: bool,
     },
 
     #[structopt(name = "lock", about = "(Re)-generate requirements.lock")]
@@ -82,10 +217,50 @@ pub enum SubCommand {
             help = "Restrict Python version",
             parse(try_from_str = "parse_python_version")
         )]
-        python_version: Option<String>,
+        python_version: Option<Marker>,
 
         #[structopt(long = "platform", help = "Restrict platform")]
         sys_platform: Option<String>,
+
+        #[structopt(
+            long = "hashes",
+            help = "Record sha256 hashes of every dependency in the lock"
+        )]
+        hashes: bool,
+
+        #[structopt(
+            long = "check",
+            help = "Exit with an error if the lock is not up to date, without writing it",
+            raw(conflicts_with = r#""dry_run""#)
+        )]
+        check: bool,
+
+        #[structopt(
+            long = "matrix",
+            help = "Merge the given snapshots, written by `dmenv freeze`, into the lock",
+            parse(from_os_str),
+            raw(conflicts_with_all = r#"&["check", "hashes", "python_version", "sys_platform"]"#)
+        )]
+        matrix: Vec<PathBuf>,
+
+        #[structopt(
+            long = "dry-run",
+            help = "Show the changes, but leave the lock untouched"
+        )]
+        dry_run: bool,
+
+        #[structopt(
+            long = "diff",
+            help = "With --dry-run, show the changes as a unified diff",
+            raw(requires = r#""dry_run""#)
+        )]
+        diff: bool,
+
+        #[structopt(
+            long = "offline",
+            help = "Install packages from the wheelhouse only, without using any index"
+        )]
+        offline: bool,
     },
 
     #[structopt(name = "run", about = "Run the given binary from the virtualenv")]
@@ -96,7 +271,28 @@ pub enum SubCommand {
         )]
         no_exec: bool,
 
-        #[structopt(name = "command", raw(required = "true"))]
+        #[structopt(
+            long = "env-file",
+            help = "Read environment variables from this file, after the `.env` file of the project. Can be used several times",
+            parse(from_os_str),
+            raw(number_of_values = "1")
+        )]
+        env_files: Vec<PathBuf>,
+
+        #[structopt(
+            short = "m",
+            help = "Run a Python module, like `python -m`. The command is then used as its arguments",
+            raw(conflicts_with = r#""code""#)
+        )]
+        module: Option<String>,
+
+        #[structopt(
+            short = "c",
+            help = "Run Python code, like `python -c`. The command is then used as its arguments"
+        )]
+        code: Option<String>,
+
+        #[structopt(name = "command", raw(required_unless_one = r#"&["module", "code"]"#))]
         cmd: Vec<String>,
     },
 
@@ -106,26 +302,168 @@ pub enum SubCommand {
         force: bool,
     },
 
+    #[structopt(
+        name = "show:config",
+        about = "Show the effective configuration, and where each value comes from"
+    )]
+    ShowConfig {
+        #[structopt(
+            long = "format",
+            help = "Output format",
+            default_value = "text",
+            raw(possible_values = r#"&["text", "json"]"#)
+        )]
+        format: OutputFormat,
+    },
+
     #[structopt(name = "show:deps", about = "Show installed dependencies information")]
-    ShowDeps {},
+    ShowDeps {
+        #[structopt(
+            long = "format",
+            help = "Output format",
+            default_value = "text",
+            raw(possible_values = r#"&["text", "json"]"#)
+        )]
+        format: OutputFormat,
+    },
+
+    #[structopt(
+        name = "show:info",
+        about = "Show the Python interpreter, virtualenv and lock used by dmenv"
+    )]
+    ShowInfo {
+        #[structopt(
+            long = "format",
+            help = "Output format",
+            default_value = "text",
+            raw(possible_values = r#"&["text", "json"]"#)
+        )]
+        format: OutputFormat,
+    },
+
+    #[structopt(
+        name = "show:lock-drift",
+        about = "Compare the virtualenv with the lock, and fail if they differ"
+    )]
+    ShowLockDrift {
+        #[structopt(
+            long = "format",
+            help = "Output format",
+            default_value = "text",
+            raw(possible_values = r#"&["text", "json"]"#)
+        )]
+        format: OutputFormat,
+    },
 
     #[structopt(
         name = "show:outdated",
         about = "Show outdated dependencies information"
     )]
-    ShowOutDated {},
+    ShowOutDated {
+        #[structopt(
+            long = "format",
+            help = "Output format",
+            default_value = "text",
+            raw(possible_values = r#"&["text", "json"]"#)
+        )]
+        format: OutputFormat,
+    },
+
+    #[structopt(
+        name = "show:pythons",
+        about = "Show the Python interpreters found on this machine"
+    )]
+    ShowPythons {
+        #[structopt(
+            long = "format",
+            help = "Output format",
+            default_value = "text",
+            raw(possible_values = r#"&["text", "json"]"#)
+        )]
+        format: OutputFormat,
+    },
 
     #[structopt(name = "show:venv_path", about = "Show path of the virtualenv")]
-    ShowVenvPath {},
+    ShowVenvPath {
+        #[structopt(
+            long = "format",
+            help = "Output format",
+            default_value = "text",
+            raw(possible_values = r#"&["text", "json"]"#)
+        )]
+        format: OutputFormat,
+    },
 
     #[structopt(
         name = "show:bin_path",
         about = "Show path of the virtualenv's binaries"
     )]
-    ShowVenvBin {},
+    ShowVenvBin {
+        #[structopt(
+            long = "format",
+            help = "Output format",
+            default_value = "text",
+            raw(possible_values = r#"&["text", "json"]"#)
+        )]
+        format: OutputFormat,
+    },
+
+    #[structopt(name = "shell", about = "Spawn a shell with the virtualenv activated")]
+    Shell {},
+
+    #[structopt(
+        name = "env",
+        about = "Print shell code activating the virtualenv, for use with `eval`"
+    )]
+    Env {
+        #[structopt(
+            long = "shell",
+            help = "Shell to print code for. Guessed from $SHELL by default",
+            raw(possible_values = r#"&["bash", "zsh", "fish"]"#)
+        )]
+        shell: Option<Shell>,
+    },
+
+    #[structopt(
+        name = "sync",
+        about = "Install, upgrade and remove packages so that the virtualenv matches the lock"
+    )]
+    Sync {},
+
+    #[structopt(
+        name = "task",
+        about = "Run a task defined in setup.cfg or in the configuration"
+    )]
+    Task {
+        #[structopt(long = "list", help = "List the tasks instead of running one")]
+        list: bool,
+
+        #[structopt(name = "name", raw(required_unless = r#""list""#))]
+        name: Option<String>,
+
+        #[structopt(
+            name = "args",
+            help = "Arguments appended to the last command of the task",
+            raw(last = "true")
+        )]
+        args: Vec<String>,
+    },
 
     #[structopt(name = "tidy", about = "Re-generate a clean lock")]
-    Tidy {},
+    Tidy {
+        #[structopt(
+            long = "dry-run",
+            help = "Show the changes, but leave the lock untouched"
+        )]
+        dry_run: bool,
+
+        #[structopt(
+            long = "diff",
+            help = "With --dry-run, show the changes as a unified diff",
+            raw(requires = r#""dry_run""#)
+        )]
+        diff: bool,
+    },
 
     #[structopt(name = "upgrade-pip", about = "Upgrade pip in the virtualenv")]
     UpgradePip {},
@@ -133,18 +471,17 @@ pub enum SubCommand {
 
 // Make sure the `--python-version` option used in `dmenv lock`
 // can be written as marker in the lock file
-fn parse_python_version(string: &str) -> Result<String, Error> {
-    // Note: parsing *all* the possible syntaxes is a hard problem
-    // (see https://www.python.org/dev/peps/pep-0508/#grammar for details),
-    // so we use a regex that matches a *subset* of what is possible
-    // instead.
-    let re = Regex::new("^(==|<|<=|>|>=) (('.*?')|(\".*?\"))$").unwrap();
-    if !re.is_match(string) {
-        return Err(new_error(
-            "should match something like `<= '3.6'`".to_string(),
-        ));
+fn parse_python_version(string: &str) -> Result<Marker, Error> {
+    let error = || new_error("should match something like `<= '3.6'`".to_string());
+    let marker = Marker::parse(&format!("python_version {}", string)).map_err(|_| error())?;
+    // Reject things like `< '3.6' or os_name == 'nt'`, which would parse fine
+    match &marker {
+        Marker::Compare {
+            right: Value::Literal(_),
+            ..
+        } => Ok(marker),
+        _ => Err(error()),
     }
-    Ok(string.to_string())
 }
 
 #[cfg(test)]
@@ -153,7 +490,10 @@ mod tests {
 
     #[test]
     fn test_parse_python_version_ok() {
-        assert_eq!("< '3.6'", parse_python_version("< '3.6'").unwrap());
+        assert_eq!(
+            parse_python_version("< '3.6'").unwrap().to_string(),
+            "python_version < '3.6'"
+        );
     }
 
     #[test]
@@ -165,4 +505,9 @@ mod tests {
     fn test_parse_python_version_not_quoted() {
         parse_python_version("<= 3.6").unwrap_err();
     }
+
+    #[test]
+    fn test_parse_python_version_several_markers() {
+        parse_python_version("< '3.6' or os_name == 'nt'").unwrap_err();
+    }
 }
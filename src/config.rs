@@ -0,0 +1,253 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::*;
+
+pub const PROJECT_CONFIG_FILENAME: &str = "dmenv.toml";
+
+/// Contents of a dmenv configuration file.
+///
+/// The same keys can be used in the user configuration file, in the
+/// project's `dmenv.toml`, or in the `[tool.dmenv]` section of
+/// `pyproject.toml`. Every key is optional.
+///
+/// See `Settings` for how the configuration files are combined.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    pub venv_outside_project: Option<bool>,
+    pub venv_from_stdlib: Option<bool>,
+    pub python: Option<String>,
+    pub production: Option<bool>,
+    pub profile: Option<String>,
+    pub system_site_packages: Option<bool>,
+    pub recreate_if_stale: Option<bool>,
+    pub auto_sync: Option<bool>,
+    pub dev_extra: Option<String>,
+    pub prod_extra: Option<String>,
+    pub dev_lock: Option<String>,
+    pub prod_lock: Option<String>,
+    pub index_url: Option<String>,
+    pub extra_index_urls: Option<Vec<String>>,
+    pub find_links: Option<Vec<String>>,
+    pub trusted_hosts: Option<Vec<String>>,
+    pub constraints: Option<Vec<String>>,
+    pub record_index_options: Option<bool>,
+    pub wheelhouse: Option<String>,
+    pub offline: Option<bool>,
+    pub profiles: Option<BTreeMap<String, ProfileConfig>>,
+    pub tasks: Option<BTreeMap<String, TaskConfig>>,
+}
+
+/// A `[profiles.<name>]` section of a configuration file.
+///
+/// Missing keys default to an extra named after the profile, a
+/// `<name>.lock` lock file, and a `<name>` virtualenv sub-directory.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfig {
+    pub extras: Option<Vec<String>>,
+    pub lock: Option<String>,
+    pub venv_subdir: Option<String>,
+}
+
+/// A `[tasks.<name>]` section of a configuration file, see `dmenv task`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaskConfig {
+    pub description: Option<String>,
+    pub run: TaskCommands,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Working directory, relative to the project
+    pub cwd: Option<String>,
+    /// Names of the tasks to run before this one
+    #[serde(default)]
+    pub depends: Vec<String>,
+}
+
+/// Either a single command line, or a list of command lines run in sequence
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum TaskCommands {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl TaskCommands {
+    pub fn lines(&self) -> Vec<String> {
+        match self {
+            TaskCommands::One(line) => vec![line.to_string()],
+            TaskCommands::Many(lines) => lines.clone(),
+        }
+    }
+}
+
+impl ConfigFile {
+    pub fn parse(path: &Path, contents: &str) -> Result<Self, Error> {
+        toml::from_str(contents).map_err(|e| new_config_error(path, e))
+    }
+}
+
+/// Read the project configuration, either from `dmenv.toml` or from
+/// the `[tool.dmenv]` section of `pyproject.toml`.
+/// Returns the path of the file that was read along with its contents.
+// Note: if both exist, `dmenv.toml` wins
+pub fn read_project_config(project_path: &Path) -> Result<Option<(PathBuf, ConfigFile)>, Error> {
+    let dmenv_toml = project_path.join(PROJECT_CONFIG_FILENAME);
+    if dmenv_toml.exists() {
+        let config = read_config(&dmenv_toml)?;
+        return Ok(Some((dmenv_toml, config)));
+    }
+    let pyproject_toml = project_path.join("pyproject.toml");
+    if !pyproject_toml.exists() {
+        return Ok(None);
+    }
+    let contents =
+        std::fs::read_to_string(&pyproject_toml).map_err(|e| new_read_error(e, &pyproject_toml))?;
+    let value: toml::Value =
+        toml::from_str(&contents).map_err(|e| new_config_error(&pyproject_toml, e))?;
+    let section = value.get("tool").and_then(|tool| tool.get("dmenv"));
+    match section {
+        None => Ok(None),
+        Some(section) => {
+            let config = section
+                .clone()
+                .try_into()
+                .map_err(|e| new_config_error(&pyproject_toml, e))?;
+            Ok(Some((pyproject_toml, config)))
+        }
+    }
+}
+
+/// Read the user configuration, if it exists
+pub fn read_user_config(path: &Path) -> Result<Option<ConfigFile>, Error> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(read_config(path)?))
+}
+
+fn read_config(path: &Path) -> Result<ConfigFile, Error> {
+    let contents = std::fs::read_to_string(path).map_err(|e| new_read_error(e, path))?;
+    ConfigFile::parse(path, &contents)
+}
+
+fn new_config_error(path: &Path, error: toml::de::Error) -> Error {
+    Error::MalformedConfig {
+        path: path.to_path_buf(),
+        details: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_config() {
+        let contents = r#"
+venv_outside_project = true
+dev_extra = "develop"
+extra_index_urls = ["https://pypi.example.com/simple"]
+"#;
+        let config = ConfigFile::parse(Path::new("dmenv.toml"), contents).unwrap();
+        assert_eq!(config.venv_outside_project, Some(true));
+        assert_eq!(config.dev_extra, Some("develop".to_string()));
+        assert_eq!(config.prod_extra, None);
+        assert_eq!(
+            config.extra_index_urls,
+            Some(vec!["https://pypi.example.com/simple".to_string()])
+        );
+    }
+
+    #[test]
+    fn parse_profiles() {
+        let contents = r#"
+profile = "docs"
+
+[profiles.docs]
+extras = ["docs", "dev"]
+
+[profiles.gpu-less-ci]
+lock = "ci.lock"
+venv_subdir = "ci"
+"#;
+        let config = ConfigFile::parse(Path::new("dmenv.toml"), contents).unwrap();
+        assert_eq!(config.profile, Some("docs".to_string()));
+        let profiles = config.profiles.unwrap();
+        assert_eq!(
+            profiles["docs"].extras,
+            Some(vec!["docs".to_string(), "dev".to_string()])
+        );
+        assert_eq!(profiles["gpu-less-ci"].lock, Some("ci.lock".to_string()));
+    }
+
+    #[test]
+    fn parse_tasks() {
+        let contents = r#"
+[tasks.lint]
+run = "flake8 ."
+
+[tasks.test]
+description = "Run the tests"
+run = ["python -m pytest", "coverage report"]
+env = { PYTHONWARNINGS = "error" }
+cwd = "tests"
+depends = ["lint"]
+"#;
+        let config = ConfigFile::parse(Path::new("dmenv.toml"), contents).unwrap();
+        let tasks = config.tasks.unwrap();
+        assert_eq!(tasks["lint"].run.lines(), &["flake8 ."]);
+        assert!(tasks["lint"].depends.is_empty());
+        let test = &tasks["test"];
+        assert_eq!(test.run.lines(), &["python -m pytest", "coverage report"]);
+        assert_eq!(test.env["PYTHONWARNINGS"], "error");
+        assert_eq!(test.cwd, Some("tests".to_string()));
+        assert_eq!(test.depends, &["lint"]);
+    }
+
+    #[test]
+    fn reject_unknown_keys() {
+        let contents = "venv_outside_projet = true\n";
+        let error = ConfigFile::parse(Path::new("dmenv.toml"), contents).unwrap_err();
+        assert!(error.to_string().contains("venv_outside_projet"));
+    }
+
+    #[test]
+    fn read_tool_section_of_pyproject() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let pyproject = "[build-system]\nrequires = [\"setuptools\"]\n\n[tool.dmenv]\nprod_lock = \"prod.lock\"\n";
+        std::fs::write(tmp_dir.path().join("pyproject.toml"), pyproject).unwrap();
+        let (path, config) = read_project_config(tmp_dir.path()).unwrap().unwrap();
+        assert_eq!(path, tmp_dir.path().join("pyproject.toml"));
+        assert_eq!(config.prod_lock, Some("prod.lock".to_string()));
+    }
+
+    #[test]
+    fn dmenv_toml_wins_over_pyproject() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        std::fs::write(
+            tmp_dir.path().join("pyproject.toml"),
+            "[tool.dmenv]\nprod_lock = \"prod.lock\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            tmp_dir.path().join("dmenv.toml"),
+            "dev_lock = \"dev.lock\"\n",
+        )
+        .unwrap();
+        let (path, config) = read_project_config(tmp_dir.path()).unwrap().unwrap();
+        assert_eq!(path, tmp_dir.path().join("dmenv.toml"));
+        assert_eq!(config.prod_lock, None);
+        assert_eq!(config.dev_lock, Some("dev.lock".to_string()));
+    }
+
+    #[test]
+    fn no_project_config() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        std::fs::write(tmp_dir.path().join("pyproject.toml"), "[tool.black]\n").unwrap();
+        assert!(read_project_config(tmp_dir.path()).unwrap().is_none());
+    }
+}
@@ -1,5 +1,6 @@
 use crate::error::Error;
 use crate::lock::parse_simple_line;
+use crate::markers::{Environment, Marker};
 
 /// Home for types that represent dependencies.
 ///
@@ -19,6 +20,13 @@ pub struct FrozenDependency {
 }
 
 impl FrozenDependency {
+    pub fn new(name: &str, version: &str) -> Self {
+        FrozenDependency {
+            name: name.to_string(),
+            version: version.to_string(),
+        }
+    }
+
     /// Construct a new FrozenDependency from a line coming from
     /// `pip freeze` output
     pub fn from_string(string: String) -> Result<Self, Error> {
@@ -78,6 +86,22 @@ impl LockedDependency {
         }
     }
 
+    pub fn marker(&self) -> Option<&Marker> {
+        match self {
+            LockedDependency::Git(_) => None,
+            LockedDependency::Simple(x) => x.marker.as_ref(),
+        }
+    }
+
+    /// Returns true if pip would install this dependency
+    /// in the given environment
+    pub fn applies_to(&self, environment: &Environment) -> bool {
+        match self.marker() {
+            None => true,
+            Some(marker) => marker.evaluate(environment),
+        }
+    }
+
     pub fn git_bump(&mut self, new_ref: &str) -> Result<(), Error> {
         match self {
             LockedDependency::Git(x) => {
@@ -136,6 +160,10 @@ pub struct SimpleDependency {
     pub name: String,
     pub line: String,
     pub version: VersionSpec,
+    // What comes after the `;`, if anything
+    pub marker: Option<Marker>,
+    // Hashes of the allowed artifacts, like `sha256:<hex digest>`
+    pub hashes: Vec<String>,
 }
 
 impl SimpleDependency {
@@ -147,19 +175,32 @@ impl SimpleDependency {
         parse_simple_line(&line).expect("failed to parse frozen line")
     }
 
-    /// Make this dependency specific to a Python version
-    pub fn python_version(&mut self, python_version: &str) {
-        let trimmed_line = self.line.trim_end_matches('\n');
-        self.line = format!("{} ; python_version {}\n", trimmed_line, python_version);
+    /// Make this dependency specific to the given marker, like
+    /// `python_version < '3.6'` or `sys_platform == 'win32'`.
+    // Note: if the dependency already has a marker, both are combined with `and`
+    pub fn add_marker(&mut self, marker: &Marker) {
+        let new_marker = match &self.marker {
+            Some(existing) => existing.and(marker),
+            None => marker.clone(),
+        };
+        self.set_marker(Some(new_marker));
     }
 
-    /// Make this dependency specific to a Python platform
-    pub fn sys_platform(&mut self, sys_platform: &str) {
-        let trimmed_line = self.line.trim_end_matches('\n');
-        self.line = format!("{} ; sys_platform == '{}'\n", trimmed_line, sys_platform);
+    /// Replace the marker of this dependency, if any
+    pub fn set_marker(&mut self, marker: Option<Marker>) {
+        let requirement = self.requirement();
+        let without_marker = requirement.split(';').next().unwrap_or(requirement);
+        let requirement = match &marker {
+            Some(marker) => format!("{} ; {}", without_marker.trim_end(), marker),
+            None => without_marker.trim_end().to_string(),
+        };
+        self.write_line(&requirement);
+        self.marker = marker;
     }
 
     /// Bump a simple dependency to a new version
+    // Note: hashes of the previous version are dropped, since
+    // they no longer match any of the new artifacts
     pub fn simple_bump(&mut self, new_version: &str) {
         let VersionSpec { start, end, .. } = &self.version;
         self.line = format!(
@@ -169,6 +210,39 @@ impl SimpleDependency {
             &self.line[*end..],
         );
         self.version.value = new_version.to_string();
+        if !self.hashes.is_empty() {
+            self.set_hashes(&[]);
+        }
+    }
+
+    /// Replace the `--hash` options of the line
+    //
+    // Hashes are written one per line, after the requirement
+    // itself, like pip-compile does:
+    //
+    //    foo==0.42 \
+    //        --hash=sha256:...
+    pub fn set_hashes(&mut self, hashes: &[String]) {
+        self.hashes = hashes.to_vec();
+        let requirement = self.requirement().to_string();
+        self.write_line(&requirement);
+    }
+
+    /// Re-write the line using the given requirement and the current hashes
+    fn write_line(&mut self, requirement: &str) {
+        let mut line = requirement.to_string();
+        for hash in &self.hashes {
+            line.push_str(" \\\n    --hash=");
+            line.push_str(hash);
+        }
+        line.push('\n');
+        self.line = line;
+    }
+
+    /// Return the part of the line before the `--hash` options
+    fn requirement(&self) -> &str {
+        let end = self.line.find("--hash=").unwrap_or(self.line.len());
+        self.line[..end].trim_end_matches(|c: char| c.is_ascii_whitespace() || c == '\\')
     }
 
     /// Freeze a simple dependency to a new version
@@ -201,4 +275,44 @@ mod tests {
         dep.simple_bump("0.43");
         assert_eq!(dep.line, "foo == 0.43");
     }
+
+    #[test]
+    fn set_hashes() {
+        let mut dep = parse_simple_line("foo==0.42 ; python_version < '3.6'\n").unwrap();
+        dep.set_hashes(&["sha256:abc".to_string(), "sha256:def".to_string()]);
+        assert_eq!(
+            dep.line,
+            "foo==0.42 ; python_version < '3.6' \\\n    --hash=sha256:abc \\\n    --hash=sha256:def\n"
+        );
+    }
+
+    #[test]
+    fn add_marker() {
+        let mut dep = parse_simple_line("foo==0.42\n").unwrap();
+        dep.add_marker(&Marker::parse("python_version < '3.6'").unwrap());
+        dep.add_marker(&Marker::parse("sys_platform == 'win32'").unwrap());
+        assert_eq!(
+            dep.line,
+            "foo==0.42 ; python_version < '3.6' and sys_platform == 'win32'\n"
+        );
+    }
+
+    #[test]
+    fn add_marker_keeps_hashes() {
+        let mut dep = parse_simple_line("foo==0.42 \\\n    --hash=sha256:abc\n").unwrap();
+        dep.add_marker(&Marker::parse("os_name == 'nt' or os_name == 'java'").unwrap());
+        dep.add_marker(&Marker::parse("python_version < '3.6'").unwrap());
+        assert_eq!(
+            dep.line,
+            "foo==0.42 ; (os_name == 'nt' or os_name == 'java') and python_version < '3.6' \\\n    --hash=sha256:abc\n"
+        );
+    }
+
+    #[test]
+    fn simple_bump_drops_hashes() {
+        let mut dep = parse_simple_line("foo==0.42 \\\n    --hash=sha256:abc\n").unwrap();
+        dep.simple_bump("0.43");
+        assert_eq!(dep.line, "foo==0.43\n");
+        assert!(dep.hashes.is_empty());
+    }
 }
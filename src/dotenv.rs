@@ -0,0 +1,241 @@
+use std::path::Path;
+
+use crate::error::*;
+
+/// Parse the contents of a `.env` file.
+///
+/// Supported syntax:
+///  * `NAME=value` lines, optionally starting with `export`
+///  * comments, starting with `#`
+///  * single-quoted values, used verbatim
+///  * double-quoted values, with `\n`, `\"` and `\\` escapes
+///  * `$NAME`, `${NAME}` and `${NAME:-default}` in unquoted and
+///    double-quoted values
+///
+/// Variables are resolved from the lines above, then using `lookup`.
+// Note: `lookup` is a parameter so that this can be tested without
+// touching the environment of the test process
+pub fn parse<F>(contents: &str, lookup: F) -> Result<Vec<(String, String)>, String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut res: Vec<(String, String)> = vec![];
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+        let error = |details: &str| format!("line {}: {}", i + 1, details);
+        let pos = line.find('=').ok_or_else(|| error("expected NAME=value"))?;
+        let name = line[..pos].trim();
+        let is_valid_name = !name.is_empty()
+            && !name.starts_with(|c: char| c.is_ascii_digit())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_valid_name {
+            return Err(error(&format!("invalid variable name: '{}'", name)));
+        }
+        let lookup = |name: &str| {
+            res.iter()
+                .rev()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.to_string())
+                .or_else(|| lookup(name))
+        };
+        let value = parse_value(line[pos + 1..].trim(), lookup).map_err(|e| error(&e))?;
+        res.push((name.to_string(), value));
+    }
+    Ok(res)
+}
+
+/// Same as `parse()`, for the file at `path`
+pub fn read<F>(path: &Path, lookup: F) -> Result<Vec<(String, String)>, Error>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let contents = std::fs::read_to_string(path).map_err(|e| new_read_error(e, path))?;
+    parse(&contents, lookup).map_err(|details| Error::MalformedConfig {
+        path: path.to_path_buf(),
+        details,
+    })
+}
+
+fn parse_value<F>(value: &str, lookup: F) -> Result<String, String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    if let Some(rest) = value.strip_prefix('\'') {
+        let end = rest.find('\'').ok_or("missing closing quote")?;
+        return Ok(rest[..end].to_string());
+    }
+    if let Some(rest) = value.strip_prefix('"') {
+        let mut unescaped = String::new();
+        let mut chars = rest.chars();
+        loop {
+            match chars.next() {
+                None => return Err("missing closing quote".to_string()),
+                Some('"') => break,
+                Some('\\') => match chars.next() {
+                    Some('n') => unescaped.push('\n'),
+                    Some(c) if "\"\\$".contains(c) => {
+                        unescaped.push('\\');
+                        unescaped.push(c)
+                    }
+                    Some(c) => {
+                        unescaped.push('\\');
+                        unescaped.push('\\');
+                        unescaped.push(c)
+                    }
+                    None => return Err("missing closing quote".to_string()),
+                },
+                Some(c) => unescaped.push(c),
+            }
+        }
+        return interpolate(&unescaped, &lookup);
+    }
+    // Note: in unquoted values, comments must be preceded by a space,
+    // so that `URL=http://example.com/#anchor` works
+    let value = match value.find(" #") {
+        Some(pos) => value[..pos].trim_end(),
+        None => value,
+    };
+    interpolate(value, &lookup)
+}
+
+/// Replace references to variables in `value`. A backslash prevents
+/// the next character from being interpreted
+fn interpolate<F>(value: &str, lookup: &F) -> Result<String, String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut res = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(next) = chars.next() {
+                    res.push(next);
+                }
+            }
+            '$' if chars.peek() == Some(&'{') => {
+                chars.next();
+                let mut expression = String::new();
+                loop {
+                    match chars.next() {
+                        None => return Err(format!("missing '}}' after '${{{}'", expression)),
+                        Some('}') => break,
+                        Some(c) => expression.push(c),
+                    }
+                }
+                let (name, default) = match expression.find(":-") {
+                    Some(pos) => (&expression[..pos], Some(&expression[pos + 2..])),
+                    None => (expression.as_str(), None),
+                };
+                let value = lookup(name).filter(|v| !v.is_empty());
+                res.push_str(
+                    &value
+                        .or_else(|| default.map(String::from))
+                        .unwrap_or_default(),
+                );
+            }
+            '$' if chars
+                .peek()
+                .is_some_and(|c| c.is_ascii_alphabetic() || *c == '_') =>
+            {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                res.push_str(&lookup(&name).unwrap_or_default());
+            }
+            c => res.push(c),
+        }
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_ok(contents: &str) -> Vec<(String, String)> {
+        parse(contents, |name| match name {
+            "HOME" => Some("/home/jane".to_string()),
+            "PATH" => Some("/usr/bin".to_string()),
+            _ => None,
+        })
+        .unwrap()
+    }
+
+    fn entry(name: &str, value: &str) -> (String, String) {
+        (name.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn test_simple() {
+        let contents = r#"
+# Database settings
+export DB_HOST=localhost
+DB_PORT = 5432 # the default
+URL=http://example.com/#anchor
+"#;
+        assert_eq!(
+            parse_ok(contents),
+            &[
+                entry("DB_HOST", "localhost"),
+                entry("DB_PORT", "5432"),
+                entry("URL", "http://example.com/#anchor"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_quotes() {
+        let contents = r#"
+SINGLE='$HOME is not expanded # here'
+DOUBLE="line 1\nsays \"hi\" from $HOME"
+"#;
+        assert_eq!(
+            parse_ok(contents),
+            &[
+                entry("SINGLE", "$HOME is not expanded # here"),
+                entry("DOUBLE", "line 1\nsays \"hi\" from /home/jane"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let contents = r#"
+DATA_DIR=${HOME}/data
+CACHE_DIR=$DATA_DIR/cache
+PATH=/opt/bin:${PATH}
+LEVEL=${LOG_LEVEL:-info}
+PRICE=\$5
+"#;
+        assert_eq!(
+            parse_ok(contents),
+            &[
+                entry("DATA_DIR", "/home/jane/data"),
+                entry("CACHE_DIR", "/home/jane/data/cache"),
+                entry("PATH", "/opt/bin:/usr/bin"),
+                entry("LEVEL", "info"),
+                entry("PRICE", "$5"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let lookup = |_: &str| None;
+        let error = parse("FOO=bar\nno equal sign\n", lookup).unwrap_err();
+        assert!(error.starts_with("line 2:"));
+        parse("1FOO=bar\n", lookup).unwrap_err();
+        parse("FOO='bar\n", lookup).unwrap_err();
+        parse("FOO=${BAR\n", lookup).unwrap_err();
+    }
+}
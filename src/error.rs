@@ -32,23 +32,49 @@ pub enum Error {
     GetProcessOutputError {
         io_error: std::io::Error,
     },
+    /// A process exited with a non-zero code, or was killed by a signal.
+    /// See `Error::exit_code()`
+    ProcessFailed {
+        command: String,
+        code: Option<i32>,
+        signal: Option<i32>,
+    },
 
     RunInfoPyError {
         message: String,
     },
 
+    NoMatchingPython {
+        requirement: String,
+    },
+    MissingPythonModule {
+        python: PathBuf,
+        version: String,
+        module: String,
+    },
+
     UpgradePipError {},
     ParsePipFreezeError {
         line: String,
     },
 
-    MissingSetupPy {},
+    MissingProjectFile {},
     MissingLock {
         expected_path: PathBuf,
     },
     MissingVenv {
         path: PathBuf,
     },
+    StaleVenv {
+        path: PathBuf,
+        details: String,
+    },
+    OutdatedLock {
+        path: PathBuf,
+    },
+    LockDrift {
+        lock_path: PathBuf,
+    },
 
     FileExists {
         path: PathBuf,
@@ -58,9 +84,46 @@ pub enum Error {
         message: String,
     },
 
+    UnknownProfile {
+        name: String,
+        known: Vec<String>,
+    },
+    UnknownTask {
+        name: String,
+        known: Vec<String>,
+    },
+    TaskCycle {
+        names: Vec<String>,
+    },
+    HashesWithGitDependencies {
+        names: Vec<String>,
+    },
+    MissingWorkspace {},
+    UnknownPlatformTag {
+        tag: String,
+    },
+    MissingFromWheelhouse {
+        wheelhouse: PathBuf,
+        missing: Vec<String>,
+    },
+    WorkspaceFailures {
+        failures: Vec<(PathBuf, Error)>,
+    },
+    MalformedConfig {
+        path: PathBuf,
+        details: String,
+    },
     MalformedLock {
         details: String,
     },
+    MalformedMarker {
+        marker: String,
+        details: String,
+    },
+    CannotNegateMarker {
+        name: String,
+        marker: String,
+    },
 
     NothingToBump {
         name: String,
@@ -75,6 +138,37 @@ pub enum Error {
     },
 }
 
+impl Error {
+    /// The exit code of dmenv when it fails with this error.
+    // Note: when a process we waited for failed, use its exit code, or
+    // 128 + the signal number if it was killed, like shells do. This way,
+    // `dmenv run --no-exec pytest` exits with the same code as `pytest`
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::ProcessFailed {
+                code: Some(code), ..
+            } => *code,
+            Error::ProcessFailed {
+                signal: Some(signal),
+                ..
+            } => 128 + signal,
+            _ => 1,
+        }
+    }
+}
+
+pub fn new_process_error(command: &Path, status: std::process::ExitStatus) -> Error {
+    #[cfg(unix)]
+    let signal = std::os::unix::process::ExitStatusExt::signal(&status);
+    #[cfg(not(unix))]
+    let signal = None;
+    Error::ProcessFailed {
+        command: command.to_string_lossy().to_string(),
+        code: status.code(),
+        signal,
+    }
+}
+
 pub fn new_error(message: String) -> Error {
     Error::Other { message }
 }
@@ -113,7 +207,6 @@ impl std::fmt::Display for Error {
                 format!("could not get current working directory: {}", io_error)
             }
 
-
             Error::StartProcessError { message } => format!("could not start process: {}", message),
             Error::WaitProcessError { io_error } => {
                 format!("could not wait for process: {}", io_error)
@@ -122,13 +215,34 @@ impl std::fmt::Display for Error {
                 format!("could not get process output: {}", io_error)
             }
 
+            Error::ProcessFailed {
+                command,
+                code,
+                signal,
+            } => match (code, signal) {
+                (Some(code), _) => format!("`{}` exited with code {}", command, code),
+                (None, Some(signal)) => format!("`{}` was killed by signal {}", command, signal),
+                (None, None) => format!("`{}` failed", command),
+            },
+
             Error::RunInfoPyError { message } => {
                 format!("could not determine Python version and platform while running the `info.py` script: {}",
                       message)
-            },
+            }
 
-            Error::MissingSetupPy {} => {
-                "setup.py not found.\nYou may want to run `dmenv init` now".to_string()
+            Error::NoMatchingPython { requirement } => format!(
+                "no Python interpreter matching '{}' found.\nYou may want to run `dmenv show:pythons` to see the interpreters dmenv found",
+                requirement
+            ),
+            Error::MissingPythonModule { python, version, module } => format!(
+                "the `{}` module is not available in {} (Python {}).\nOn Debian and Ubuntu, you may want to install the `python3-venv` package, or set the DMENV_NO_VENV_STDLIB environment variable to use `virtualenv` instead",
+                module,
+                python.display(),
+                version
+            ),
+            Error::MissingProjectFile {} => {
+                "neither setup.py nor pyproject.toml found.\nYou may want to run `dmenv init` now"
+                    .to_string()
             }
             Error::MissingLock { expected_path } => format!(
                 "{} not found.\nYou may want to run `dmenv lock` now",
@@ -139,6 +253,19 @@ impl std::fmt::Display for Error {
                 message.push_str("Please run `dmenv lock` or `dmenv install` to create it");
                 message
             }
+            Error::StaleVenv { path, details } => format!(
+                "virtualenv in '{}' is stale: {}\nYou may want to run `dmenv clean`, or `dmenv --recreate-if-stale install`",
+                path.display(),
+                details
+            ),
+            Error::OutdatedLock { path } => format!(
+                "{} is not up to date.\nYou may want to run `dmenv lock` now",
+                path.display()
+            ),
+            Error::LockDrift { lock_path } => format!(
+                "virtualenv does not match {}.\nYou may want to run `dmenv sync` now",
+                lock_path.display()
+            ),
 
             Error::ParsePipFreezeError { line } => {
                 format!("could not parse `pip freeze` output at line: '{}'", line)
@@ -149,7 +276,69 @@ impl std::fmt::Display for Error {
 
             Error::FileExists { path } => format!("{} already exists", path.display()),
 
+            Error::UnknownProfile { name, known } => format!(
+                "unknown profile: '{}' (known profiles: {})",
+                name,
+                known.join(", ")
+            ),
+            Error::UnknownTask { name, known } => {
+                if known.is_empty() {
+                    format!("unknown task: '{}' (no tasks are defined)", name)
+                } else {
+                    format!("unknown task: '{}' (known tasks: {})", name, known.join(", "))
+                }
+            }
+            Error::TaskCycle { names } => {
+                format!("tasks depend on each other: {}", names.join(" -> "))
+            }
+            Error::UnknownPlatformTag { tag } => format!(
+                "unknown platform tag '{}'. Expected a tag like `win_amd64`, \
+                 `macosx_10_9_x86_64` or `manylinux2014_x86_64`",
+                tag
+            ),
+            Error::MissingFromWheelhouse {
+                wheelhouse,
+                missing,
+            } => {
+                let mut message = format!(
+                    "the following locked packages are missing from {}:\n",
+                    wheelhouse.display()
+                );
+                for name in missing {
+                    message.push_str(&format!("  {}\n", name));
+                }
+                message.push_str("You may want to run `dmenv download` where the index is reachable");
+                message
+            }
+            Error::HashesWithGitDependencies { names } => format!(
+                "hashes can't be recorded in a lock containing dependencies installed from git: {}\n\
+                 pip does not support them in hash-checking mode",
+                names.join(", ")
+            ),
+            Error::MissingWorkspace {} => format!(
+                "Could not find {} in any of the parent directories",
+                crate::workspace::WORKSPACE_FILENAME
+            ),
+            Error::WorkspaceFailures { failures } => {
+                let mut message = format!("{} workspace member(s) failed:", failures.len());
+                for (path, error) in failures {
+                    message.push_str(&format!("\n* {}: {}", path.display(), error));
+                }
+                message
+            }
+            Error::MalformedConfig { path, details } => {
+                format!("Malformed config in {}: {}", path.display(), details)
+            }
             Error::MalformedLock { details } => format!("Malformed lock: {}", details),
+            Error::MalformedMarker { marker, details } => {
+                format!("invalid marker `{}`: {}", marker, details)
+            }
+            Error::CannotNegateMarker { name, marker } => format!(
+                "cannot add a line for '{}' that does not overlap with the existing ones: \
+                 marker `{}` cannot be negated.\n\
+                 Please rewrite it without the `~=` and `===` operators",
+                name, marker
+            ),
 
             Error::NothingToBump { name } => format!("'{}' not found in lock", name),
             Error::MultipleBumps { name } => {
@@ -0,0 +1,173 @@
+# Print the sha256 hashes of every artifact of a release, one per line,
+# so that the lock can be installed on any platform and with any
+# version of Python.
+#
+# Usage: python hashes.py <name> <version> [pip options]
+#
+# Note: this script is run with the Python of the virtualenv, and only
+# uses the standard library. Hashes are read from the links of the
+# "simple" pages of the indexes (see PEP 503), like pip does, and
+# the artifacts are only downloaded when their links have no hash
+import argparse
+import base64
+import hashlib
+import os
+import re
+import ssl
+import sys
+
+from html.parser import HTMLParser
+from urllib.parse import urljoin, urlsplit, urlunsplit
+from urllib.request import Request, urlopen
+
+DEFAULT_INDEX_URL = "https://pypi.org/simple"
+SDIST_EXTENSIONS = (".tar.gz", ".tar.bz2", ".tar.xz", ".tgz", ".zip")
+
+
+def normalize_name(name):
+    return re.sub(r"[-_.]+", "-", name).lower()
+
+
+def release_of(file_name):
+    """Return the normalized name and the version of an artifact,
+    or None if this is not a wheel nor a source distribution"""
+    if file_name.endswith(".whl"):
+        parts = file_name[: -len(".whl")].split("-")
+        if len(parts) not in (5, 6):
+            return None
+        return normalize_name(parts[0]), parts[1].lower()
+    for extension in SDIST_EXTENSIONS:
+        if file_name.endswith(extension):
+            stem = file_name[: -len(extension)]
+            name, _, version = stem.rpartition("-")
+            if not name:
+                return None
+            return normalize_name(name), version.lower()
+    return None
+
+
+class LinkParser(HTMLParser):
+    def __init__(self):
+        super().__init__()
+        self.links = []
+
+    def handle_starttag(self, tag, attrs):
+        if tag == "a":
+            href = dict(attrs).get("href")
+            if href:
+                self.links.append(href)
+
+
+class Fetcher:
+    def __init__(self, trusted_hosts):
+        self.trusted_hosts = trusted_hosts
+        self.credentials = {}
+
+    def open(self, url):
+        """Open the given URL, using the credentials found in the
+        index URLs for the same host, if any"""
+        parts = urlsplit(url)
+        netloc = parts.hostname or ""
+        if parts.port:
+            netloc += ":%d" % parts.port
+        if parts.username:
+            user_pass = "%s:%s" % (parts.username, parts.password or "")
+            token = base64.b64encode(user_pass.encode()).decode()
+            self.credentials[netloc] = "Basic " + token
+        url = urlunsplit(parts._replace(netloc=netloc))
+        request = Request(url)
+        if netloc in self.credentials:
+            request.add_header("Authorization", self.credentials[netloc])
+        context = None
+        if parts.hostname in self.trusted_hosts:
+            context = ssl._create_unverified_context()
+        return urlopen(request, context=context)
+
+    def links(self, url):
+        """Return the absolute URLs of the links of the given page"""
+        try:
+            with self.open(url) as response:
+                base_url = response.geturl()
+                contents = response.read().decode("utf-8", "replace")
+        except OSError as e:
+            # Note: indexes return 404 for unknown projects
+            if getattr(e, "code", None) == 404:
+                return []
+            raise
+        parser = LinkParser()
+        parser.feed(contents)
+        return [urljoin(base_url, link) for link in parser.links]
+
+
+def hash_file(path):
+    digest = hashlib.sha256()
+    with open(path, "rb") as f:
+        for chunk in iter(lambda: f.read(65536), b""):
+            digest.update(chunk)
+    return digest.hexdigest()
+
+
+def hash_url(fetcher, url):
+    digest = hashlib.sha256()
+    with fetcher.open(url) as response:
+        for chunk in iter(lambda: response.read(65536), b""):
+            digest.update(chunk)
+    return digest.hexdigest()
+
+
+def hashes_from_links(fetcher, links, release):
+    res = set()
+    for link in links:
+        url, _, fragment = link.partition("#")
+        file_name = os.path.basename(urlsplit(url).path)
+        if release_of(file_name) != release:
+            continue
+        if fragment.startswith("sha256="):
+            res.add(fragment[len("sha256=") :])
+        else:
+            res.add(hash_url(fetcher, url))
+    return res
+
+
+def hashes_from_dir(path, release):
+    res = set()
+    for file_name in os.listdir(path):
+        if release_of(file_name) == release:
+            res.add(hash_file(os.path.join(path, file_name)))
+    return res
+
+
+def main():
+    parser = argparse.ArgumentParser()
+    parser.add_argument("name")
+    parser.add_argument("version")
+    parser.add_argument("--index-url", default=DEFAULT_INDEX_URL)
+    parser.add_argument("--extra-index-url", action="append", default=[])
+    parser.add_argument("--find-links", action="append", default=[])
+    parser.add_argument("--trusted-host", action="append", default=[])
+    parser.add_argument("--no-index", action="store_true")
+    # Note: other pip options, like --constraint, are ignored
+    args, _ = parser.parse_known_args()
+
+    name = normalize_name(args.name)
+    release = (name, args.version.lower())
+    fetcher = Fetcher(args.trusted_host)
+    index_urls = [] if args.no_index else [args.index_url] + args.extra_index_url
+    hashes = set()
+    for index_url in index_urls:
+        page_url = "%s/%s/" % (index_url.rstrip("/"), name)
+        hashes |= hashes_from_links(fetcher, fetcher.links(page_url), release)
+    for location in args.find_links:
+        if os.path.isdir(location):
+            hashes |= hashes_from_dir(location, release)
+        else:
+            hashes |= hashes_from_links(fetcher, fetcher.links(location), release)
+
+    if not hashes:
+        sys.exit("No artifacts found for %s==%s" % (args.name, args.version))
+    for value in sorted(hashes):
+        print("sha256:" + value)
+
+
+if __name__ == "__main__":
+    main()
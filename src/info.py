@@ -1,4 +1,80 @@
+# Note: this script is run with every Python interpreter dmenv
+# finds, so it must stay compatible with Python 2.7.
+# Keep the keys in sync with src/python_info.rs
+import json
+import os
 import platform
+import sys
+import sysconfig
 
-print(platform.python_version())
-print(platform.system())
+
+def has_module(name):
+    try:
+        from importlib.util import find_spec
+    except ImportError:
+        import imp
+
+        try:
+            imp.find_module(name)
+            return True
+        except ImportError:
+            return False
+    return find_spec(name) is not None
+
+
+def get_pip_version():
+    try:
+        from importlib.metadata import version
+
+        return version("pip")
+    except ImportError:
+        pass
+    except Exception:
+        return None
+    try:
+        import pip
+
+        return pip.__version__
+    except ImportError:
+        return None
+
+
+def get_implementation_name():
+    implementation = getattr(sys, "implementation", None)
+    if implementation:
+        return implementation.name
+    return platform.python_implementation().lower()
+
+
+def get_python_tag():
+    short_names = {"cpython": "cp", "pypy": "pp", "ironpython": "ip", "jython": "jy"}
+    name = get_implementation_name()
+    prefix = short_names.get(name, name)
+    return "%s%d%d" % (prefix, sys.version_info[0], sys.version_info[1])
+
+
+def main():
+    base_prefix = getattr(sys, "base_prefix", getattr(sys, "real_prefix", sys.prefix))
+    info = {
+        "executable": sys.executable,
+        "version": platform.python_version(),
+        "version_info": list(sys.version_info[:5]),
+        "implementation_name": get_implementation_name(),
+        "platform_python_implementation": platform.python_implementation(),
+        "platform_system": platform.system(),
+        "platform_machine": platform.machine(),
+        "platform_release": platform.release(),
+        "sys_platform": sys.platform,
+        "os_name": os.name,
+        "python_tag": get_python_tag(),
+        "soabi": sysconfig.get_config_var("SOABI"),
+        "prefix": sys.prefix,
+        "base_prefix": base_prefix,
+        "has_venv": has_module("venv"),
+        "has_ensurepip": has_module("ensurepip"),
+        "pip_version": get_pip_version(),
+    }
+    print(json.dumps(info))
+
+
+main()
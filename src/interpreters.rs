@@ -0,0 +1,429 @@
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+
+use crate::error::*;
+use crate::markers::compare_values;
+use crate::python_info::PythonInfo;
+use crate::ui::print_warning;
+
+/// What the user asked for with the `--python` option
+#[derive(Debug, PartialEq)]
+pub enum PythonRequest {
+    /// A path, or a binary name to look for in PATH, like `python3.8`
+    Binary(String),
+    /// A version prefix, like `3.8`
+    Version(String),
+}
+
+impl PythonRequest {
+    pub fn parse(text: &str) -> Self {
+        let is_version = text.starts_with(|c: char| c.is_ascii_digit())
+            && text.chars().all(|c| c.is_ascii_digit() || c == '.');
+        if is_version {
+            PythonRequest::Version(text.to_string())
+        } else {
+            PythonRequest::Binary(text.to_string())
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum SpecifierOperator {
+    Compatible,
+    Equal,
+    NotEqual,
+    LessOrEqual,
+    GreaterOrEqual,
+    Less,
+    Greater,
+    Arbitrary,
+}
+
+// Note: longest operators first, so that `==` is not parsed as `=`
+// and `<=` is not parsed as `<`
+const OPERATORS: &[(&str, SpecifierOperator)] = &[
+    ("===", SpecifierOperator::Arbitrary),
+    ("~=", SpecifierOperator::Compatible),
+    ("==", SpecifierOperator::Equal),
+    ("!=", SpecifierOperator::NotEqual),
+    ("<=", SpecifierOperator::LessOrEqual),
+    (">=", SpecifierOperator::GreaterOrEqual),
+    ("<", SpecifierOperator::Less),
+    (">", SpecifierOperator::Greater),
+];
+
+/// One clause of a version specifier, like `>= 3.6` or `== 3.7.*`
+#[derive(Debug, Clone, PartialEq)]
+struct Specifier {
+    operator: SpecifierOperator,
+    version: String,
+    wildcard: bool,
+}
+
+impl Specifier {
+    fn parse(text: &str) -> Result<Self, Error> {
+        let text = text.trim();
+        let error = || new_error(format!("invalid version specifier: '{}'", text));
+        let (operator, rest) = OPERATORS
+            .iter()
+            .find(|(symbol, _)| text.starts_with(symbol))
+            .map(|(symbol, operator)| (*operator, text[symbol.len()..].trim()))
+            .ok_or_else(error)?;
+        let (version, wildcard) = match rest.strip_suffix(".*") {
+            Some(prefix) => (prefix, true),
+            None => (rest, false),
+        };
+        let wildcard_allowed = matches!(
+            operator,
+            SpecifierOperator::Equal | SpecifierOperator::NotEqual
+        );
+        if version.is_empty() || (wildcard && !wildcard_allowed) {
+            return Err(error());
+        }
+        if operator == SpecifierOperator::Compatible && !version.contains('.') {
+            return Err(error());
+        }
+        Ok(Specifier {
+            operator,
+            version: version.to_string(),
+            wildcard,
+        })
+    }
+
+    fn matches(&self, version: &str) -> bool {
+        let ordering = compare_values(version, &self.version);
+        match self.operator {
+            SpecifierOperator::Equal if self.wildcard => has_prefix(version, &self.version),
+            SpecifierOperator::NotEqual if self.wildcard => !has_prefix(version, &self.version),
+            SpecifierOperator::Equal => ordering == Ordering::Equal,
+            SpecifierOperator::NotEqual => ordering != Ordering::Equal,
+            SpecifierOperator::LessOrEqual => ordering != Ordering::Greater,
+            SpecifierOperator::GreaterOrEqual => ordering != Ordering::Less,
+            SpecifierOperator::Less => ordering == Ordering::Less,
+            SpecifierOperator::Greater => ordering == Ordering::Greater,
+            SpecifierOperator::Arbitrary => version == self.version,
+            SpecifierOperator::Compatible => {
+                // `~= 3.6` means `>= 3.6, == 3.*`
+                let components: Vec<_> = self.version.split('.').collect();
+                let prefix = components[..components.len() - 1].join(".");
+                ordering != Ordering::Less && has_prefix(version, &prefix)
+            }
+        }
+    }
+}
+
+/// Whether the components of `prefix` are the first components of `version`
+fn has_prefix(version: &str, prefix: &str) -> bool {
+    let mut version = version.split('.');
+    prefix.split('.').all(|p| version.next() == Some(p))
+}
+
+/// A comma-separated list of version specifiers, as found in
+/// the `python_requires` or `requires-python` project metadata
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Specifiers(Vec<Specifier>);
+
+impl Specifiers {
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let specifiers: Result<Vec<_>, _> = text
+            .split(',')
+            .filter(|x| !x.trim().is_empty())
+            .map(Specifier::parse)
+            .collect();
+        Ok(Specifiers(specifiers?))
+    }
+
+    /// Match versions starting with the given prefix, like `--python 3.8` does
+    pub fn version_prefix(prefix: &str) -> Self {
+        Specifiers(vec![Specifier {
+            operator: SpecifierOperator::Equal,
+            version: prefix.to_string(),
+            wildcard: true,
+        }])
+    }
+
+    pub fn extend(&mut self, other: Specifiers) {
+        self.0.extend(other.0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn matches(&self, version: &str) -> bool {
+        self.0.iter().all(|s| s.matches(version))
+    }
+}
+
+/// Find the Python interpreter to use.
+//
+// Notes:
+// * An explicit binary is always used as is
+// * Otherwise, use `python3` or `python` from PATH, like dmenv always did,
+//   unless it does not match the requested version or the project requirement.
+//   In that case, look for the newest matching interpreter among the ones
+//   found by `discover()`
+pub fn find_python(
+    requested: &Option<String>,
+    requirement: Option<&str>,
+) -> Result<PythonInfo, Error> {
+    let mut specifiers = Specifiers::default();
+    let mut description = vec![];
+    if let Some(requested) = requested {
+        match PythonRequest::parse(requested) {
+            PythonRequest::Binary(binary) => return PythonInfo::from_binary(Path::new(&binary)),
+            PythonRequest::Version(version) => {
+                specifiers.extend(Specifiers::version_prefix(&version));
+                description.push(version);
+            }
+        }
+    }
+    // Note: an invalid requirement in the project must not prevent
+    // using dmenv, so it is ignored with a warning
+    if let Some(requirement) = requirement {
+        match Specifiers::parse(requirement) {
+            Ok(parsed) => {
+                specifiers.extend(parsed);
+                description.push(requirement.to_string());
+            }
+            Err(e) => print_warning(&format!(
+                "Ignoring the Python requirement of the project: {}",
+                e
+            )),
+        }
+    }
+
+    if specifiers.is_empty() {
+        return PythonInfo::from_binary(&default_binary()?);
+    }
+    if let Ok(binary) = default_binary() {
+        if let Ok(info) = PythonInfo::from_binary(&binary) {
+            if specifiers.matches(&info.version) {
+                return Ok(info);
+            }
+        }
+    }
+    let mut candidates: Vec<_> = discover()
+        .into_iter()
+        .filter(|info| specifiers.matches(&info.version))
+        .collect();
+    candidates.sort_by(|a, b| compare_values(&b.version, &a.version));
+    candidates
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::NoMatchingPython {
+            requirement: description.join(", "),
+        })
+}
+
+/// Look for a suitable Python binary in PATH
+fn default_binary() -> Result<PathBuf, Error> {
+    if let Ok(python3) = which::which("python3") {
+        return Ok(python3);
+    }
+
+    which::which("python")
+        .map_err(|_| new_error("Neither `python3` nor `python` found in PATH".to_string()))
+}
+
+/// Find all the Python interpreters installed on the machine, by running
+/// the info.py script with every candidate binary.
+// Notes:
+// * binaries that fail to run are skipped - for instance, pyenv shims
+//   for versions that are not selected
+// * so are binaries from virtualenvs
+// * several binaries may point to the same interpreter (symlinks, pyenv
+//   shims ...), so keep the first one for each `sys.executable`
+pub fn discover() -> Vec<PythonInfo> {
+    let canonical = |p: &Path| p.canonicalize().unwrap_or_else(|_| p.to_path_buf());
+    let mut seen_binaries = vec![];
+    let mut seen_executables = vec![];
+    let mut res = vec![];
+    for binary in candidate_binaries() {
+        let canonical_binary = canonical(&binary);
+        if seen_binaries.contains(&canonical_binary) {
+            continue;
+        }
+        seen_binaries.push(canonical_binary);
+        let info = match PythonInfo::from_binary(&binary) {
+            Ok(info) => info,
+            Err(_) => continue,
+        };
+        let executable = canonical(&info.executable);
+        if info.in_venv() || seen_executables.contains(&executable) {
+            continue;
+        }
+        seen_executables.push(executable);
+        res.push(info);
+    }
+    res
+}
+
+fn candidate_binaries() -> Vec<PathBuf> {
+    let mut res = vec![];
+    for dir in candidate_dirs() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        let mut binaries: Vec<_> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_file() && is_python_binary(p))
+            .collect();
+        binaries.sort();
+        res.extend(binaries);
+    }
+    res
+}
+
+/// Directories that may contain Python binaries: entries of PATH,
+/// then pyenv versions, conda environments and system directories
+fn candidate_dirs() -> Vec<PathBuf> {
+    let mut res = vec![];
+    if let Some(path) = std::env::var_os("PATH") {
+        res.extend(std::env::split_paths(&path));
+    }
+    let home = std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from);
+    let pyenv_root = std::env::var_os("PYENV_ROOT")
+        .map(PathBuf::from)
+        .or_else(|| home.as_ref().map(|h| h.join(".pyenv")));
+    if let Some(pyenv_root) = pyenv_root {
+        res.extend(sub_dirs(&pyenv_root.join("versions")).map(|v| bin_dir(&v)));
+    }
+    if let Some(conda_prefix) = std::env::var_os("CONDA_PREFIX") {
+        res.push(bin_dir(Path::new(&conda_prefix)));
+    }
+    if let Some(home) = &home {
+        for name in &["anaconda3", "miniconda3", "miniforge3"] {
+            let conda_root = home.join(name);
+            res.push(bin_dir(&conda_root));
+            res.extend(sub_dirs(&conda_root.join("envs")).map(|e| bin_dir(&e)));
+        }
+    }
+    if cfg!(unix) {
+        res.push(PathBuf::from("/usr/local/bin"));
+        res.push(PathBuf::from("/usr/bin"));
+    }
+    res
+}
+
+fn sub_dirs(path: &Path) -> impl Iterator<Item = PathBuf> {
+    let mut res: Vec<_> = std::fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .collect();
+    res.sort();
+    res.into_iter()
+}
+
+// Note: on Windows, conda and pyenv-win put `python.exe` at the root
+// of the installation, not in a `Scripts` sub-directory
+fn bin_dir(prefix: &Path) -> PathBuf {
+    if cfg!(windows) {
+        prefix.to_path_buf()
+    } else {
+        prefix.join("bin")
+    }
+}
+
+/// Match `python`, `python3` and `python3.X`, but not `python3-config`
+/// or `python3.8m-config`
+fn is_python_binary(path: &Path) -> bool {
+    let name = match path.file_name() {
+        Some(name) => name.to_string_lossy(),
+        None => return false,
+    };
+    let name = if cfg!(windows) {
+        match name.strip_suffix(".exe") {
+            Some(stem) => stem.to_string(),
+            None => return false,
+        }
+    } else {
+        name.to_string()
+    };
+    match name.strip_prefix("python3.") {
+        Some(minor) => !minor.is_empty() && minor.chars().all(|c| c.is_ascii_digit()),
+        None => name == "python" || name == "python3",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(specifiers: &str, version: &str) -> bool {
+        Specifiers::parse(specifiers).unwrap().matches(version)
+    }
+
+    #[test]
+    fn test_parse_request() {
+        assert_eq!(
+            PythonRequest::parse("3.9"),
+            PythonRequest::Version("3.9".to_string())
+        );
+        assert_eq!(
+            PythonRequest::parse("python3.9"),
+            PythonRequest::Binary("python3.9".to_string())
+        );
+        assert_eq!(
+            PythonRequest::parse("/usr/bin/python3"),
+            PythonRequest::Binary("/usr/bin/python3".to_string())
+        );
+    }
+
+    #[test]
+    fn test_comparisons() {
+        assert!(matches(">=3.6", "3.8.1"));
+        assert!(matches(">= 3.6, < 4", "3.6"));
+        assert!(!matches(">=3.6, <4", "3.5.9"));
+        assert!(!matches(">3.6", "3.6.0"));
+        assert!(matches("<=3.7", "3.7.0"));
+    }
+
+    #[test]
+    fn test_wildcards() {
+        assert!(matches("==3.7.*", "3.7.4"));
+        assert!(!matches("==3.7.*", "3.8.0"));
+        assert!(matches("!=3.0.*, !=3.1.*", "3.7.4"));
+        assert!(!matches("!=3.0.*, !=3.1.*", "3.1.2"));
+    }
+
+    #[test]
+    fn test_compatible_release() {
+        assert!(matches("~=3.6", "3.9.1"));
+        assert!(!matches("~=3.6", "4.0"));
+        assert!(!matches("~=3.6.2", "3.6.1"));
+        assert!(!matches("~=3.6.2", "3.7.0"));
+    }
+
+    #[test]
+    fn test_version_prefix() {
+        let specifiers = Specifiers::version_prefix("3.8");
+        assert!(specifiers.matches("3.8.10"));
+        assert!(!specifiers.matches("3.80.0"));
+        assert!(!specifiers.matches("3.9.0"));
+    }
+
+    #[test]
+    fn test_invalid_specifiers() {
+        Specifiers::parse("3.6").unwrap_err();
+        Specifiers::parse(">=3.6.*").unwrap_err();
+        Specifiers::parse("~=3").unwrap_err();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_is_python_binary() {
+        assert!(is_python_binary(Path::new("/usr/bin/python3")));
+        assert!(is_python_binary(Path::new("/usr/bin/python3.11")));
+        assert!(!is_python_binary(Path::new("/usr/bin/python3-config")));
+        assert!(!is_python_binary(Path::new("/usr/bin/python3.8m-config")));
+        assert!(!is_python_binary(Path::new("/usr/bin/pythonw")));
+    }
+}
@@ -1,37 +1,54 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+mod activate;
 mod cli;
+mod config;
 mod dependencies;
+mod dotenv;
 mod error;
 #[cfg(unix)]
 mod execv;
+mod interpreters;
 mod lock;
+mod markers;
 mod operations;
 mod paths;
+mod project;
 mod python_info;
 mod run;
 mod settings;
+#[cfg(unix)]
+mod signals;
+mod tasks;
 mod ui;
+mod wheelhouse;
 #[cfg(windows)]
 mod win_job;
+mod workspace;
 
 use crate::cli::commands;
 use crate::cli::syntax::SubCommand;
+use crate::markers::Marker;
 use crate::paths::{Paths, PathsResolver};
+use crate::project::ProjectKind;
 use crate::python_info::PythonInfo;
 use crate::run::VenvRunner;
+use crate::workspace::Workspace;
 
 pub use crate::cli::syntax::Command;
 pub use crate::error::*;
 pub use crate::paths::{DEV_LOCK_FILENAME, PROD_LOCK_FILENAME};
 pub use crate::settings::Settings;
-pub use crate::ui::{print_error, print_info_1, print_info_2};
+pub use crate::ui::{print_error, print_info_1, print_info_2, print_warning};
 
 #[derive(Debug)]
 pub struct Metadata {
     pub dmenv_version: String,
     pub python_platform: String,
     pub python_version: String,
+    /// Lines with pip options to write at the top of the lock,
+    /// or None to keep the ones already there
+    pub lock_options: Option<Vec<String>>,
 }
 
 #[derive(Debug)]
@@ -51,12 +68,57 @@ pub enum BumpType {
     Simple,
 }
 
+/// Output format of the `show:*` commands, see the `--format` option
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            other => Err(new_error(format!(
+                "unknown format: '{}' (should be 'text' or 'json')",
+                other
+            ))),
+        }
+    }
+}
+
+/// What to do with a lock once it has been computed,
+/// see the `--dry-run` and `--diff` options
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DryRun {
+    /// Write the lock
+    Off,
+    /// Leave the lock untouched, and show a summary of the changes
+    Summary,
+    /// Leave the lock untouched, and show the changes as a unified diff
+    Diff,
+}
+
+impl DryRun {
+    fn new(dry_run: bool, diff: bool) -> Self {
+        match (dry_run, diff) {
+            (false, _) => DryRun::Off,
+            (true, false) => DryRun::Summary,
+            (true, true) => DryRun::Diff,
+        }
+    }
+}
+
 #[derive(Default, Debug)]
 /// Represents options passed to `dmenv lock`,
 /// see `cmd::SubCommand::Lock`
 pub struct UpdateLockOptions {
-    pub python_version: Option<String>,
+    pub python_version: Option<Marker>,
     pub sys_platform: Option<String>,
+    pub hashes: bool,
 }
 
 #[derive(Debug)]
@@ -65,6 +127,8 @@ pub struct Context {
     python_info: PythonInfo,
     settings: Settings,
     venv_runner: VenvRunner,
+    /// The workspace the project belongs to, if any
+    workspace: Option<Workspace>,
 }
 
 fn get_context(cmd: &Command) -> Result<Context, Error> {
@@ -73,10 +137,27 @@ fn get_context(cmd: &Command) -> Result<Context, Error> {
     } else {
         look_up_for_project_path()?
     };
-    let python_info = PythonInfo::new(&cmd.python_binary)?;
-    let python_version = python_info.version.clone();
-    let settings = Settings::from_shell(&cmd);
-    let paths_resolver = PathsResolver::new(project_path.clone(), python_version, &settings);
+    let workspace = match Workspace::find(&project_path) {
+        Ok(workspace) => workspace,
+        Err(error) => ignore_workspace_error(&project_path, error)?,
+    };
+    // Note: when the virtualenv is shared, every member of the workspace
+    // uses the settings, the virtualenv and the lock of the workspace root
+    let shared_workspace = workspace.as_ref().filter(|w| w.is_shared_by(&project_path));
+    let (project_path, requirement) = match shared_workspace {
+        Some(workspace) => (
+            workspace.root.to_path_buf(),
+            workspace.python_requirement()?,
+        ),
+        None => {
+            let requirement = project::python_requirement(&project_path)?;
+            (project_path, requirement)
+        }
+    };
+    let settings = Settings::load(cmd, &project_path)?;
+    let python_info = interpreters::find_python(&settings.python, requirement.as_deref())?;
+    let python_dir_name = python_info.venv_dir_name();
+    let paths_resolver = PathsResolver::new(project_path.clone(), python_dir_name, &settings);
     let paths = paths_resolver.paths()?;
     let venv_runner = VenvRunner::new(&project_path, &paths.venv);
     Ok(Context {
@@ -84,24 +165,44 @@ fn get_context(cmd: &Command) -> Result<Context, Error> {
         python_info,
         settings,
         venv_runner,
+        workspace,
     })
 }
 
+/// A broken workspace only matters to the projects it contains:
+/// for the others, print a warning and carry on without a workspace
+fn ignore_workspace_error(project_path: &Path, error: Error) -> Result<Option<Workspace>, Error> {
+    let root = match Workspace::find_root(project_path)? {
+        None => return Err(error),
+        Some(root) => root,
+    };
+    if Workspace::contains(&root, project_path) {
+        return Err(error);
+    }
+    print_warning(&format!(
+        "Ignoring the workspace in {}: {}",
+        root.display(),
+        error
+    ));
+    Ok(None)
+}
+
 fn look_up_for_project_path() -> Result<PathBuf, Error> {
     let mut candidate = std::env::current_dir()
         .map_err(|e| new_error(format!("Could not get current directory: {}", e)))?;
     loop {
-        let setup_py_path = candidate.join("setup.py");
-        if setup_py_path.exists() {
+        // Note: the root of a workspace is used as the project when
+        // the workspace has a shared virtualenv, see `get_context()`
+        let is_workspace_root = candidate.join(workspace::WORKSPACE_FILENAME).exists();
+        if ProjectKind::detect(&candidate)?.is_some() || is_workspace_root {
             return Ok(candidate);
         } else {
             let parent = candidate.parent();
             match parent {
-                None => {
-                    return Err(new_error(
-                        "Could not find setup.py in any of the parent directories".to_string(),
-                    ))
-                }
+                None => return Err(new_error(
+                    "Could not find setup.py or pyproject.toml in any of the parent directories"
+                        .to_string(),
+                )),
                 Some(p) => candidate = p.to_path_buf(),
             }
         }
@@ -109,6 +210,9 @@ fn look_up_for_project_path() -> Result<PathBuf, Error> {
 }
 
 pub fn run_cmd(cmd: Command) -> Result<(), Error> {
+    if cmd.workspace {
+        return run_workspace(cmd);
+    }
     let context = get_context(&cmd);
 
     match &cmd.sub_cmd {
@@ -117,9 +221,17 @@ pub fn run_cmd(cmd: Command) -> Result<(), Error> {
             version,
             author,
             no_setup_cfg,
-        } => commands::init(cmd.project_path, name, version, author, !no_setup_cfg),
+            pyproject,
+        } => commands::init(
+            cmd.project_path,
+            name,
+            version,
+            author,
+            !no_setup_cfg,
+            *pyproject,
+        ),
 
-        SubCommand::Install { no_develop } => {
+        SubCommand::Install { no_develop, .. } => {
             let post_install_action = if *no_develop {
                 PostInstallAction::None
             } else {
@@ -128,9 +240,14 @@ pub fn run_cmd(cmd: Command) -> Result<(), Error> {
             commands::install(&context?, post_install_action)
         }
 
+        SubCommand::Freeze {} => commands::freeze(&context?),
         SubCommand::Create {} => commands::create_venv(&context?),
         SubCommand::Clean {} => commands::clean_venv(context?),
         SubCommand::Develop {} => commands::develop(&context?),
+        SubCommand::Download {
+            platforms,
+            python_version,
+        } => commands::download(&context?, platforms, python_version.as_deref()),
         SubCommand::UpgradePip {} => commands::upgrade_pip(&context?),
 
         SubCommand::ProcessScripts { force } => {
@@ -145,38 +262,161 @@ pub fn run_cmd(cmd: Command) -> Result<(), Error> {
         SubCommand::Lock {
             python_version,
             sys_platform,
+            hashes,
+            check,
+            matrix,
+            dry_run,
+            diff,
+            ..
         } => {
+            let dry_run = DryRun::new(*dry_run, *diff);
+            if !matrix.is_empty() {
+                return commands::merge_lock(&context?, matrix, dry_run);
+            }
             let update_options = UpdateLockOptions {
                 python_version: python_version.clone(),
                 sys_platform: sys_platform.clone(),
+                hashes: *hashes,
             };
-            commands::update_lock(&context?, update_options)
+            if *check {
+                return commands::check_lock(&context?, update_options);
+            }
+            commands::update_lock(&context?, update_options, dry_run)
         }
 
-        SubCommand::BumpInLock { name, version, git } => {
+        SubCommand::BumpInLock {
+            name,
+            version,
+            git,
+            dry_run,
+            diff,
+        } => {
             let bump_type = if *git {
                 BumpType::Git
             } else {
                 BumpType::Simple
             };
-            commands::bump_in_lock(&context?, name, version, bump_type)
+            let dry_run = DryRun::new(*dry_run, *diff);
+            commands::bump_in_lock(&context?, name, version, bump_type, dry_run)
         }
 
-        SubCommand::Run { ref cmd, no_exec } => {
+        SubCommand::Run {
+            ref cmd,
+            no_exec,
+            env_files,
+            module,
+            code,
+        } => {
+            let cmd = &commands::python_cmd(module.as_deref(), code.as_deref(), cmd);
             if *no_exec {
-                commands::run(&context?, &cmd)
+                commands::run(&context?, cmd, env_files)
             } else {
-                commands::run_and_die(&context?, &cmd)
+                commands::run_and_die(&context?, cmd, env_files)
             }
         }
 
-        SubCommand::ShowDeps {} => commands::show_deps(&context?),
-        SubCommand::ShowOutDated {} => commands::show_outdated(&context?),
-        SubCommand::ShowVenvPath {} => commands::show_venv_path(&context?),
-        SubCommand::ShowVenvBin {} => commands::show_venv_bin_path(&context?),
+        SubCommand::ShowConfig { format } => commands::show_config(&context?, *format),
+        SubCommand::ShowDeps { format } => commands::show_deps(&context?, *format),
+        SubCommand::ShowInfo { format } => commands::show_info(&context?, *format),
+        SubCommand::ShowLockDrift { format } => commands::show_lock_drift(&context?, *format),
+        SubCommand::ShowPythons { format } => commands::show_pythons(*format),
+        SubCommand::ShowOutDated { format } => commands::show_outdated(&context?, *format),
+        SubCommand::ShowVenvPath { format } => commands::show_venv_path(&context?, *format),
+        SubCommand::ShowVenvBin { format } => commands::show_venv_bin_path(&context?, *format),
+
+        SubCommand::Shell {} => commands::shell(&context?),
+        SubCommand::Env { shell } => commands::print_env(&context?, *shell),
+
+        SubCommand::Sync {} => commands::sync(&context?),
+
+        SubCommand::Task { list, name, args } => match name {
+            Some(name) if !*list => commands::run_task(&context?, name, args),
+            _ => commands::list_tasks(&context?),
+        },
+
+        SubCommand::Tidy { dry_run, diff } => {
+            let dry_run = DryRun::new(*dry_run, *diff);
+            commands::tidy(&cmd, context?, dry_run)
+        }
+    }
+}
+
+/// Run the command in every member of the workspace, as if `--project`
+/// was used with the path of each member
+//
+// Notes:
+// * Members are processed one after the other, unless `--parallel` is used
+// * A failing member does not prevent the others from being processed:
+//   failures are collected and reported at the end
+fn run_workspace(cmd: Command) -> Result<(), Error> {
+    match &cmd.sub_cmd {
+        SubCommand::Lock { .. }
+        | SubCommand::Install { .. }
+        | SubCommand::Tidy { .. }
+        | SubCommand::ShowLockDrift { .. } => (),
+        _ => {
+            return Err(new_error(
+                "only lock, install, tidy and show:lock-drift can be used with --workspace"
+                    .to_string(),
+            ))
+        }
+    }
+    let start = if let Some(p) = &cmd.project_path {
+        PathBuf::from(p)
+    } else {
+        std::env::current_dir().map_err(|e| Error::NoWorkingDirectory { io_error: e })?
+    };
+    let workspace = Workspace::find(&start)?.ok_or(Error::MissingWorkspace {})?;
+    // Note: with a shared virtualenv, the command only needs to run once,
+    // from the workspace root
+    let members = if workspace.shared_venv {
+        vec![workspace.root.to_path_buf()]
+    } else {
+        workspace.member_paths()
+    };
+
+    let relative = |path: &PathBuf| match path.strip_prefix(&workspace.root) {
+        Ok(p) if p.as_os_str().is_empty() => PathBuf::from("."),
+        Ok(p) => p.to_path_buf(),
+        Err(_) => path.to_path_buf(),
+    };
+    let run_member = |member_path: &PathBuf| {
+        print_info_1(&format!("Processing {}", relative(member_path).display()));
+        let member_cmd = Command {
+            project_path: Some(member_path.to_string_lossy().into()),
+            workspace: false,
+            parallel: false,
+            ..cmd.clone()
+        };
+        run_cmd(member_cmd)
+    };
+    let results: Vec<_> = if cmd.parallel {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = members
+                .iter()
+                .map(|m| scope.spawn(move || run_member(m)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| {
+                    h.join()
+                        .unwrap_or_else(|_| Err(new_error("thread panicked".to_string())))
+                })
+                .collect()
+        })
+    } else {
+        members.iter().map(run_member).collect()
+    };
 
-        SubCommand::Tidy {} => commands::tidy(&cmd, context?),
+    let failures: Vec<_> = members
+        .into_iter()
+        .zip(results)
+        .filter_map(|(path, result)| result.err().map(|e| (relative(&path), e)))
+        .collect();
+    if !failures.is_empty() {
+        return Err(Error::WorkspaceFailures { failures });
     }
+    Ok(())
 }
 
 #[cfg(test)]
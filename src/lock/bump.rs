@@ -1,31 +1,23 @@
-use crate::dependencies::LockedDependency;
 use crate::error::Error;
+use crate::lock::Lock;
 use crate::BumpType;
 
-pub fn simple_bump(
-    dependencies: &mut [LockedDependency],
-    name: &str,
-    version: &str,
-) -> Result<bool, Error> {
-    bump_impl(dependencies, name, version, BumpType::Simple)
+pub fn simple_bump(lock: &mut Lock, name: &str, version: &str) -> Result<bool, Error> {
+    bump_impl(lock, name, version, BumpType::Simple)
 }
 
-pub fn git_bump(
-    dependencies: &mut [LockedDependency],
-    name: &str,
-    version: &str,
-) -> Result<bool, Error> {
-    bump_impl(dependencies, name, version, BumpType::Git)
+pub fn git_bump(lock: &mut Lock, name: &str, version: &str) -> Result<bool, Error> {
+    bump_impl(lock, name, version, BumpType::Git)
 }
 
 fn bump_impl(
-    dependencies: &mut [LockedDependency],
+    lock: &mut Lock,
     name: &str,
     version: &str,
     bump_type: BumpType,
 ) -> Result<bool, Error> {
-    let mut matching_names: Vec<_> = dependencies
-        .iter_mut()
+    let mut matching_names: Vec<_> = lock
+        .dependencies_mut()
         .filter(|x| x.name() == name)
         .collect();
     if matching_names.is_empty() {
@@ -57,41 +49,53 @@ mod tests {
     #[test]
     fn simple_change() {
         let lock_contents = "bar==0.3\nfoo==0.42\n";
-        let mut deps = parse(&lock_contents).unwrap();
-        let changed = simple_bump(&mut deps, "foo", "0.43").unwrap();
+        let mut lock = parse(lock_contents).unwrap();
+        let changed = simple_bump(&mut lock, "foo", "0.43").unwrap();
         assert!(changed);
-        let actual = dump(deps);
+        let actual = dump(&lock);
         assert_eq!(actual, "bar==0.3\nfoo==0.43\n");
     }
 
     #[test]
     fn simple_no_change() {
         let lock_contents = "bar==0.3\nfoo==0.42\n";
-        let mut deps = parse(&lock_contents).unwrap();
-        let changed = simple_bump(&mut deps, "foo", "0.42").unwrap();
+        let mut lock = parse(lock_contents).unwrap();
+        let changed = simple_bump(&mut lock, "foo", "0.42").unwrap();
         assert!(!changed);
-        let actual = dump(deps);
+        let actual = dump(&lock);
         assert_eq!(actual, "bar==0.3\nfoo==0.42\n");
     }
 
     #[test]
     fn dep_not_found() {
         let lock_contents = "bar==0.3\nfoo==0.42\n";
-        let mut deps = parse(&lock_contents).unwrap();
-        let actual_error = simple_bump(&mut deps, "no-such", "1.2");
+        let mut lock = parse(lock_contents).unwrap();
+        let actual_error = simple_bump(&mut lock, "no-such", "1.2");
         match actual_error {
             Err(Error::NothingToBump { name }) => assert_eq!(name, "no-such"),
             _ => panic!("Expecting NothingToBump, got: {:?}", actual_error),
         }
     }
 
+    #[test]
+    fn keep_comments() {
+        let lock_contents = "# Pinned because of #42\nbar==0.3\n\n# Other deps\nfoo==0.42\n";
+        let mut lock = parse(lock_contents).unwrap();
+        simple_bump(&mut lock, "bar", "0.4").unwrap();
+        let actual = dump(&lock);
+        assert_eq!(
+            actual,
+            "# Pinned because of #42\nbar==0.4\n\n# Other deps\nfoo==0.42\n"
+        );
+    }
+
     #[test]
     fn bump_git_ref() {
         let lock_contents = "git@example.com/bar.git@dae42f#egg=bar\n";
-        let mut deps = parse(&lock_contents).unwrap();
-        let changed = git_bump(&mut deps, "bar", "cda431").unwrap();
+        let mut lock = parse(lock_contents).unwrap();
+        let changed = git_bump(&mut lock, "bar", "cda431").unwrap();
         assert!(changed);
-        let actual = dump(deps);
+        let actual = dump(&lock);
         let expected = "git@example.com/bar.git@cda431#egg=bar\n";
         assert_eq!(actual, expected);
     }
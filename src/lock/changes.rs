@@ -0,0 +1,311 @@
+use std::cmp::Ordering;
+
+use crate::dependencies::LockedDependency;
+use crate::lock::Lock;
+use crate::markers::{compare_values, Marker};
+use crate::ui::format_table;
+
+/// A change made to one line of the lock
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    Added {
+        name: String,
+        requirement: String,
+    },
+    Removed {
+        name: String,
+        requirement: String,
+    },
+    Upgraded {
+        name: String,
+        old: String,
+        new: String,
+    },
+    Downgraded {
+        name: String,
+        old: String,
+        new: String,
+    },
+    MarkerChanged {
+        name: String,
+        old: Option<Marker>,
+        new: Option<Marker>,
+    },
+}
+
+impl Change {
+    fn name(&self) -> &str {
+        match self {
+            Change::Added { name, .. }
+            | Change::Removed { name, .. }
+            | Change::Upgraded { name, .. }
+            | Change::Downgraded { name, .. }
+            | Change::MarkerChanged { name, .. } => name,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Change::Added { .. } => "added",
+            Change::Removed { .. } => "removed",
+            Change::Upgraded { .. } => "upgraded",
+            Change::Downgraded { .. } => "downgraded",
+            Change::MarkerChanged { .. } => "marker changed",
+        }
+    }
+
+    /// Return what the line looked like before and after the change
+    fn before_after(&self) -> (String, String) {
+        let marker_or_none = |m: &Option<Marker>| match m {
+            Some(m) => m.to_string(),
+            None => "(no marker)".to_string(),
+        };
+        match self {
+            Change::Added { requirement, .. } => (String::new(), requirement.to_string()),
+            Change::Removed { requirement, .. } => (requirement.to_string(), String::new()),
+            Change::Upgraded { old, new, .. } | Change::Downgraded { old, new, .. } => {
+                (old.to_string(), new.to_string())
+            }
+            Change::MarkerChanged { old, new, .. } => (marker_or_none(old), marker_or_none(new)),
+        }
+    }
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let (before, after) = self.before_after();
+        match self {
+            Change::Added { .. } => write!(f, "+ {}", after),
+            Change::Removed { .. } => write!(f, "- {}", before),
+            _ => write!(f, "{}: {} -> {}", self.name(), before, after),
+        }
+    }
+}
+
+/// Every change between two versions of a lock.
+///
+/// This is computed by comparing the locks, instead of recording what
+/// `Updater`, `tidy` and the other operations do, so that every command
+/// reports its changes in the same way.
+#[derive(Debug, Default)]
+pub struct ChangeSet {
+    changes: Vec<Change>,
+}
+
+impl ChangeSet {
+    pub fn between(old: &Lock, new: &Lock) -> Self {
+        let mut names: Vec<_> = old
+            .dependencies()
+            .chain(new.dependencies())
+            .map(|d| d.name())
+            .collect();
+        names.sort_by_key(|n| n.to_lowercase());
+        names.dedup();
+
+        let mut changes = vec![];
+        for name in names {
+            let old_deps: Vec<_> = old.dependencies().filter(|d| d.name() == name).collect();
+            let new_deps: Vec<_> = new.dependencies().filter(|d| d.name() == name).collect();
+            changes.extend(Self::compare_lines(&name, old_deps, new_deps));
+        }
+        ChangeSet { changes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    /// Render the changes as a table, with one line per change
+    pub fn table(&self) -> String {
+        let mut rows = vec![vec![
+            "change".to_string(),
+            "name".to_string(),
+            "before".to_string(),
+            "after".to_string(),
+        ]];
+        for change in &self.changes {
+            let (before, after) = change.before_after();
+            rows.push(vec![
+                change.kind().to_string(),
+                change.name().to_string(),
+                before,
+                after,
+            ]);
+        }
+        format_table(&rows)
+    }
+
+    // Lines for the same dependency are paired in this order:
+    //  * lines with the same version and marker are unchanged
+    //  * then, lines with the same marker
+    //  * then, lines with the same version
+    //  * then, the remaining lines, in order
+    // Lines left after that have been added or removed.
+    fn compare_lines(
+        name: &str,
+        mut old: Vec<&LockedDependency>,
+        mut new: Vec<&LockedDependency>,
+    ) -> Vec<Change> {
+        type SameFn = fn(&LockedDependency, &LockedDependency) -> bool;
+        let passes: [SameFn; 4] = [
+            |a, b| a.version() == b.version() && a.marker() == b.marker(),
+            |a, b| a.marker() == b.marker(),
+            |a, b| a.version() == b.version(),
+            |_, _| true,
+        ];
+        let mut res = vec![];
+        for same in passes.iter() {
+            let mut i = 0;
+            while i < old.len() {
+                match new.iter().position(|n| same(old[i], n)) {
+                    Some(j) => {
+                        let (o, n) = (old.remove(i), new.remove(j));
+                        res.extend(Self::compare_line(name, o, n));
+                    }
+                    None => i += 1,
+                }
+            }
+        }
+        for dep in old {
+            res.push(Change::Removed {
+                name: name.to_string(),
+                requirement: requirement(dep),
+            });
+        }
+        for dep in new {
+            res.push(Change::Added {
+                name: name.to_string(),
+                requirement: requirement(dep),
+            });
+        }
+        res
+    }
+
+    fn compare_line(name: &str, old: &LockedDependency, new: &LockedDependency) -> Vec<Change> {
+        let mut res = vec![];
+        let (old_version, new_version) = (old.version(), new.version());
+        if old_version != new_version {
+            // Note: git refs can't be compared, so changing them is
+            // always considered an upgrade
+            let is_simple = matches!(new, LockedDependency::Simple(_));
+            let change =
+                if is_simple && compare_values(&new_version, &old_version) == Ordering::Less {
+                    Change::Downgraded {
+                        name: name.to_string(),
+                        old: old_version,
+                        new: new_version,
+                    }
+                } else {
+                    Change::Upgraded {
+                        name: name.to_string(),
+                        old: old_version,
+                        new: new_version,
+                    }
+                };
+            res.push(change);
+        }
+        if old.marker() != new.marker() {
+            res.push(Change::MarkerChanged {
+                name: name.to_string(),
+                old: old.marker().cloned(),
+                new: new.marker().cloned(),
+            });
+        }
+        res
+    }
+}
+
+/// Return the first line of the dependency, without the hashes
+fn requirement(dep: &LockedDependency) -> String {
+    let line = dep.line();
+    let first_line = line.lines().next().unwrap_or("");
+    first_line
+        .trim_end_matches(|c: char| c.is_ascii_whitespace() || c == '\\')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lock::parse;
+
+    fn changes(old: &str, new: &str) -> Vec<String> {
+        let old = parse(old).unwrap();
+        let new = parse(new).unwrap();
+        let change_set = ChangeSet::between(&old, &new);
+        change_set.changes().iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn no_changes() {
+        let lock = "# comment\nbar==1.3\nfoo==0.42 \\\n    --hash=sha256:abc\n";
+        assert!(changes(lock, lock).is_empty());
+    }
+
+    #[test]
+    fn added_and_removed() {
+        assert_eq!(
+            changes(
+                "bar==1.3\n",
+                "foo==0.42 ; sys_platform == 'win32' \\\n    --hash=sha256:abc\n"
+            ),
+            &["- bar==1.3", "+ foo==0.42 ; sys_platform == 'win32'"]
+        );
+    }
+
+    #[test]
+    fn upgraded_and_downgraded() {
+        assert_eq!(
+            changes("bar==1.3\nfoo==0.9\n", "bar==1.2\nfoo==0.10\n"),
+            &["bar: 1.3 -> 1.2", "foo: 0.9 -> 0.10"]
+        );
+        let old = parse("bar==1.3\n").unwrap();
+        let new = parse("bar==1.2\n").unwrap();
+        let change_set = ChangeSet::between(&old, &new);
+        assert_eq!(change_set.changes()[0].kind(), "downgraded");
+    }
+
+    #[test]
+    fn marker_changed() {
+        assert_eq!(
+            changes(
+                "foo==1.0\n",
+                "foo==1.0 ; sys_platform == 'win32'\nfoo==1.1 ; sys_platform != 'win32'\n"
+            ),
+            &[
+                "foo: (no marker) -> sys_platform == 'win32'",
+                "+ foo==1.1 ; sys_platform != 'win32'"
+            ]
+        );
+    }
+
+    #[test]
+    fn pair_lines_with_the_same_marker() {
+        assert_eq!(
+            changes(
+                "foo==1.0 ; sys_platform == 'win32'\nfoo==1.1 ; sys_platform != 'win32'\n",
+                "foo==1.0 ; sys_platform == 'win32'\nfoo==1.2 ; sys_platform != 'win32'\n",
+            ),
+            &["foo: 1.1 -> 1.2"]
+        );
+    }
+
+    #[test]
+    fn table() {
+        let old = parse("bar==1.3\nfoo==0.42\n").unwrap();
+        let new = parse("foo==0.43\n").unwrap();
+        let change_set = ChangeSet::between(&old, &new);
+        assert_eq!(
+            change_set.table(),
+            "\
+change    name  before    after
+removed   bar   bar==1.3
+upgraded  foo   0.42      0.43
+"
+        );
+    }
+}
@@ -0,0 +1,227 @@
+use crate::dependencies::LockedDependency;
+
+/// Contents of a lock file.
+///
+/// Besides dependencies, the lock keeps everything a human may have written
+/// in it (comments, blank lines), so that dumping a lock that was not modified
+/// gives back the exact same text.
+///
+/// Comments right above a dependency are *attached* to it: they are removed
+/// along with the dependency. Other comments and blank lines are kept as is.
+#[derive(Debug, Default)]
+pub struct Lock {
+    items: Vec<LockItem>,
+}
+
+#[derive(Debug)]
+enum LockItem {
+    // Blank lines and comments not attached to any dependency
+    Text(String),
+    // Lines with index options written by dmenv, like
+    // `--index-url https://pypi.example.com`
+    PipOption(String),
+    Dependency {
+        comments: String,
+        dependency: LockedDependency,
+    },
+}
+
+impl Lock {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn push_text(&mut self, text: &str) {
+        if !text.is_empty() {
+            self.items.push(LockItem::Text(text.to_string()));
+        }
+    }
+
+    pub fn push_option(&mut self, line: &str) {
+        self.items.push(LockItem::PipOption(line.to_string()));
+    }
+
+    /// Replace the lines with index options by the given ones, at
+    /// the top of the lock. Lines with other pip options are kept
+    pub fn set_options(&mut self, options: &[String]) {
+        self.items
+            .retain(|item| !matches!(item, LockItem::PipOption(_)));
+        let new_items = options
+            .iter()
+            .map(|option| LockItem::PipOption(format!("{}\n", option)));
+        self.items.splice(0..0, new_items);
+    }
+
+    pub fn push_dependency(&mut self, comments: &str, dependency: LockedDependency) {
+        self.items.push(LockItem::Dependency {
+            comments: comments.to_string(),
+            dependency,
+        });
+    }
+
+    pub fn dependencies(&self) -> impl Iterator<Item = &LockedDependency> {
+        self.items.iter().filter_map(|item| match item {
+            LockItem::Dependency { dependency, .. } => Some(dependency),
+            _ => None,
+        })
+    }
+
+    pub fn dependencies_mut(&mut self) -> impl Iterator<Item = &mut LockedDependency> {
+        self.items.iter_mut().filter_map(|item| match item {
+            LockItem::Dependency { dependency, .. } => Some(dependency),
+            _ => None,
+        })
+    }
+
+    /// Add a new dependency to the lock.
+    //
+    // Note: the dependency is inserted before the first dependency that
+    // comes after it in the lexicographic order, so that sorted locks stay
+    // sorted. See `lock::dump()` for details
+    pub fn add(&mut self, dependency: LockedDependency) {
+        let key = dependency.line().to_lowercase();
+        let insert_index = self.items.iter().position(|item| match item {
+            LockItem::Dependency { dependency, .. } => dependency.line().to_lowercase() > key,
+            _ => false,
+        });
+        let last_dependency_index = self
+            .items
+            .iter()
+            .rposition(|item| matches!(item, LockItem::Dependency { .. }));
+        let index = match (insert_index, last_dependency_index) {
+            (Some(i), _) => i,
+            (None, Some(i)) => i + 1,
+            (None, None) => self.items.len(),
+        };
+        let item = LockItem::Dependency {
+            comments: String::new(),
+            dependency,
+        };
+        self.items.insert(index, item);
+    }
+
+    /// Remove the dependencies for which `predicate` returns false,
+    /// along with their comments. Return the removed dependencies
+    pub fn retain<F>(&mut self, mut predicate: F) -> Vec<LockedDependency>
+    where
+        F: FnMut(&LockedDependency) -> bool,
+    {
+        let (kept, removed): (Vec<_>, Vec<_>) = self.items.drain(..).partition(|item| match item {
+            LockItem::Dependency { dependency, .. } => predicate(dependency),
+            _ => true,
+        });
+        self.items = kept;
+        removed
+            .into_iter()
+            .filter_map(|item| match item {
+                LockItem::Dependency { dependency, .. } => Some(dependency),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Return the text of the lock, without the top comment
+    /// written by `operations::lock::write_lock()`
+    pub fn text(&self) -> String {
+        let mut res = String::new();
+        for item in &self.items {
+            match item {
+                LockItem::Text(text) | LockItem::PipOption(text) => res.push_str(text),
+                LockItem::Dependency {
+                    comments,
+                    dependency,
+                } => {
+                    res.push_str(comments);
+                    res.push_str(&dependency.line());
+                }
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dependencies::{FrozenDependency, LockedDependency, SimpleDependency};
+    use crate::lock::{dump, parse};
+
+    fn new_dep(name: &str, version: &str) -> LockedDependency {
+        let frozen = FrozenDependency::new(name, version);
+        LockedDependency::Simple(SimpleDependency::from_frozen(&frozen))
+    }
+
+    #[test]
+    fn round_trip() {
+        let text = "\
+# Pinned because of a bug in 0.43
+foo==0.42
+
+# TODO: unpin when
+# bar is fixed
+bar==1.3 \\
+    --hash=sha256:abc
+--find-links ./wheels
+
+git@example.com/baz.git@dae42f#egg=baz
+# last comment
+";
+        let lock = parse(text).unwrap();
+        assert_eq!(dump(&lock), text);
+    }
+
+    #[test]
+    fn add_keeps_sorted_locks_sorted() {
+        let mut lock = parse("bar==1.3\n\n# keep me\nfoo==0.42\n").unwrap();
+        lock.add(new_dep("baz", "2.0"));
+        lock.add(new_dep("spam", "1.0"));
+        assert_eq!(
+            dump(&lock),
+            "bar==1.3\n\nbaz==2.0\n# keep me\nfoo==0.42\nspam==1.0\n"
+        );
+    }
+
+    #[test]
+    fn add_after_last_dependency() {
+        let mut lock = parse("bar==1.3\n# trailing comment\n").unwrap();
+        lock.add(new_dep("foo", "0.42"));
+        assert_eq!(dump(&lock), "bar==1.3\nfoo==0.42\n# trailing comment\n");
+    }
+
+    #[test]
+    fn set_options() {
+        let mut lock =
+            parse("--index-url https://old.example.com\n# about bar\nbar==1.3\n").unwrap();
+        lock.set_options(&[
+            "--index-url https://pypi.example.com/simple".to_string(),
+            "--trusted-host pypi.example.com".to_string(),
+        ]);
+        assert_eq!(
+            dump(&lock),
+            "--index-url https://pypi.example.com/simple\n--trusted-host pypi.example.com\n# about bar\nbar==1.3\n"
+        );
+        lock.set_options(&[]);
+        assert_eq!(dump(&lock), "# about bar\nbar==1.3\n");
+    }
+
+    #[test]
+    fn set_options_keeps_other_options() {
+        let mut lock = parse(
+            "--index-url https://old.example.com\n-r base.txt\n-c constraints.txt\nbar==1.3\n",
+        )
+        .unwrap();
+        lock.set_options(&["--index-url https://pypi.example.com/simple".to_string()]);
+        assert_eq!(
+            dump(&lock),
+            "--index-url https://pypi.example.com/simple\n-r base.txt\n-c constraints.txt\nbar==1.3\n"
+        );
+        assert_eq!(lock.dependencies().count(), 1);
+    }
+
+    #[test]
+    fn retain_removes_attached_comments() {
+        let mut lock = parse("# about bar\nbar==1.3\n\n# free comment\n\nfoo==0.42\n").unwrap();
+        let removed = lock.retain(|dep| dep.name() != "bar");
+        assert_eq!(removed.len(), 1);
+        assert_eq!(dump(&lock), "\n# free comment\n\nfoo==0.42\n");
+    }
+}
@@ -0,0 +1,132 @@
+use crate::dependencies::{FrozenDependency, LockedDependency};
+use crate::lock::Lock;
+use crate::markers::Environment;
+
+/// A difference between the lock and what is installed in the virtualenv
+#[derive(Debug, PartialEq)]
+pub enum Drift {
+    /// In the lock, but not installed
+    Missing { name: String, locked: String },
+    /// Installed, but not in the lock
+    Extra { name: String, installed: String },
+    /// Installed with a different version than the one in the lock
+    WrongVersion {
+        name: String,
+        locked: String,
+        installed: String,
+    },
+}
+
+/// Compare the lines of the lock that apply to the given environment
+/// with the output of `pip freeze`
+// Notes:
+//  * names are compared case-insensitively, like pip does
+//  * `pip freeze` only knows the version of packages installed from git,
+//    so they are never considered at the wrong version
+pub fn drift(
+    lock: &Lock,
+    frozen_deps: &[FrozenDependency],
+    environment: &Environment,
+) -> Vec<Drift> {
+    let locked: Vec<_> = lock
+        .dependencies()
+        .filter(|d| d.applies_to(environment))
+        .collect();
+    let find_frozen = |name: &str| {
+        frozen_deps
+            .iter()
+            .find(|f| f.name.to_lowercase() == name.to_lowercase())
+    };
+    let mut res = vec![];
+    for dep in &locked {
+        let name = dep.name();
+        match find_frozen(&name) {
+            None => res.push(Drift::Missing {
+                locked: dep.version(),
+                name,
+            }),
+            Some(frozen) => {
+                if let LockedDependency::Simple(s) = dep {
+                    if s.version.value != frozen.version {
+                        res.push(Drift::WrongVersion {
+                            name,
+                            locked: s.version.value.to_string(),
+                            installed: frozen.version.to_string(),
+                        })
+                    }
+                }
+            }
+        }
+    }
+    for frozen in frozen_deps {
+        let in_lock = locked
+            .iter()
+            .any(|d| d.name().to_lowercase() == frozen.name.to_lowercase());
+        if !in_lock {
+            res.push(Drift::Extra {
+                name: frozen.name.to_string(),
+                installed: frozen.version.to_string(),
+            });
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lock::parse;
+
+    fn linux_environment() -> Environment {
+        let mut res = Environment::new();
+        res.set("sys_platform", "linux");
+        res
+    }
+
+    #[test]
+    fn no_drift() {
+        let lock = parse("Foo==1.0\ngit@example.com:bar.git@master#egg=bar\n").unwrap();
+        let frozen = [
+            FrozenDependency::new("foo", "1.0"),
+            FrozenDependency::new("bar", "0.3"),
+        ];
+        assert!(drift(&lock, &frozen, &linux_environment()).is_empty());
+    }
+
+    #[test]
+    fn missing_extra_and_wrong_version() {
+        let lock = parse("bar==2.0\nfoo==1.0\n").unwrap();
+        let frozen = [
+            FrozenDependency::new("foo", "1.1"),
+            FrozenDependency::new("spam", "0.1"),
+        ];
+        assert_eq!(
+            drift(&lock, &frozen, &linux_environment()),
+            &[
+                Drift::Missing {
+                    name: "bar".to_string(),
+                    locked: "2.0".to_string()
+                },
+                Drift::WrongVersion {
+                    name: "foo".to_string(),
+                    locked: "1.0".to_string(),
+                    installed: "1.1".to_string()
+                },
+                Drift::Extra {
+                    name: "spam".to_string(),
+                    installed: "0.1".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn respect_markers() {
+        let lock = parse(
+            "foo==1.0 ; sys_platform == 'win32'\nfoo==1.1 ; sys_platform != 'win32'\npywin32==227 ; sys_platform == 'win32'\n",
+        )
+        .unwrap();
+        let frozen = [FrozenDependency::new("foo", "1.1")];
+        assert!(drift(&lock, &frozen, &linux_environment()).is_empty());
+    }
+}
@@ -1,16 +1,15 @@
-use crate::dependencies::LockedDependency;
+use crate::lock::Lock;
 
-pub fn dump<Deps>(locked_dependencies: Deps) -> String
-where
-    Deps: IntoIterator<Item = LockedDependency>,
-{
-    // Dependencies are sorted according to their *lowercase* name.
-    // This is consistent with how `pip freeze` is implemented.
-    // See bottom of pip/_internal/operations/freeze.py:freeze()
-    let mut lines: Vec<_> = locked_dependencies.into_iter().map(|x| x.line()).collect();
-    lines.sort_by(|x, y| x.to_lowercase().cmp(&y.to_lowercase()));
-    let mut res = lines.join("");
-    if !res.ends_with('\n') {
+/// Return the contents of the lock, as they should be written in the
+/// lock file.
+// Note: we no longer sort every line here, since this would move comments
+// around. Instead, new dependencies are inserted at the right place by
+// `Lock::add()`. This means dependencies are still sorted according
+// to their *lowercase* name. This is consistent with how `pip freeze`
+// is implemented. See bottom of pip/_internal/operations/freeze.py:freeze()
+pub fn dump(lock: &Lock) -> String {
+    let mut res = lock.text();
+    if !res.is_empty() && !res.ends_with('\n') {
         res.push('\n');
     }
     res
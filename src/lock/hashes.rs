@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use crate::dependencies::LockedDependency;
+use crate::error::Error;
+use crate::lock::Lock;
+
+/// Hashes of the artifacts of some dependencies, indexed
+/// by name and version
+#[derive(Debug, Default)]
+pub struct Hashes {
+    hashes: HashMap<(String, String), Vec<String>>,
+}
+
+impl Hashes {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn insert(&mut self, name: &str, version: &str, hashes: Vec<String>) {
+        self.hashes.insert(Self::key(name, version), hashes);
+    }
+
+    pub fn get(&self, name: &str, version: &str) -> Option<&Vec<String>> {
+        self.hashes.get(&Self::key(name, version))
+    }
+
+    // Note: names are compared case-insensitively, like pip does
+    fn key(name: &str, version: &str) -> (String, String) {
+        (name.to_lowercase(), version.to_string())
+    }
+}
+
+/// Returns true if at least one dependency in the lock has hashes.
+// Note: as soon as one requirement has a hash, pip switches to
+// hash-checking mode for all of them
+pub fn has_hashes(lock: &Lock) -> bool {
+    lock.dependencies().any(|dep| match dep {
+        LockedDependency::Simple(s) => !s.hashes.is_empty(),
+        LockedDependency::Git(_) => false,
+    })
+}
+
+/// Add the known hashes to the simple dependencies that have none.
+//
+// Notes:
+//  * existing hashes are left untouched, so that running
+//    `dmenv lock --hashes` twice does not change the lock
+//  * pip refuses requirements installed from git in hash-checking
+//    mode, so a lock with hashes can't contain any
+pub fn apply_hashes(lock: &mut Lock, hashes: &Hashes) -> Result<(), Error> {
+    for dep in lock.dependencies_mut() {
+        if let LockedDependency::Simple(s) = dep {
+            if !s.hashes.is_empty() {
+                continue;
+            }
+            if let Some(known_hashes) = hashes.get(&s.name, &s.version.value) {
+                s.set_hashes(known_hashes);
+            }
+        }
+    }
+    if !has_hashes(lock) {
+        return Ok(());
+    }
+    check_hashable(lock)
+}
+
+/// Make sure hashes can be recorded in the lock, that is, that
+/// it contains no dependency installed from git
+pub fn check_hashable(lock: &Lock) -> Result<(), Error> {
+    let git_names: Vec<_> = lock
+        .dependencies()
+        .filter_map(|dep| match dep {
+            LockedDependency::Git(g) => Some(g.name.to_string()),
+            LockedDependency::Simple(_) => None,
+        })
+        .collect();
+    if git_names.is_empty() {
+        return Ok(());
+    }
+    Err(Error::HashesWithGitDependencies { names: git_names })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lock::{dump, parse};
+
+    #[test]
+    fn add_missing_hashes() {
+        let mut lock = parse("bar==1.3\nfoo==0.42\n").unwrap();
+        let mut hashes = Hashes::new();
+        hashes.insert("Foo", "0.42", vec!["sha256:abc".to_string()]);
+        apply_hashes(&mut lock, &hashes).unwrap();
+        assert!(has_hashes(&lock));
+        let actual = dump(&lock);
+        assert_eq!(actual, "bar==1.3\nfoo==0.42 \\\n    --hash=sha256:abc\n");
+    }
+
+    #[test]
+    fn keep_existing_hashes() {
+        let mut lock = parse("foo==0.42 \\\n    --hash=sha256:abc\n").unwrap();
+        let mut hashes = Hashes::new();
+        hashes.insert("foo", "0.42", vec!["sha256:def".to_string()]);
+        apply_hashes(&mut lock, &hashes).unwrap();
+        let actual = dump(&lock);
+        assert_eq!(actual, "foo==0.42 \\\n    --hash=sha256:abc\n");
+    }
+
+    #[test]
+    fn no_hashes_for_other_versions() {
+        let mut lock = parse("foo==0.42\n").unwrap();
+        let mut hashes = Hashes::new();
+        hashes.insert("foo", "0.43", vec!["sha256:def".to_string()]);
+        apply_hashes(&mut lock, &hashes).unwrap();
+        assert!(!has_hashes(&lock));
+    }
+
+    #[test]
+    fn reject_hashes_with_git_dependencies() {
+        let mut lock = parse(
+            "foo==0.42
+git+https://example.com/bar.git@master#egg=bar
+",
+        )
+        .unwrap();
+        let mut hashes = Hashes::new();
+        hashes.insert("foo", "0.42", vec!["sha256:abc".to_string()]);
+        match apply_hashes(&mut lock, &hashes) {
+            Err(Error::HashesWithGitDependencies { names }) => assert_eq!(names, &["bar"]),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
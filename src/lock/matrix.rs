@@ -0,0 +1,562 @@
+use std::cmp::Ordering;
+
+use crate::dependencies::{FrozenDependency, LockedDependency, SimpleDependency};
+use crate::error::*;
+use crate::lock::Lock;
+use crate::markers::{canonical_variable, compare_values, Environment, Marker, Operator};
+
+// Only those variables can be compared with `<` and `>=`
+const VERSION_VARIABLES: &[&str] = &["python_version", "python_full_version"];
+
+/// Output of `pip freeze` captured for a given interpreter, along with the
+/// values of the marker variables for this interpreter.
+///
+/// Snapshots are written by `dmenv freeze` and look like this:
+///
+/// ```text
+/// # python_version: 3.7
+/// # sys_platform: linux
+/// attrs==19.3.0
+/// ```
+#[derive(Debug)]
+pub struct Snapshot {
+    variables: Vec<(String, String)>,
+    dependencies: Vec<FrozenDependency>,
+}
+
+impl Snapshot {
+    pub fn new(environment: &Environment, dependencies: Vec<FrozenDependency>) -> Self {
+        let variables = ["python_version", "sys_platform"]
+            .iter()
+            .filter_map(|name| Some((name.to_string(), environment.get(name)?.to_string())))
+            .collect();
+        Snapshot {
+            variables,
+            dependencies,
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut variables = vec![];
+        let mut dependencies = vec![];
+        for line in text.lines().map(|l| l.trim()) {
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('#') {
+                // Other comments are ignored, like pip does
+                if let Some((name, value)) = Self::parse_variable(line) {
+                    variables.push((name.to_string(), value.to_string()));
+                }
+                continue;
+            }
+            dependencies.push(FrozenDependency::from_string(line.to_string())?);
+        }
+        Ok(Snapshot {
+            variables,
+            dependencies,
+        })
+    }
+
+    fn parse_variable(line: &str) -> Option<(&'static str, &str)> {
+        let mut words = line.trim_start_matches('#').splitn(2, ':');
+        let name = canonical_variable(words.next()?.trim())?;
+        let value = words.next()?.trim();
+        Some((name, value))
+    }
+
+    pub fn dump(&self) -> String {
+        let mut res = String::new();
+        for (name, value) in &self.variables {
+            res.push_str(&format!("# {}: {}\n", name, value));
+        }
+        for dep in &self.dependencies {
+            res.push_str(&format!("{}=={}\n", dep.name, dep.version));
+        }
+        res
+    }
+
+    fn environment(&self) -> Environment {
+        let mut res = Environment::new();
+        for (name, value) in &self.variables {
+            res.set(name, value);
+        }
+        res
+    }
+
+    fn value(&self, variable: &str) -> &str {
+        self.variables
+            .iter()
+            .find(|(name, _)| name == variable)
+            .map_or("", |(_, value)| value.as_str())
+    }
+
+    fn version_of(&self, name: &str) -> Option<&str> {
+        self.dependencies
+            .iter()
+            .find(|d| d.name == name)
+            .map(|d| d.version.as_str())
+    }
+}
+
+/// Merge several snapshots into the lock, so that each line applies
+/// to the snapshots it was frozen from, and to those only.
+///
+/// For instance, if `foo` is at version 1.0 on Windows and 1.1 on Linux, the lock
+/// will contain:
+///
+/// ```text
+/// foo==1.0 ; sys_platform == 'win32'
+/// foo==1.1 ; sys_platform != 'win32'
+/// ```
+// Notes:
+//  * Comments in the lock are kept, and git dependencies are left untouched
+//  * Dependencies not found in any snapshot are kept
+//  * When a dependency is present in every snapshot, the line for its most common
+//    version gets the negation of the other markers, so that it also applies to
+//    environments that were not part of the matrix
+pub fn merge(lock: &mut Lock, snapshots: &[Snapshot]) -> Result<(), Error> {
+    let variables = check_snapshots(snapshots)?;
+    let mut names: Vec<_> = snapshots
+        .iter()
+        .flat_map(|s| s.dependencies.iter().map(|d| d.name.as_str()))
+        .collect();
+    names.sort_by_key(|n| n.to_lowercase());
+    names.dedup();
+
+    for name in names {
+        let has_git_line = lock
+            .dependencies()
+            .any(|d| d.name() == name && matches!(d, LockedDependency::Git(_)));
+        if has_git_line {
+            continue;
+        }
+        let lines = desired_lines(name, snapshots, &variables);
+        apply_lines(lock, name, lines);
+    }
+    Ok(())
+}
+
+/// Make sure the snapshots can be told apart, and return the
+/// names of the variables they declare
+fn check_snapshots(snapshots: &[Snapshot]) -> Result<Vec<String>, Error> {
+    let first = match snapshots.first() {
+        None => return Err(new_error("no snapshot to merge".to_string())),
+        Some(s) => s,
+    };
+    let mut variables: Vec<_> = first.variables.iter().map(|(n, _)| n.clone()).collect();
+    variables.sort();
+    for snapshot in snapshots {
+        let mut other: Vec<_> = snapshot.variables.iter().map(|(n, _)| n.clone()).collect();
+        other.sort();
+        if other != variables {
+            return Err(new_error(format!(
+                "all snapshots should declare the same variables (expected: {}, got: {})",
+                variables.join(", "),
+                other.join(", ")
+            )));
+        }
+    }
+    for (i, snapshot) in snapshots.iter().enumerate() {
+        let same_values = |other: &Snapshot| {
+            variables
+                .iter()
+                .all(|v| snapshot.value(v) == other.value(v))
+        };
+        if snapshots[i + 1..].iter().any(same_values) {
+            let values: Vec<_> = snapshot
+                .variables
+                .iter()
+                .map(|(n, v)| format!("{}: {}", n, v))
+                .collect();
+            return Err(new_error(format!(
+                "several snapshots have the same environment ({})",
+                values.join(", ")
+            )));
+        }
+    }
+    Ok(variables)
+}
+
+/// Compute the versions and markers to write in the lock for the given dependency
+fn desired_lines(
+    name: &str,
+    snapshots: &[Snapshot],
+    variables: &[String],
+) -> Vec<(String, Option<Marker>)> {
+    let mut versions: Vec<&str> = snapshots
+        .iter()
+        .filter_map(|s| s.version_of(name))
+        .collect();
+    // Sort by number of snapshots, then by version, most common and newest first
+    let count = |v: &str| {
+        snapshots
+            .iter()
+            .filter(|s| s.version_of(name) == Some(v))
+            .count()
+    };
+    versions.sort_by(|a, b| count(b).cmp(&count(a)).then(compare_values(b, a)));
+    versions.dedup();
+
+    let present_everywhere = snapshots.iter().all(|s| s.version_of(name).is_some());
+    let (default, others) = if present_everywhere {
+        (versions.first().cloned(), &versions[1..])
+    } else {
+        (None, &versions[..])
+    };
+
+    let split = |version: &str| -> (Vec<&Snapshot>, Vec<&Snapshot>) {
+        snapshots
+            .iter()
+            .partition(|s| s.version_of(name) == Some(version))
+    };
+
+    let mut res = vec![];
+    for version in others {
+        let (matching, other) = split(version);
+        let marker = separate(&matching, &other, variables);
+        res.push((version.to_string(), marker));
+    }
+    if let Some(version) = default {
+        let (matching, other) = split(version);
+        let markers: Vec<_> = res.iter().filter_map(|(_, m)| m.as_ref()).collect();
+        let negated = markers
+            .split_first()
+            .and_then(|(first, rest)| {
+                rest.iter()
+                    .fold((*first).clone(), |acc, m| acc.or(m))
+                    .negate()
+            })
+            .filter(|m| is_separator(m, &matching, &other));
+        let marker = match negated {
+            Some(m) => Some(m),
+            None => separate(&matching, &other, variables),
+        };
+        res.insert(0, (version.to_string(), marker));
+    }
+    res.sort_by(|(a, _), (b, _)| compare_values(a, b));
+    res
+}
+
+fn is_separator(marker: &Marker, matching: &[&Snapshot], other: &[&Snapshot]) -> bool {
+    matching.iter().all(|s| marker.evaluate(&s.environment()))
+        && !other.iter().any(|s| marker.evaluate(&s.environment()))
+}
+
+/// Return the shortest marker that is true for all the `matching` snapshots
+/// and false for all the `other` ones, or None if there is no need for a
+/// marker
+// Note: since no two snapshots have the same environment, there is always
+// a solution
+fn separate(matching: &[&Snapshot], other: &[&Snapshot], variables: &[String]) -> Option<Marker> {
+    if other.is_empty() {
+        return None;
+    }
+    let mut candidates = vec![];
+    for variable in variables {
+        candidates.extend(single_variable_markers(variable, matching, other));
+    }
+    // If no variable is enough on its own, split on the values of one of them,
+    // and look for a marker using the other ones
+    if candidates.is_empty() {
+        for (i, variable) in variables.iter().enumerate() {
+            let rest: Vec<_> = variables
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, v)| v.clone())
+                .collect();
+            let parts = values(variable, matching).into_iter().map(|value| {
+                let has_value = |s: &&&Snapshot| s.value(variable) == value;
+                let sub_matching: Vec<_> = matching.iter().filter(has_value).cloned().collect();
+                let sub_other: Vec<_> = other.iter().filter(has_value).cloned().collect();
+                let marker = Marker::compare(variable, Operator::Equal, value);
+                match separate(&sub_matching, &sub_other, &rest) {
+                    Some(sub) => marker.and(&sub),
+                    None => marker,
+                }
+            });
+            candidates.extend(parts.fold(None, |acc: Option<Marker>, m| match acc {
+                Some(acc) => Some(acc.or(&m)),
+                None => Some(m),
+            }));
+        }
+    }
+    // Note: min_by_key returns the first of the shortest candidates
+    let res = candidates
+        .into_iter()
+        .filter(|m| is_separator(m, matching, other))
+        .min_by_key(|m| m.to_string().len());
+    Some(res.expect("snapshots should be distinct"))
+}
+
+/// Return the markers using only `variable` that could tell `matching`
+/// and `other` snapshots apart, in order of preference
+fn single_variable_markers(
+    variable: &str,
+    matching: &[&Snapshot],
+    other: &[&Snapshot],
+) -> Vec<Marker> {
+    let matching_values = values(variable, matching);
+    let other_values = values(variable, other);
+    if matching_values.iter().any(|v| other_values.contains(v)) {
+        return vec![];
+    }
+    let mut res = vec![];
+    if VERSION_VARIABLES.contains(&variable) {
+        let max_matching = matching_values
+            .last()
+            .expect("matching should not be empty");
+        let min_matching = matching_values
+            .first()
+            .expect("matching should not be empty");
+        let min_other = other_values.first().expect("other should not be empty");
+        let max_other = other_values.last().expect("other should not be empty");
+        if compare_values(max_matching, min_other) == Ordering::Less {
+            res.push(Marker::compare(variable, Operator::LessThan, min_other));
+        }
+        if compare_values(max_other, min_matching) == Ordering::Less {
+            res.push(Marker::compare(
+                variable,
+                Operator::GreaterEqual,
+                min_matching,
+            ));
+        }
+    }
+    res.push(join(
+        variable,
+        Operator::Equal,
+        &matching_values,
+        Marker::or,
+    ));
+    res.push(join(
+        variable,
+        Operator::NotEqual,
+        &other_values,
+        Marker::and,
+    ));
+    res
+}
+
+fn join(
+    variable: &str,
+    operator: Operator,
+    values: &[&str],
+    combine: fn(&Marker, &Marker) -> Marker,
+) -> Marker {
+    let (first, rest) = values.split_first().expect("values should not be empty");
+    let first = Marker::compare(variable, operator, first);
+    rest.iter().fold(first, |acc, value| {
+        combine(&acc, &Marker::compare(variable, operator, value))
+    })
+}
+
+/// Return the sorted, distinct values of `variable` in the given snapshots
+fn values<'a>(variable: &str, snapshots: &[&'a Snapshot]) -> Vec<&'a str> {
+    let mut res: Vec<_> = snapshots.iter().map(|s| s.value(variable)).collect();
+    res.sort_by(|a, b| compare_values(a, b));
+    res.dedup();
+    res
+}
+
+/// Make the lines for `name` in the lock match `lines`, modifying
+/// as few of them as possible
+fn apply_lines(lock: &mut Lock, name: &str, lines: Vec<(String, Option<Marker>)>) {
+    let mut existing: Vec<_> = lock
+        .dependencies_mut()
+        .filter_map(|d| match d {
+            LockedDependency::Simple(s) if s.name == name => Some(s),
+            _ => None,
+        })
+        .collect();
+
+    // First, keep the lines that are already correct
+    let mut used = vec![false; existing.len()];
+    let mut remaining = vec![];
+    for (version, marker) in lines {
+        let found = existing
+            .iter()
+            .enumerate()
+            .position(|(i, dep)| !used[i] && dep.version.value == version && dep.marker == marker);
+        match found {
+            Some(i) => used[i] = true,
+            None => remaining.push((version, marker)),
+        }
+    }
+
+    // Then, patch the others
+    let mut to_add = vec![];
+    for (version, marker) in remaining {
+        let unused = existing.iter_mut().enumerate().find(|(i, _)| !used[*i]);
+        match unused {
+            Some((i, dep)) => {
+                used[i] = true;
+                patch_line(dep, &version, marker);
+            }
+            None => to_add.push((version, marker)),
+        }
+    }
+
+    // Remove what's left ...
+    let mut index = 0;
+    lock.retain(|d| match d {
+        LockedDependency::Simple(s) if s.name == name => {
+            index += 1;
+            used[index - 1]
+        }
+        _ => true,
+    });
+
+    // ... and add the missing lines
+    for (version, marker) in to_add {
+        let mut dep = SimpleDependency::from_frozen(&FrozenDependency::new(name, &version));
+        dep.set_marker(marker);
+        lock.add(LockedDependency::Simple(dep));
+    }
+}
+
+fn patch_line(dep: &mut SimpleDependency, version: &str, marker: Option<Marker>) {
+    if dep.version.value != version {
+        dep.update(version);
+    }
+    if dep.marker != marker {
+        dep.set_marker(marker);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lock::{dump, parse};
+
+    fn snapshot(python_version: &str, sys_platform: &str, deps: &str) -> Snapshot {
+        let text = format!(
+            "# python_version: {}\n# sys_platform: {}\n{}",
+            python_version, sys_platform, deps
+        );
+        Snapshot::parse(&text).unwrap()
+    }
+
+    fn assert_merge(initial_contents: &str, snapshots: &[Snapshot], final_contents: &str) {
+        let mut lock = parse(initial_contents).unwrap();
+        merge(&mut lock, snapshots).unwrap();
+        assert_eq!(dump(&lock), final_contents);
+    }
+
+    #[test]
+    fn parse_and_dump_snapshot() {
+        let text = "# python_version: 3.7\n# sys.platform: linux\nattrs==19.3.0\n";
+        let snapshot = Snapshot::parse(text).unwrap();
+        assert_eq!(
+            snapshot.dump(),
+            "# python_version: 3.7\n# sys_platform: linux\nattrs==19.3.0\n"
+        );
+    }
+
+    #[test]
+    fn same_version_everywhere() {
+        assert_merge(
+            "",
+            &[
+                snapshot("3.7", "linux", "foo==1.0\n"),
+                snapshot("3.7", "win32", "foo==1.0\n"),
+            ],
+            "foo==1.0\n",
+        );
+    }
+
+    #[test]
+    fn version_divergence() {
+        assert_merge(
+            "",
+            &[
+                snapshot("3.7", "win32", "foo==1.0\n"),
+                snapshot("3.7", "linux", "foo==1.1\n"),
+                snapshot("3.7", "darwin", "foo==1.1\n"),
+            ],
+            "foo==1.0 ; sys_platform == 'win32'\nfoo==1.1 ; sys_platform != 'win32'\n",
+        );
+    }
+
+    #[test]
+    fn platform_specific_dependency() {
+        assert_merge(
+            "",
+            &[
+                snapshot("3.7", "win32", "foo==1.0\npywin32==227\n"),
+                snapshot("3.7", "linux", "foo==1.0\n"),
+            ],
+            "foo==1.0\npywin32==227 ; sys_platform == 'win32'\n",
+        );
+    }
+
+    #[test]
+    fn python_version_range() {
+        assert_merge(
+            "",
+            &[
+                snapshot("3.5", "linux", "pathlib2==2.3.5\n"),
+                snapshot("3.7", "linux", ""),
+                snapshot("3.10", "linux", ""),
+            ],
+            "pathlib2==2.3.5 ; python_version < '3.7'\n",
+        );
+    }
+
+    #[test]
+    fn several_variables() {
+        assert_merge(
+            "",
+            &[
+                snapshot("3.5", "linux", "foo==1.0\n"),
+                snapshot("3.5", "win32", ""),
+                snapshot("3.7", "linux", ""),
+            ],
+            "foo==1.0 ; sys_platform == 'linux' and python_version < '3.7'\n",
+        );
+    }
+
+    #[test]
+    fn patch_existing_lines() {
+        assert_merge(
+            "# Pinned because of #42\nfoo==0.9\nfoo==0.8 ; sys_platform == 'darwin'\nbar==2.0\nfoo==0.7 ; sys_platform == 'cygwin'\n",
+            &[
+                snapshot("3.7", "win32", "foo==1.0\n"),
+                snapshot("3.7", "linux", "foo==1.1\n"),
+            ],
+            "# Pinned because of #42\nfoo==1.0 ; sys_platform == 'win32'\nfoo==1.1 ; sys_platform != 'win32'\nbar==2.0\n",
+        );
+    }
+
+    #[test]
+    fn keep_git_dependencies() {
+        assert_merge(
+            "git@example.com:bar/foo.git@master#egg=foo\n",
+            &[
+                snapshot("3.7", "win32", "foo==1.0\n"),
+                snapshot("3.7", "linux", "foo==1.1\n"),
+            ],
+            "git@example.com:bar/foo.git@master#egg=foo\n",
+        );
+    }
+
+    #[test]
+    fn duplicate_environments() {
+        let mut lock = Lock::new();
+        let snapshots = [
+            snapshot("3.7", "linux", "foo==1.0\n"),
+            snapshot("3.7", "linux", "foo==1.1\n"),
+        ];
+        merge(&mut lock, &snapshots).unwrap_err();
+    }
+
+    #[test]
+    fn different_variables() {
+        let mut lock = Lock::new();
+        let snapshots = [
+            snapshot("3.7", "linux", "foo==1.0\n"),
+            Snapshot::parse("# python_version: 3.6\nfoo==1.0\n").unwrap(),
+        ];
+        merge(&mut lock, &snapshots).unwrap_err();
+    }
+}
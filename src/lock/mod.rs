@@ -1,9 +1,21 @@
 mod bump;
+mod changes;
+mod document;
+mod drift;
 mod dump;
+mod hashes;
+mod matrix;
 mod parse;
 mod update;
 
 pub use bump::{git_bump, simple_bump};
+pub use changes::ChangeSet;
+pub use document::Lock;
+pub use drift::{drift, Drift};
 pub use dump::dump;
-pub use parse::{parse, parse_git_line, parse_line, parse_simple_line};
+pub use hashes::{apply_hashes, check_hashable, has_hashes, Hashes};
+pub use matrix::{merge, Snapshot};
+#[cfg(test)]
+pub use parse::parse_git_line;
+pub use parse::{parse, parse_simple_line};
 pub use update::Updater;
@@ -1,21 +1,82 @@
 use crate::dependencies::{GitDependency, LockedDependency, SimpleDependency, VersionSpec};
 use crate::error::Error;
+use crate::lock::Lock;
+use crate::markers::Marker;
 
-pub fn parse(text: &str) -> Result<Vec<LockedDependency>, Error> {
-    let mut res = vec![];
-    let lines = split_logical_lines(&text);
-    for line in lines.iter() {
-        if !line.starts_with('#') {
-            // skip comments
-            let locked_dependency = parse_line(line)?;
-            res.push(locked_dependency);
+// Keep this in sync with `operations::lock::write_lock()`
+const TOP_COMMENT_PREFIX: &str = "# Generated with dmenv";
+
+// Keep this in sync with `Settings::index_options()`
+const INDEX_OPTIONS: &[&str] = &[
+    "--index-url",
+    "--extra-index-url",
+    "--find-links",
+    "--trusted-host",
+];
+
+pub fn parse(text: &str) -> Result<Lock, Error> {
+    let mut res = Lock::new();
+    let mut comments = String::new();
+    let lines = split_logical_lines(text);
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if i == 0 && trimmed.starts_with(TOP_COMMENT_PREFIX) {
+            // The top comment is re-generated each time the lock is written
+            continue;
+        }
+        if trimmed.starts_with('#') {
+            comments.push_str(line);
+            continue;
+        }
+        if trimmed.is_empty() {
+            // Comments followed by a blank line are not attached
+            // to any dependency
+            res.push_text(&comments);
+            comments.clear();
+            res.push_text(line);
+            continue;
         }
+        // Note: lines with the index options written by dmenv, like
+        // `--index-url`, are replaced, see `Lock::set_options()`. Lines
+        // with other options, like `-r base.txt`, are kept as text,
+        // except for `-e` which is used for git dependencies
+        if is_index_option(trimmed) {
+            res.push_text(&comments);
+            comments.clear();
+            res.push_option(line);
+            continue;
+        }
+        if trimmed.starts_with('-') && !is_editable(trimmed) {
+            comments.push_str(line);
+            res.push_text(&comments);
+            comments.clear();
+            continue;
+        }
+        let locked_dependency = parse_line(line)?;
+        res.push_dependency(&comments, locked_dependency);
+        comments.clear();
     }
+    res.push_text(&comments);
     Ok(res)
 }
 
+fn option_name(line: &str) -> &str {
+    line.split(|c: char| c.is_whitespace() || c == '=')
+        .next()
+        .unwrap_or("")
+}
+
+fn is_index_option(line: &str) -> bool {
+    INDEX_OPTIONS.contains(&option_name(line))
+}
+
+fn is_editable(line: &str) -> bool {
+    let name = option_name(line);
+    name == "-e" || name == "--editable"
+}
+
 fn split_logical_lines(text: &str) -> Vec<String> {
-    let not_joined = text.split_terminator(|x| x == '\n');
+    let not_joined = text.split_terminator('\n');
     let mut res = vec![];
     let mut current_line = String::new();
     for line in not_joined {
@@ -46,18 +107,44 @@ pub fn parse_line(line: &str) -> Result<LockedDependency, Error> {
     })
 }
 
-// Note: technically this function cannot fail, but we want to keep symetry with
-// parse_git_line()
 pub fn parse_simple_line(line: &str) -> Result<SimpleDependency, Error> {
-    let version = parse_simple_version(&line);
-    let name = parse_simple_name(&line);
+    let version = parse_simple_version(line);
+    let name = parse_simple_name(line);
+    let marker = parse_simple_marker(line)?;
+    let hashes = parse_hashes(line);
     Ok(SimpleDependency {
         line: line.to_string(),
         name,
         version,
+        marker,
+        hashes,
     })
 }
 
+fn parse_simple_marker(line: &str) -> Result<Option<Marker>, Error> {
+    let end = line.find("--hash=").unwrap_or(line.len());
+    let requirement = &line[..end];
+    let marker = match requirement.find(';') {
+        None => return Ok(None),
+        Some(pos) => &requirement[pos + 1..],
+    };
+    // Skip comment at the end of the line, if any
+    let marker = marker.split(" #").next().unwrap_or(marker);
+    let marker = marker.trim_end_matches(|c: char| c.is_ascii_whitespace() || c == '\\');
+    Marker::parse(marker.trim()).map(Some)
+}
+
+fn parse_hashes(line: &str) -> Vec<String> {
+    let is_end = |c: char| c.is_ascii_whitespace() || (c == '\\');
+    line.split("--hash=")
+        .skip(1)
+        .map(|x| {
+            let end = x.find(is_end).unwrap_or(x.len());
+            x[..end].to_string()
+        })
+        .collect()
+}
+
 fn parse_simple_name(line: &str) -> String {
     let dep_name = line.split("==").next().unwrap();
     dep_name.trim().to_string()
@@ -138,7 +225,7 @@ mod tests {
     #[test]
     fn malformed_lock() {
         let lock_contents = "bar==42\ngit://foo/bar.git@master#egggg=bar";
-        let actual = parse(&lock_contents);
+        let actual = parse(lock_contents);
         let actual = actual.unwrap_err();
         match actual {
             Error::MalformedLock { .. } => (),
@@ -173,9 +260,21 @@ bar \\
 foo==0.42
 git+ssh://git@host.tld:team/name.git@v0.32#egg=bar
 ";
-        let actual_deps = parse(text).unwrap();
+        let lock = parse(text).unwrap();
+
+        assert_eq!(lock.dependencies().count(), 2);
+    }
+
+    #[test]
+    fn test_skip_top_comment() {
+        let text = "\
+# Generated with dmenv 0.20.0, python 3.7.5, on Linux
+# Comment about foo
+foo==0.42
+";
+        let lock = parse(text).unwrap();
 
-        assert_eq!(actual_deps.len(), 2);
+        assert_eq!(lock.text(), "# Comment about foo\nfoo==0.42\n");
     }
 
     #[test]
@@ -197,6 +296,34 @@ git+ssh://git@host.tld:team/name.git@v0.32#egg=bar
         );
     }
 
+    #[test]
+    fn test_parse_simple_marker() {
+        assert_eq!(parse_simple_marker("foo==0.42\n").unwrap(), None);
+        let marker = parse_simple_marker("foo==0.42 ; python_version<'3.6' # old\n").unwrap();
+        assert_eq!(marker.unwrap().to_string(), "python_version < '3.6'");
+        let marker =
+            parse_simple_marker("foo==0.42;os_name=='nt' \\\n    --hash=sha256:abc\n").unwrap();
+        assert_eq!(marker.unwrap().to_string(), "os_name == 'nt'");
+    }
+
+    #[test]
+    fn test_invalid_marker() {
+        let actual = parse("foo==0.42 ; python_version <\n").unwrap_err();
+        match actual {
+            Error::MalformedMarker { .. } => (),
+            _ => panic!("Expecting MalformedMarker, got: {}", actual),
+        }
+    }
+
+    #[test]
+    fn test_parse_hashes() {
+        assert!(parse_hashes("foo==0.42\n").is_empty());
+        assert_eq!(
+            parse_hashes("foo==0.42 \\\n    --hash=sha256:abc \\\n    --hash=sha256:def\n"),
+            &["sha256:abc", "sha256:def"]
+        );
+    }
+
     #[test]
     fn test_parse_git_ref() {
         assert_eq!(
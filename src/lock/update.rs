@@ -1,10 +1,14 @@
 use crate::dependencies::{FrozenDependency, LockedDependency, SimpleDependency};
+use crate::error::Error;
+use crate::lock::Lock;
+use crate::markers::{Environment, Marker, Operator};
 use crate::UpdateLockOptions;
 
 #[derive(Debug)]
 pub struct Updater {
-    python_version: Option<String>,
+    python_version: Option<Marker>,
     sys_platform: Option<String>,
+    environment: Option<Environment>,
 }
 
 impl Updater {
@@ -12,6 +16,7 @@ impl Updater {
         Updater {
             python_version: None,
             sys_platform: None,
+            environment: None,
         }
     }
 
@@ -20,33 +25,52 @@ impl Updater {
         UpdateLockOptions {
             python_version,
             sys_platform,
+            ..
         }: UpdateLockOptions,
     ) {
         self.python_version = python_version;
         self.sys_platform = sys_platform;
     }
 
+    /// Only update the lines of the lock that apply to the given environment.
+    // Note: when no environment is set, every line of the lock is
+    // considered to apply
+    pub fn set_environment(&mut self, environment: Environment) {
+        self.environment = Some(environment);
+    }
+
     /// Applies a set of new FrozenDependency to the lock
-    // Basically, update `self.dependencies` using the new frozen deps,
+    // Basically, update the lock using the new frozen deps,
     // making sure no existing information in the lock is lost.
     // Note that we only modify existing lines or add new ones
     // (no deletion occurs).
     pub fn update(
         &self,
-        locked_dependencies: &mut Vec<LockedDependency>,
+        lock: &mut Lock,
         frozen_dependencies: &[FrozenDependency],
-    ) {
-        self.patch_existing_deps(locked_dependencies, frozen_dependencies);
-        self.add_missing_deps(locked_dependencies, frozen_dependencies);
+    ) -> Result<(), Error> {
+        self.patch_existing_deps(lock, frozen_dependencies);
+        self.add_missing_deps(lock, frozen_dependencies)
+    }
+
+    fn applies(&self, dep: &LockedDependency) -> bool {
+        match &self.environment {
+            None => true,
+            Some(environment) => dep.applies_to(environment),
+        }
     }
 
     /// Add dependencies from `frozen_deps` that were missing in the lock
     fn add_missing_deps(
         &self,
-        locked_dependencies: &mut Vec<LockedDependency>,
+        lock: &mut Lock,
         frozen_deps: &[FrozenDependency],
-    ) {
-        let known_names: Vec<_> = locked_dependencies.iter().map(|d| d.name()).collect();
+    ) -> Result<(), Error> {
+        let known_names: Vec<_> = lock
+            .dependencies()
+            .filter(|d| self.applies(d))
+            .map(|d| d.name())
+            .collect();
         let new_deps: Vec<_> = frozen_deps
             .iter()
             .filter(|x| !known_names.contains(&x.name))
@@ -61,23 +85,52 @@ impl Updater {
             // so that `foo` is *not* installed when running `pip install` on Linux.
             let mut locked_dep = SimpleDependency::from_frozen(dep);
             if let Some(python_version) = &self.python_version {
-                locked_dep.python_version(python_version);
+                locked_dep.add_marker(python_version);
             }
             if let Some(sys_platform) = &self.sys_platform {
-                locked_dep.sys_platform(sys_platform);
+                let marker = Marker::compare("sys_platform", Operator::Equal, sys_platform);
+                locked_dep.add_marker(&marker);
+            }
+            // Also make sure the new line does not apply where the existing
+            // lines for the same dependency do
+            if let Some(marker) = Self::exclusion_marker(lock, &dep.name)? {
+                locked_dep.add_marker(&marker);
             }
-            print!("+ {}", locked_dep.line);
-            locked_dependencies.push(LockedDependency::Simple(locked_dep));
+            lock.add(LockedDependency::Simple(locked_dep));
+        }
+        Ok(())
+    }
+
+    /// Return a marker that is false wherever one of the lines for `name`
+    /// in the lock applies, if there are any such lines.
+    // Notes:
+    //  * this is only called for dependencies whose existing lines
+    //    do not apply to the current environment, so they all have markers
+    //  * markers using `~=` or `===` can't be negated, and adding the line
+    //    without a marker would make pip fail on a double requirement
+    fn exclusion_marker(lock: &Lock, name: &str) -> Result<Option<Marker>, Error> {
+        let markers: Vec<_> = lock
+            .dependencies()
+            .filter(|d| d.name() == name)
+            .filter_map(|d| d.marker())
+            .collect();
+        let (first, rest) = match markers.split_first() {
+            None => return Ok(None),
+            Some(x) => x,
+        };
+        let combined = rest.iter().fold((*first).clone(), |acc, m| acc.or(m));
+        match combined.negate() {
+            Some(negated) => Ok(Some(negated)),
+            None => Err(Error::CannotNegateMarker {
+                name: name.to_string(),
+                marker: combined.to_string(),
+            }),
         }
     }
 
     /// Modify dependencies that were in the lock to match those passed in `frozen_deps`
-    fn patch_existing_deps(
-        &self,
-        locked_dependencies: &mut Vec<LockedDependency>,
-        frozen_deps: &[FrozenDependency],
-    ) {
-        for dep in locked_dependencies.iter_mut() {
+    fn patch_existing_deps(&self, lock: &mut Lock, frozen_deps: &[FrozenDependency]) {
+        for dep in lock.dependencies_mut().filter(|d| self.applies(d)) {
             match dep {
                 // frozen deps *never* contain git information (because `pip freeze`
                 // only returns names and versions), so always keep those in the lock.
@@ -99,9 +152,7 @@ impl Updater {
         if &dep.version.value == frozen_version {
             return;
         }
-
-        println!("{}: {} -> {}", dep.name, dep.version.value, &frozen_version);
-        dep.update(&frozen_version)
+        dep.update(frozen_version)
     }
 }
 
@@ -110,24 +161,15 @@ mod tests {
     use super::*;
     use crate::lock::{dump, parse};
 
-    impl FrozenDependency {
-        pub fn new(name: &str, version: &str) -> Self {
-            FrozenDependency {
-                name: name.to_string(),
-                version: version.to_string(),
-            }
-        }
-    }
-
     fn assert_update(
         updater: Updater,
         initial_contents: &str,
         frozen: &[FrozenDependency],
         final_contents: &str,
     ) {
-        let mut locked = parse(initial_contents).unwrap();
-        updater.update(&mut locked, frozen);
-        let actual = dump(locked);
+        let mut lock = parse(initial_contents).unwrap();
+        updater.update(&mut lock, frozen).unwrap();
+        let actual = dump(&lock);
         assert_eq!(actual, final_contents);
     }
 
@@ -186,6 +228,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn keep_hashes() {
+        let updater = Updater::new();
+        assert_update(
+            updater,
+            "foo==0.42 \\\n    --hash=sha256:abc\n",
+            &[FrozenDependency::new("foo", "0.42")],
+            "foo==0.42 \\\n    --hash=sha256:abc\n",
+        );
+    }
+
+    #[test]
+    fn drop_outdated_hashes() {
+        let updater = Updater::new();
+        assert_update(
+            updater,
+            "foo==0.42 \\\n    --hash=sha256:abc\n",
+            &[FrozenDependency::new("foo", "0.43")],
+            "foo==0.43\n",
+        );
+    }
+
+    #[test]
+    fn keep_comments() {
+        let updater = Updater::new();
+        assert_update(
+            updater,
+            "# Do not upgrade bar, see #42\nbar==6.2\n\n# End of lock\n",
+            &[
+                FrozenDependency::new("bar", "6.2"),
+                FrozenDependency::new("foo", "0.42"),
+            ],
+            "# Do not upgrade bar, see #42\nbar==6.2\nfoo==0.42\n\n# End of lock\n",
+        );
+    }
+
     #[test]
     fn add_new_deps() {
         let updater = Updater::new();
@@ -201,8 +279,8 @@ mod tests {
     fn different_python_version() {
         let mut updater = Updater::new();
         updater.set_options(UpdateLockOptions {
-            python_version: Some("< '3.6'".to_string()),
-            sys_platform: None,
+            python_version: Some(Marker::parse("python_version < '3.6'").unwrap()),
+            ..Default::default()
         });
         assert_update(
             updater,
@@ -215,12 +293,70 @@ mod tests {
         );
     }
 
+    #[test]
+    fn different_python_version_and_platform() {
+        let mut updater = Updater::new();
+        updater.set_options(UpdateLockOptions {
+            python_version: Some(Marker::parse("python_version < '3.6'").unwrap()),
+            sys_platform: Some("win32".to_string()),
+            ..Default::default()
+        });
+        assert_update(
+            updater,
+            "",
+            &[FrozenDependency::new("foo", "0.42")],
+            "foo==0.42 ; python_version < '3.6' and sys_platform == 'win32'\n",
+        );
+    }
+
+    fn linux_environment() -> Environment {
+        let mut res = Environment::new();
+        res.set("sys_platform", "linux");
+        res
+    }
+
+    #[test]
+    fn only_patch_deps_for_current_environment() {
+        let mut updater = Updater::new();
+        updater.set_environment(linux_environment());
+        assert_update(
+            updater,
+            "foo==0.42 ; sys_platform == 'win32'\nfoo==0.43 ; sys_platform != 'win32'\n",
+            &[FrozenDependency::new("foo", "0.44")],
+            "foo==0.42 ; sys_platform == 'win32'\nfoo==0.44 ; sys_platform != 'win32'\n",
+        );
+    }
+
+    #[test]
+    fn exclude_other_environments() {
+        let mut updater = Updater::new();
+        updater.set_environment(linux_environment());
+        assert_update(
+            updater,
+            "foo==0.42 ; sys_platform == 'win32'\n",
+            &[FrozenDependency::new("foo", "0.43")],
+            "foo==0.42 ; sys_platform == 'win32'\nfoo==0.43 ; sys_platform != 'win32'\n",
+        );
+    }
+
+    #[test]
+    fn cannot_exclude_other_environments() {
+        let mut updater = Updater::new();
+        updater.set_environment(linux_environment());
+        let mut lock = parse("foo==0.42 ; python_version ~= '2.7'\n").unwrap();
+        let res = updater.update(&mut lock, &[FrozenDependency::new("foo", "0.43")]);
+        match res {
+            Err(Error::CannotNegateMarker { name, .. }) => assert_eq!(name, "foo"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
     #[test]
     fn different_platform() {
         let mut updater = Updater::new();
         updater.set_options(UpdateLockOptions {
-            python_version: None,
             sys_platform: Some("win32".to_string()),
+            ..Default::default()
         });
         assert_update(
             updater,
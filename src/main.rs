@@ -5,6 +5,6 @@ fn main() {
     let result = dmenv::run_cmd(cmd);
     if let Err(error) = result {
         dmenv::print_error(&error.to_string());
-        std::process::exit(1)
+        std::process::exit(error.exit_code())
     };
 }
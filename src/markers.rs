@@ -0,0 +1,605 @@
+use std::collections::HashMap;
+
+use crate::error::Error;
+use crate::python_info::PythonInfo;
+
+// Note: the legacy dotted names (`os.name`, `sys.platform`, ...)
+// are converted to the new ones when parsing, like pip does
+const VARIABLES: &[(&str, &str)] = &[
+    ("python_version", "python_version"),
+    ("python_full_version", "python_full_version"),
+    ("os_name", "os_name"),
+    ("os.name", "os_name"),
+    ("sys_platform", "sys_platform"),
+    ("sys.platform", "sys_platform"),
+    ("platform_release", "platform_release"),
+    ("platform.release", "platform_release"),
+    ("platform_system", "platform_system"),
+    ("platform.system", "platform_system"),
+    ("platform_version", "platform_version"),
+    ("platform.version", "platform_version"),
+    ("platform_machine", "platform_machine"),
+    ("platform.machine", "platform_machine"),
+    (
+        "platform_python_implementation",
+        "platform_python_implementation",
+    ),
+    (
+        "platform.python_implementation",
+        "platform_python_implementation",
+    ),
+    ("python_implementation", "platform_python_implementation"),
+    ("implementation_name", "implementation_name"),
+    ("implementation_version", "implementation_version"),
+    ("extra", "extra"),
+];
+
+/// A PEP 508 environment marker.
+///
+/// Markers are the part after the `;` in a line like
+/// `foo==0.42 ; python_version < '3.6' and sys_platform == 'win32'`.
+///
+/// They can be parsed, combined with `and` / `or`, negated, and evaluated
+/// against an `Environment` built from a `PythonInfo`, which allows dmenv to
+/// know which lines of the lock apply to the current interpreter.
+///
+/// See https://www.python.org/dev/peps/pep-0508/#environment-markers
+#[derive(Debug, Clone, PartialEq)]
+pub enum Marker {
+    Compare {
+        left: Value,
+        operator: Operator,
+        right: Value,
+    },
+    And(Box<Marker>, Box<Marker>),
+    Or(Box<Marker>, Box<Marker>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Variable(String),
+    Literal(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Equal,
+    NotEqual,
+    LessThan,
+    LessEqual,
+    GreaterThan,
+    GreaterEqual,
+    Compatible,
+    ArbitraryEqual,
+    In,
+    NotIn,
+}
+
+impl Marker {
+    /// Parse a marker, like `python_version < '3.6' or os_name == 'nt'`
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser {
+            text,
+            tokens,
+            position: 0,
+        };
+        let res = parser.parse_or()?;
+        if parser.position != parser.tokens.len() {
+            return Err(parser.error("unexpected trailing input"));
+        }
+        Ok(res)
+    }
+
+    /// Build a marker comparing a variable with a literal,
+    /// like `sys_platform == 'win32'`
+    pub fn compare(variable: &str, operator: Operator, literal: &str) -> Self {
+        Marker::Compare {
+            left: Value::Variable(variable.to_string()),
+            operator,
+            right: Value::Literal(literal.to_string()),
+        }
+    }
+
+    pub fn and(&self, other: &Marker) -> Self {
+        Marker::And(Box::new(self.clone()), Box::new(other.clone()))
+    }
+
+    pub fn or(&self, other: &Marker) -> Self {
+        Marker::Or(Box::new(self.clone()), Box::new(other.clone()))
+    }
+
+    /// Return a marker that is true when this one is false.
+    //
+    // Note: there is no `not` in PEP 508, so we negate the operators
+    // and use De Morgan's laws. This fails for `~=` and `===`, which
+    // have no opposite.
+    pub fn negate(&self) -> Option<Self> {
+        match self {
+            Marker::Compare {
+                left,
+                operator,
+                right,
+            } => Some(Marker::Compare {
+                left: left.clone(),
+                operator: operator.negate()?,
+                right: right.clone(),
+            }),
+            Marker::And(a, b) => Some(a.negate()?.or(&b.negate()?)),
+            Marker::Or(a, b) => Some(a.negate()?.and(&b.negate()?)),
+        }
+    }
+
+    pub fn evaluate(&self, environment: &Environment) -> bool {
+        match self {
+            Marker::Compare {
+                left,
+                operator,
+                right,
+            } => {
+                let left = environment.resolve(left);
+                let right = environment.resolve(right);
+                operator.evaluate(left, right)
+            }
+            Marker::And(a, b) => a.evaluate(environment) && b.evaluate(environment),
+            Marker::Or(a, b) => a.evaluate(environment) || b.evaluate(environment),
+        }
+    }
+}
+
+impl std::fmt::Display for Marker {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Marker::Compare {
+                left,
+                operator,
+                right,
+            } => write!(f, "{} {} {}", left, operator, right),
+            // `and` binds tighter than `or`, so only `or` needs parenthesis
+            Marker::And(a, b) => {
+                let with_parens = |m: &Marker| match m {
+                    Marker::Or(..) => format!("({})", m),
+                    _ => m.to_string(),
+                };
+                write!(f, "{} and {}", with_parens(a), with_parens(b))
+            }
+            Marker::Or(a, b) => write!(f, "{} or {}", a, b),
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Value::Variable(name) => write!(f, "{}", name),
+            Value::Literal(value) if value.contains('\'') => write!(f, "\"{}\"", value),
+            Value::Literal(value) => write!(f, "'{}'", value),
+        }
+    }
+}
+
+impl Operator {
+    fn negate(self) -> Option<Self> {
+        let res = match self {
+            Operator::Equal => Operator::NotEqual,
+            Operator::NotEqual => Operator::Equal,
+            Operator::LessThan => Operator::GreaterEqual,
+            Operator::LessEqual => Operator::GreaterThan,
+            Operator::GreaterThan => Operator::LessEqual,
+            Operator::GreaterEqual => Operator::LessThan,
+            Operator::In => Operator::NotIn,
+            Operator::NotIn => Operator::In,
+            Operator::Compatible | Operator::ArbitraryEqual => return None,
+        };
+        Some(res)
+    }
+
+    fn evaluate(self, left: &str, right: &str) -> bool {
+        use std::cmp::Ordering::*;
+        let ordering = compare_values(left, right);
+        match self {
+            Operator::Equal => ordering == Equal,
+            Operator::NotEqual => ordering != Equal,
+            Operator::LessThan => ordering == Less,
+            Operator::LessEqual => ordering != Greater,
+            Operator::GreaterThan => ordering == Greater,
+            Operator::GreaterEqual => ordering != Less,
+            Operator::ArbitraryEqual => left == right,
+            Operator::In => right.contains(left),
+            Operator::NotIn => !right.contains(left),
+            Operator::Compatible => match (parse_version(left), parse_version(right)) {
+                // `~= 3.6` means `>= 3.6` and `== 3.*`
+                (Some(l), Some(r)) if r.len() >= 2 => {
+                    let prefix_len = r.len() - 1;
+                    let same_prefix =
+                        (0..prefix_len).all(|i| l.get(i).unwrap_or(&0) == r.get(i).unwrap_or(&0));
+                    compare_versions(&l, &r) != Less && same_prefix
+                }
+                _ => false,
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let s = match self {
+            Operator::Equal => "==",
+            Operator::NotEqual => "!=",
+            Operator::LessThan => "<",
+            Operator::LessEqual => "<=",
+            Operator::GreaterThan => ">",
+            Operator::GreaterEqual => ">=",
+            Operator::Compatible => "~=",
+            Operator::ArbitraryEqual => "===",
+            Operator::In => "in",
+            Operator::NotIn => "not in",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Values of the marker variables for a given interpreter.
+// Note: variables that are not set evaluate to the empty string
+#[derive(Debug, Default, Clone)]
+pub struct Environment {
+    values: HashMap<String, String>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Values of the PEP 508 environment markers for the given interpreter
+    // Note: `platform_version` and `implementation_version` are not set,
+    // they are almost never used in locks
+    pub fn from_python_info(python_info: &PythonInfo) -> Self {
+        let mut res = Environment::new();
+        let (major, minor, ..) = &python_info.version_info;
+        res.set("python_full_version", &python_info.version);
+        res.set("python_version", &format!("{}.{}", major, minor));
+        res.set("platform_system", &python_info.platform);
+        res.set("platform_machine", &python_info.platform_machine);
+        res.set("platform_release", &python_info.platform_release);
+        res.set(
+            "platform_python_implementation",
+            &python_info.platform_python_implementation,
+        );
+        res.set("implementation_name", &python_info.implementation_name);
+        res.set("sys_platform", &python_info.sys_platform);
+        res.set("os_name", &python_info.os_name);
+        res
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> &mut Self {
+        self.values.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(|x| x.as_str())
+    }
+
+    fn resolve<'a>(&'a self, value: &'a Value) -> &'a str {
+        match value {
+            Value::Literal(s) => s,
+            Value::Variable(name) => self.values.get(name).map_or("", |x| x.as_str()),
+        }
+    }
+}
+
+/// Return the PEP 508 name of a marker variable, or None if
+/// `name` is not a marker variable
+pub fn canonical_variable(name: &str) -> Option<&'static str> {
+    VARIABLES
+        .iter()
+        .find(|(x, _)| *x == name)
+        .map(|(_, canonical)| *canonical)
+}
+
+/// Compare two values the way the marker operators do.
+// Note: like in PEP 508, use version comparison when both sides are
+// versions, and fall back to string comparison otherwise
+pub fn compare_values(left: &str, right: &str) -> std::cmp::Ordering {
+    match (parse_version(left), parse_version(right)) {
+        (Some(l), Some(r)) => compare_versions(&l, &r),
+        _ => left.cmp(right),
+    }
+}
+
+fn parse_version(text: &str) -> Option<Vec<u64>> {
+    text.split('.').map(|x| x.parse().ok()).collect()
+}
+
+// Note: missing components are considered to be zero, so that
+// `3.6` and `3.6.0` are equal
+fn compare_versions(left: &[u64], right: &[u64]) -> std::cmp::Ordering {
+    let len = left.len().max(right.len());
+    let component = |v: &[u64], i| *v.get(i).unwrap_or(&0);
+    (0..len)
+        .map(|i| component(left, i).cmp(&component(right, i)))
+        .find(|x| *x != std::cmp::Ordering::Equal)
+        .unwrap_or(std::cmp::Ordering::Equal)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LeftParen,
+    RightParen,
+    And,
+    Or,
+    Operator(Operator),
+    Variable(String),
+    Literal(String),
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, Error> {
+    let error = |details: &str| Error::MalformedMarker {
+        marker: text.to_string(),
+        details: details.to_string(),
+    };
+    let chars: Vec<char> = text.chars().collect();
+    let mut res = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == '(' || c == ')' {
+            res.push(if c == '(' {
+                Token::LeftParen
+            } else {
+                Token::RightParen
+            });
+            i += 1;
+            continue;
+        }
+        if c == '\'' || c == '"' {
+            let end = chars[i + 1..]
+                .iter()
+                .position(|x| *x == c)
+                .ok_or_else(|| error("unterminated string"))?;
+            let literal: String = chars[i + 1..i + 1 + end].iter().collect();
+            res.push(Token::Literal(literal));
+            i += end + 2;
+            continue;
+        }
+        if "=!<>~".contains(c) {
+            let rest: String = chars[i..].iter().take(3).collect();
+            let (operator, len) = if rest.starts_with("===") {
+                (Operator::ArbitraryEqual, 3)
+            } else if rest.starts_with("==") {
+                (Operator::Equal, 2)
+            } else if rest.starts_with("!=") {
+                (Operator::NotEqual, 2)
+            } else if rest.starts_with("<=") {
+                (Operator::LessEqual, 2)
+            } else if rest.starts_with(">=") {
+                (Operator::GreaterEqual, 2)
+            } else if rest.starts_with("~=") {
+                (Operator::Compatible, 2)
+            } else if rest.starts_with('<') {
+                (Operator::LessThan, 1)
+            } else if rest.starts_with('>') {
+                (Operator::GreaterThan, 1)
+            } else {
+                return Err(error(&format!("unknown operator: '{}'", rest)));
+            };
+            res.push(Token::Operator(operator));
+            i += len;
+            continue;
+        }
+        if c.is_ascii_alphabetic() || c == '_' {
+            let word: String = chars[i..]
+                .iter()
+                .take_while(|x| x.is_ascii_alphanumeric() || **x == '_' || **x == '.')
+                .collect();
+            i += word.chars().count();
+            let token = match word.as_str() {
+                "and" => Token::And,
+                "or" => Token::Or,
+                "in" => Token::Operator(Operator::In),
+                "not" => {
+                    let next_word: String = chars[i..]
+                        .iter()
+                        .skip_while(|x| x.is_whitespace())
+                        .take_while(|x| x.is_ascii_alphabetic())
+                        .collect();
+                    if next_word != "in" {
+                        return Err(error("expecting `in` after `not`"));
+                    }
+                    let skipped = chars[i..].iter().take_while(|x| x.is_whitespace()).count();
+                    i += skipped + 2;
+                    Token::Operator(Operator::NotIn)
+                }
+                _ => {
+                    let canonical = canonical_variable(&word)
+                        .ok_or_else(|| error(&format!("unknown variable: '{}'", word)))?;
+                    Token::Variable(canonical.to_string())
+                }
+            };
+            res.push(token);
+            continue;
+        }
+        return Err(error(&format!("unexpected character: '{}'", c)));
+    }
+    Ok(res)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, details: &str) -> Error {
+        Error::MalformedMarker {
+            marker: self.text.to_string(),
+            details: details.to_string(),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let res = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        res
+    }
+
+    fn parse_or(&mut self) -> Result<Marker, Error> {
+        let mut res = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.position += 1;
+            let right = self.parse_and()?;
+            res = Marker::Or(Box::new(res), Box::new(right));
+        }
+        Ok(res)
+    }
+
+    fn parse_and(&mut self) -> Result<Marker, Error> {
+        let mut res = self.parse_atom()?;
+        while self.peek() == Some(&Token::And) {
+            self.position += 1;
+            let right = self.parse_atom()?;
+            res = Marker::And(Box::new(res), Box::new(right));
+        }
+        Ok(res)
+    }
+
+    fn parse_atom(&mut self) -> Result<Marker, Error> {
+        if self.peek() == Some(&Token::LeftParen) {
+            self.position += 1;
+            let res = self.parse_or()?;
+            if self.next() != Some(Token::RightParen) {
+                return Err(self.error("expecting `)`"));
+            }
+            return Ok(res);
+        }
+        let left = self.parse_value()?;
+        let operator = match self.next() {
+            Some(Token::Operator(operator)) => operator,
+            _ => return Err(self.error("expecting a comparison operator")),
+        };
+        let right = self.parse_value()?;
+        Ok(Marker::Compare {
+            left,
+            operator,
+            right,
+        })
+    }
+
+    fn parse_value(&mut self) -> Result<Value, Error> {
+        match self.next() {
+            Some(Token::Variable(name)) => Ok(Value::Variable(name)),
+            Some(Token::Literal(value)) => Ok(Value::Literal(value)),
+            _ => Err(self.error("expecting a variable or a quoted string")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linux_py36() -> Environment {
+        let mut res = Environment::new();
+        res.set("python_version", "3.6")
+            .set("python_full_version", "3.6.9")
+            .set("sys_platform", "linux")
+            .set("os_name", "posix")
+            .set("platform_machine", "x86_64");
+        res
+    }
+
+    fn evaluate(marker: &str) -> bool {
+        Marker::parse(marker).unwrap().evaluate(&linux_py36())
+    }
+
+    #[test]
+    fn parse_and_display() {
+        let marker =
+            Marker::parse("python_version<\"3.6\" and (sys_platform=='win32' or os.name == 'nt')")
+                .unwrap();
+        assert_eq!(
+            marker.to_string(),
+            "python_version < '3.6' and (sys_platform == 'win32' or os_name == 'nt')"
+        );
+    }
+
+    #[test]
+    fn parse_not_in() {
+        let marker = Marker::parse("'arm' not in platform_machine").unwrap();
+        assert_eq!(marker.to_string(), "'arm' not in platform_machine");
+    }
+
+    #[test]
+    fn parse_errors() {
+        Marker::parse("python_version <").unwrap_err();
+        Marker::parse("python_version < 3.6").unwrap_err();
+        Marker::parse("python_verion < '3.6'").unwrap_err();
+        Marker::parse("(python_version < '3.6'").unwrap_err();
+        Marker::parse("python_version < '3.6' sys_platform").unwrap_err();
+    }
+
+    #[test]
+    fn evaluate_versions() {
+        assert!(evaluate("python_version >= '3.6'"));
+        assert!(evaluate("python_version < '3.10'"));
+        assert!(evaluate("python_full_version == '3.6.9'"));
+        assert!(evaluate("python_version == '3.6.0'"));
+        assert!(evaluate("python_full_version ~= '3.6.1'"));
+        assert!(!evaluate("python_full_version ~= '3.7'"));
+    }
+
+    #[test]
+    fn evaluate_strings() {
+        assert!(evaluate("sys_platform == 'linux'"));
+        assert!(!evaluate("sys_platform == 'win32'"));
+        assert!(evaluate("'x86' in platform_machine"));
+        assert!(evaluate("extra == ''"));
+    }
+
+    #[test]
+    fn evaluate_boolean_operators() {
+        assert!(evaluate(
+            "sys_platform == 'win32' or python_version < '3.7'"
+        ));
+        assert!(!evaluate(
+            "sys_platform == 'win32' and python_version < '3.7'"
+        ));
+        assert!(evaluate(
+            "(sys_platform == 'win32' or os_name == 'posix') and python_version > '3'"
+        ));
+    }
+
+    #[test]
+    fn negate() {
+        let marker = Marker::parse("sys_platform == 'win32' or python_version < '3.6'").unwrap();
+        let negated = marker.negate().unwrap();
+        assert_eq!(
+            negated.to_string(),
+            "sys_platform != 'win32' and python_version >= '3.6'"
+        );
+        assert!(Marker::parse("python_version ~= '3.6'")
+            .unwrap()
+            .negate()
+            .is_none());
+    }
+
+    #[test]
+    fn combine_with_parens() {
+        let a = Marker::parse("python_version < '3.6' or python_version > '3.8'").unwrap();
+        let b = Marker::compare("sys_platform", Operator::Equal, "win32");
+        assert_eq!(
+            a.and(&b).to_string(),
+            "(python_version < '3.6' or python_version > '3.8') and sys_platform == 'win32'"
+        );
+    }
+}
@@ -8,6 +8,7 @@ pub struct InitOptions {
     version: String,
     author: Option<String>,
     setup_cfg: bool,
+    pyproject: bool,
 }
 
 impl InitOptions {
@@ -17,6 +18,7 @@ impl InitOptions {
             version,
             author: None,
             setup_cfg: true,
+            pyproject: false,
         }
     }
 
@@ -29,6 +31,12 @@ impl InitOptions {
         self.setup_cfg = false;
         self
     }
+
+    /// Write a `pyproject.toml` file instead of `setup.py` and `setup.cfg`
+    pub fn pyproject(&mut self) -> &mut Self {
+        self.pyproject = true;
+        self
+    }
 }
 
 fn ensure_path_does_not_exist(path: &Path) -> Result<(), Error> {
@@ -45,6 +53,9 @@ fn write_to_path(path: &Path, contents: &str) -> Result<(), Error> {
 }
 
 pub fn init(project_path: &Path, options: &InitOptions) -> Result<(), Error> {
+    if options.pyproject {
+        return init_pyproject(project_path, options);
+    }
     let setup_cfg_path = project_path.join("setup.cfg");
     let setup_py_path = project_path.join("setup.py");
 
@@ -70,6 +81,18 @@ pub fn init(project_path: &Path, options: &InitOptions) -> Result<(), Error> {
     Ok(())
 }
 
+fn init_pyproject(project_path: &Path, options: &InitOptions) -> Result<(), Error> {
+    let pyproject_path = project_path.join("pyproject.toml");
+    ensure_path_does_not_exist(&pyproject_path)?;
+    write_from_template(
+        include_str!("init/pyproject.in.toml"),
+        &pyproject_path,
+        options,
+    )?;
+    print_info_1("Project initialized with a pyproject.toml file");
+    Ok(())
+}
+
 fn write_from_template(
     template: &str,
     dest_path: &Path,
@@ -90,20 +113,19 @@ fn write_from_template(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tempdir;
 
     #[test]
     fn creates_two_files_by_default() {
         let tmp_dir = tempdir::TempDir::new("test-dmenv-init").unwrap();
         let tmp_path = tmp_dir.path();
 
-        run_init(&tmp_path.to_path_buf()).unwrap();
+        run_init(tmp_path).unwrap();
 
-        let setup_py = std::fs::read_to_string(&tmp_path.join("setup.py")).unwrap();
+        let setup_py = std::fs::read_to_string(tmp_path.join("setup.py")).unwrap();
         assert_contains(&setup_py, "setup()");
         assert_not_contains(&setup_py, "foo");
 
-        let setup_cfg = std::fs::read_to_string(&tmp_path.join("setup.cfg")).unwrap();
+        let setup_cfg = std::fs::read_to_string(tmp_path.join("setup.cfg")).unwrap();
         assert_contains(&setup_cfg, "name = foo");
         assert_contains(&setup_cfg, "version = 0.42");
     }
@@ -113,7 +135,7 @@ mod tests {
         let temp_dir = tempdir::TempDir::new("test-dmenv-init").unwrap();
         let tmp_path = temp_dir.path();
 
-        run_init_no_setup_cfg(&tmp_path.to_path_buf()).unwrap();
+        run_init_no_setup_cfg(tmp_path).unwrap();
 
         let setup_cfg_path = tmp_path.join("setup.cfg");
         assert!(!setup_cfg_path.exists());
@@ -130,7 +152,7 @@ mod tests {
         let setup_cfg_path = tmp_path.join("setup.cfg");
         touch(&setup_cfg_path);
 
-        let err = run_init(&tmp_path.to_path_buf()).unwrap_err();
+        let err = run_init(tmp_path).unwrap_err();
         assert_file_exists_error(err, &setup_cfg_path);
     }
 
@@ -141,10 +163,42 @@ mod tests {
         let setup_py_path = tmp_path.join("setup.py");
         touch(&setup_py_path);
 
-        let err = run_init_no_setup_cfg(&tmp_path.to_path_buf()).unwrap_err();
+        let err = run_init_no_setup_cfg(tmp_path).unwrap_err();
         assert_file_exists_error(err, &setup_py_path);
     }
 
+    #[test]
+    fn pyproject() {
+        let temp_dir = tempdir::TempDir::new("test-dmenv-init").unwrap();
+        let tmp_path = temp_dir.path();
+
+        let mut init_options = InitOptions::new("foo".to_string(), "0.42".to_string());
+        init_options.pyproject().author("jane@corp.com");
+        init(tmp_path, &init_options).unwrap();
+
+        assert!(!tmp_path.join("setup.py").exists());
+        assert!(!tmp_path.join("setup.cfg").exists());
+        let pyproject = std::fs::read_to_string(tmp_path.join("pyproject.toml")).unwrap();
+        assert_contains(&pyproject, "name = \"foo\"");
+        assert_contains(&pyproject, "version = \"0.42\"");
+        assert_contains(&pyproject, "{ name = \"jane@corp.com\" }");
+        let project_kind = crate::project::ProjectKind::detect(tmp_path).unwrap();
+        assert_eq!(project_kind, Some(crate::project::ProjectKind::Pyproject));
+    }
+
+    #[test]
+    fn does_not_overwrite_pyproject() {
+        let temp_dir = tempdir::TempDir::new("test-dmenv-init").unwrap();
+        let tmp_path = temp_dir.path();
+        let pyproject_path = tmp_path.join("pyproject.toml");
+        touch(&pyproject_path);
+
+        let mut init_options = InitOptions::new("foo".to_string(), "0.42".to_string());
+        init_options.pyproject();
+        let err = init(tmp_path, &init_options).unwrap_err();
+        assert_file_exists_error(err, &pyproject_path);
+    }
+
     fn assert_contains(text: &str, sub_string: &str) {
         if !text.contains(sub_string) {
             panic!("\n{}should contain {}", text, sub_string);
@@ -165,7 +219,7 @@ mod tests {
     }
 
     fn touch(path: &Path) {
-        std::fs::write(&path, "# don't overwrite me").unwrap()
+        std::fs::write(path, "# don't overwrite me").unwrap()
     }
 
     fn run_init(tmp_path: &Path) -> Result<(), Error> {
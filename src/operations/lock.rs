@@ -1,13 +1,15 @@
 use colored::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use crate::dependencies::FrozenDependency;
 use crate::error::*;
 use crate::lock;
 use crate::lock::Updater;
-use crate::lock::{git_bump, simple_bump};
+use crate::lock::{apply_hashes, git_bump, simple_bump, ChangeSet, Hashes, Lock, Snapshot};
+use crate::markers::Environment;
 use crate::ui::*;
 use crate::BumpType;
+use crate::DryRun;
 use crate::Metadata;
 use crate::UpdateLockOptions;
 
@@ -16,22 +18,27 @@ pub fn bump(
     name: &str,
     version: &str,
     bump_type: BumpType,
+    hashes: &Hashes,
+    dry_run: DryRun,
     metadata: &Metadata,
 ) -> Result<(), Error> {
     let lock_contents =
         std::fs::read_to_string(lock_path).map_err(|e| new_read_error(e, lock_path))?;
-    let mut deps = lock::parse(&lock_contents)?;
+    let mut lock = lock::parse(&lock_contents)?;
     let changed = match bump_type {
-        BumpType::Git => git_bump(&mut deps, name, version),
-        BumpType::Simple => simple_bump(&mut deps, name, version),
+        BumpType::Git => git_bump(&mut lock, name, version),
+        BumpType::Simple => simple_bump(&mut lock, name, version),
     }?;
     if !changed {
         print_warning(&format!("Dependency {} already up-to-date", name.bold()));
         return Ok(());
     }
-    let new_contents = lock::dump(deps);
-    write_lock(lock_path, &new_contents, metadata)?;
-    println!("{}", "ok!".green());
+    apply_hashes(&mut lock, hashes)?;
+    apply_lock_options(&mut lock, metadata);
+    save(lock_path, &lock_contents, &lock, dry_run, metadata)?;
+    if dry_run == DryRun::Off {
+        println!("{}", "ok!".green());
+    }
     Ok(())
 }
 
@@ -39,44 +46,190 @@ pub fn update(
     lock_path: &Path,
     frozen_deps: Vec<FrozenDependency>,
     update_options: UpdateLockOptions,
+    environment: &Environment,
+    hashes: &Hashes,
+    dry_run: DryRun,
     metadata: &Metadata,
 ) -> Result<(), Error> {
     print_info_2(&format!("Generating {}", lock_path.display()));
-    let lock_contents = if lock_path.exists() {
-        std::fs::read_to_string(lock_path).map_err(|e| new_read_error(e, lock_path))?
-    } else {
-        String::new()
-    };
+    let lock_contents = read_lock_if_exists(lock_path)?;
+    let mut lock = updated_lock(
+        &lock_contents,
+        &frozen_deps,
+        update_options,
+        environment,
+        hashes,
+    )?;
+    apply_lock_options(&mut lock, metadata);
+    save(lock_path, &lock_contents, &lock, dry_run, metadata)
+}
+
+/// Same as `update()`, but instead of writing the lock, return an
+/// error if its contents would change
+pub fn check(
+    lock_path: &Path,
+    frozen_deps: Vec<FrozenDependency>,
+    update_options: UpdateLockOptions,
+    environment: &Environment,
+    hashes: &Hashes,
+    metadata: &Metadata,
+) -> Result<(), Error> {
+    print_info_2(&format!("Checking {}", lock_path.display()));
+    let lock_contents = read_lock_if_exists(lock_path)?;
+    // Note: parse and dump the current lock so that the top
+    // comment is not part of the comparison
+    let old_contents = lock::dump(&lock::parse(&lock_contents)?);
+    let mut new_lock = updated_lock(
+        &lock_contents,
+        &frozen_deps,
+        update_options,
+        environment,
+        hashes,
+    )?;
+    apply_lock_options(&mut new_lock, metadata);
+    let new_contents = lock::dump(&new_lock);
+    if old_contents != new_contents {
+        print_diff(&lock_name(lock_path), &old_contents, &new_contents);
+        return Err(Error::OutdatedLock {
+            path: lock_path.to_path_buf(),
+        });
+    }
+    println!("{}", "ok!".green());
+    Ok(())
+}
 
+fn updated_lock(
+    lock_contents: &str,
+    frozen_deps: &[FrozenDependency],
+    update_options: UpdateLockOptions,
+    environment: &Environment,
+    hashes: &Hashes,
+) -> Result<Lock, Error> {
     let mut updater = Updater::new();
     updater.set_options(update_options);
-    let mut locked_deps = lock::parse(&lock_contents)?;
-    updater.update(&mut locked_deps, &frozen_deps);
+    updater.set_environment(environment.clone());
+    let mut lock = lock::parse(lock_contents)?;
+    updater.update(&mut lock, frozen_deps)?;
+    apply_hashes(&mut lock, hashes)?;
+    Ok(lock)
+}
 
-    let new_contents = lock::dump(locked_deps);
-    write_lock(lock_path, &new_contents, metadata)
+// Note: a missing lock is the same as an empty one
+fn read_lock_if_exists(lock_path: &Path) -> Result<String, Error> {
+    if !lock_path.exists() {
+        return Ok(String::new());
+    }
+    std::fs::read_to_string(lock_path).map_err(|e| new_read_error(e, lock_path))
 }
 
 pub fn tidy(
     lock_path: &Path,
     frozen_deps: Vec<FrozenDependency>,
+    environment: &Environment,
+    dry_run: DryRun,
     metadata: &Metadata,
 ) -> Result<(), Error> {
     print_info_2(&format!("Tidying {}", lock_path.display()));
     let frozen_names: Vec<_> = frozen_deps.into_iter().map(|x| x.name).collect();
     let lock_contents =
         std::fs::read_to_string(lock_path).map_err(|e| new_read_error(e, lock_path))?;
-    let locked_deps = lock::parse(&lock_contents)?;
-
-    // Remove from the lock file anything that is _not_ in the clean virtualenv
-    let (deps_to_keep, deps_to_remove): (Vec<_>, Vec<_>) = locked_deps
-        .into_iter()
-        .partition(|x| frozen_names.contains(&x.name()));
-    for dep in deps_to_remove {
-        println!("- {}", dep.name());
+    let mut lock = lock::parse(&lock_contents)?;
+
+    // Remove from the lock file anything that is _not_ in the clean virtualenv,
+    // keeping lines that do not apply to the current interpreter
+    lock.retain(|x| !x.applies_to(environment) || frozen_names.contains(&x.name()));
+    apply_lock_options(&mut lock, metadata);
+    save(lock_path, &lock_contents, &lock, dry_run, metadata)
+}
+
+pub fn merge(
+    lock_path: &Path,
+    snapshot_paths: &[PathBuf],
+    dry_run: DryRun,
+    metadata: &Metadata,
+) -> Result<(), Error> {
+    print_info_2(&format!("Generating {}", lock_path.display()));
+    let mut snapshots = vec![];
+    for snapshot_path in snapshot_paths {
+        let contents =
+            std::fs::read_to_string(snapshot_path).map_err(|e| new_read_error(e, snapshot_path))?;
+        snapshots.push(Snapshot::parse(&contents)?);
+    }
+    let lock_contents = read_lock_if_exists(lock_path)?;
+    let mut lock = lock::parse(&lock_contents)?;
+    lock::merge(&mut lock, &snapshots)?;
+    apply_lock_options(&mut lock, metadata);
+    save(lock_path, &lock_contents, &lock, dry_run, metadata)
+}
+
+/// Write the pip options at the top of the lock, if they are recorded.
+/// See the `record_index_options` setting
+fn apply_lock_options(lock: &mut Lock, metadata: &Metadata) {
+    if let Some(options) = &metadata.lock_options {
+        lock.set_options(options);
+    }
+}
+
+/// Show the changes made to the lock, then write it, unless
+/// `dry_run` is set
+fn save(
+    lock_path: &Path,
+    old_contents: &str,
+    new_lock: &Lock,
+    dry_run: DryRun,
+    metadata: &Metadata,
+) -> Result<(), Error> {
+    let old_lock = lock::parse(old_contents)?;
+    let new_contents = lock::dump(new_lock);
+    match dry_run {
+        DryRun::Off => {
+            for change in ChangeSet::between(&old_lock, new_lock).changes() {
+                println!("{}", change);
+            }
+            write_lock(lock_path, &new_contents, metadata)
+        }
+        DryRun::Summary => {
+            let changes = ChangeSet::between(&old_lock, new_lock);
+            if changes.is_empty() {
+                println!("No changes in {}", lock_path.display());
+            } else {
+                print!("{}", changes.table());
+            }
+            Ok(())
+        }
+        DryRun::Diff => {
+            let old_contents = lock::dump(&old_lock);
+            print_diff(&lock_name(lock_path), &old_contents, &new_contents);
+            Ok(())
+        }
     }
-    let new_contents = &lock::dump(deps_to_keep);
-    write_lock(lock_path, new_contents, metadata)
+}
+
+fn lock_name(lock_path: &Path) -> String {
+    lock_path.file_name().map_or_else(
+        || lock_path.display().to_string(),
+        |x| x.to_string_lossy().to_string(),
+    )
+}
+
+/// Returns true if the lock contains hashes.
+// Note: a missing lock has no hashes
+pub fn has_hashes(lock_path: &Path) -> Result<bool, Error> {
+    let lock = read(lock_path)?;
+    Ok(lock::has_hashes(&lock))
+}
+
+/// Same as `lock::check_hashable()`, so that we can fail before
+/// computing any hash
+pub fn check_hashable(lock_path: &Path) -> Result<(), Error> {
+    let lock = read(lock_path)?;
+    lock::check_hashable(&lock)
+}
+
+/// Read and parse the lock, if it exists
+pub fn read(lock_path: &Path) -> Result<Lock, Error> {
+    let lock_contents = read_lock_if_exists(lock_path)?;
+    lock::parse(&lock_contents)
 }
 
 pub fn write_lock(lock_path: &Path, lock_contents: &str, metadata: &Metadata) -> Result<(), Error> {
@@ -84,6 +237,7 @@ pub fn write_lock(lock_path: &Path, lock_contents: &str, metadata: &Metadata) ->
         dmenv_version,
         python_version,
         python_platform,
+        ..
     } = metadata;
 
     let top_comment = format!(
@@ -92,5 +246,5 @@ pub fn write_lock(lock_path: &Path, lock_contents: &str, metadata: &Metadata) ->
     );
 
     let to_write = top_comment + lock_contents;
-    std::fs::write(&lock_path, to_write).map_err(|e| new_write_error(e, lock_path))
+    std::fs::write(lock_path, to_write).map_err(|e| new_write_error(e, lock_path))
 }
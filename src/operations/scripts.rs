@@ -19,12 +19,7 @@ pub fn process(paths: &Paths, mode: ProcessScriptsMode) -> Result<(), Error> {
         console_scripts.len()
     ));
     for console_script in console_scripts {
-        process_script(
-            &paths.venv,
-            &scripts_path.to_path_buf(),
-            &console_script,
-            mode,
-        )?;
+        process_script(&paths.venv, scripts_path, &console_script, mode)?;
     }
     Ok(())
 }
@@ -45,7 +40,7 @@ fn process_script(
     ];
 
     for name in names.iter() {
-        process_script_with_name(venv_path, scripts_path, &name, mode)?;
+        process_script_with_name(venv_path, scripts_path, name, mode)?;
     }
     Ok(())
 }
@@ -164,7 +159,7 @@ fn delete_if_exists(path: &Path) -> Result<(), Error> {
 
 fn list_egg_info_dirs(project_path: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
     let mut res = vec![];
-    for entry in std::fs::read_dir(&project_path)? {
+    for entry in std::fs::read_dir(project_path)? {
         let entry = entry?;
         let path = entry.path();
         if path.is_dir() {
@@ -1,4 +1,6 @@
 use colored::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::path::{Path, PathBuf};
 
 use crate::error::*;
@@ -7,6 +9,22 @@ use crate::run::run;
 use crate::settings::Settings;
 use crate::ui::*;
 
+/// Name of the file, inside the virtualenv, where the base interpreter
+/// is recorded. See `check_base_python()`
+const BASE_PYTHON_FILENAME: &str = "dmenv-base-python.json";
+
+/// Name of the file, inside the virtualenv, where the fingerprint of
+/// the project files is recorded. See `fingerprint()`
+const FINGERPRINT_FILENAME: &str = "dmenv-fingerprint.txt";
+
+/// The interpreter used to create the virtualenv
+#[derive(Debug, Serialize, Deserialize)]
+struct BasePython {
+    executable: PathBuf,
+    version: String,
+    sha256: String,
+}
+
 pub fn clean(venv_path: PathBuf) -> Result<(), Error> {
     print_info_1(&format!("Cleaning {}", venv_path.display()));
     if !venv_path.exists() {
@@ -22,11 +40,14 @@ pub fn create(
     python_info: &PythonInfo,
     settings: &Settings,
 ) -> Result<(), Error> {
+    if settings.venv_from_stdlib {
+        check_venv_modules(python_info)?;
+    }
     let parent_venv_path = venv_path
         .parent()
         .ok_or_else(|| new_error("venv_path has no parent".to_string()))?;
     print_info_2(&format!("Creating virtualenv in: {}", venv_path.display()));
-    std::fs::create_dir_all(&parent_venv_path).map_err(|e| {
+    std::fs::create_dir_all(parent_venv_path).map_err(|e| {
         new_error(format!(
             "Could not create {}: {}",
             parent_venv_path.display(),
@@ -56,7 +77,137 @@ pub fn create(
         args.join(" ")
     );
     let cwd = std::env::current_dir().map_err(|e| Error::NoWorkingDirectory { io_error: e })?;
-    run(&cwd, &python_binary, &args)
+    run(&cwd, python_binary, &args)?;
+    let executable = canonical_executable(python_info);
+    record_base_python(venv_path, &executable, &python_info.version)
+}
+
+fn record_base_python(venv_path: &Path, executable: &Path, version: &str) -> Result<(), Error> {
+    let base_python = BasePython {
+        executable: executable.to_path_buf(),
+        version: version.to_string(),
+        sha256: hash_file(executable)?,
+    };
+    let path = venv_path.join(BASE_PYTHON_FILENAME);
+    let contents = serde_json::to_string_pretty(&base_python)
+        .map_err(|e| new_error(format!("could not serialize base interpreter: {}", e)))?;
+    std::fs::write(&path, contents).map_err(|e| new_write_error(e, &path))
+}
+
+/// Check that the interpreter used to create the virtualenv has not been
+/// removed or upgraded in place since then. Returns what is wrong, if anything.
+//
+// Note: virtualenvs created by older versions of dmenv, or by other tools,
+// have nothing recorded and are not checked
+pub fn check_base_python(
+    venv_path: &Path,
+    python_info: &PythonInfo,
+) -> Result<Option<String>, Error> {
+    check_base(
+        venv_path,
+        &canonical_executable(python_info),
+        &python_info.version,
+    )
+}
+
+// Note: `current_executable` and `current_version` describe the
+// interpreter dmenv is using
+fn check_base(
+    venv_path: &Path,
+    current_executable: &Path,
+    current_version: &str,
+) -> Result<Option<String>, Error> {
+    let path = venv_path.join(BASE_PYTHON_FILENAME);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = std::fs::read_to_string(&path).map_err(|e| new_read_error(e, &path))?;
+    let base_python: BasePython = serde_json::from_str(&contents)
+        .map_err(|e| new_error(format!("could not parse {}: {}", path.display(), e)))?;
+    let BasePython {
+        executable,
+        version,
+        sha256,
+    } = base_python;
+    let description = format!("{} (Python {})", executable.display(), version);
+    if !executable.exists() {
+        return Ok(Some(format!("{} no longer exists", description)));
+    }
+    if hash_file(&executable)? == sha256 {
+        return Ok(None);
+    }
+    // If the base interpreter is the one we are using, we know
+    // its new version
+    if current_executable == executable && current_version != version {
+        return Ok(Some(format!(
+            "{} has been upgraded to Python {}",
+            description, current_version
+        )));
+    }
+    Ok(Some(format!("{} has changed", description)))
+}
+
+/// Compute a fingerprint of the given files, so that we know when
+/// one of them changes. Missing files are taken into account too.
+pub fn fingerprint(paths: &[PathBuf]) -> Result<String, Error> {
+    let mut hasher = Sha256::new();
+    for path in paths {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        hasher.update(name.as_bytes());
+        if path.exists() {
+            hasher.update(hash_file(path)?.as_bytes());
+        } else {
+            hasher.update(b"missing");
+        }
+        hasher.update(b"\n");
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Remember the fingerprint of the files that were last installed in
+/// the virtualenv
+pub fn write_fingerprint(venv_path: &Path, fingerprint: &str) -> Result<(), Error> {
+    let path = venv_path.join(FINGERPRINT_FILENAME);
+    std::fs::write(&path, fingerprint).map_err(|e| new_write_error(e, &path))
+}
+
+/// Return the fingerprint written by `write_fingerprint()`, if any
+pub fn read_fingerprint(venv_path: &Path) -> Result<Option<String>, Error> {
+    let path = venv_path.join(FINGERPRINT_FILENAME);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = std::fs::read_to_string(&path).map_err(|e| new_read_error(e, &path))?;
+    Ok(Some(contents.trim().to_string()))
+}
+
+fn canonical_executable(python_info: &PythonInfo) -> PathBuf {
+    let executable = &python_info.executable;
+    executable
+        .canonicalize()
+        .unwrap_or_else(|_| executable.to_path_buf())
+}
+
+fn hash_file(path: &Path) -> Result<String, Error> {
+    let contents = std::fs::read(path).map_err(|e| new_read_error(e, path))?;
+    Ok(format!("{:x}", Sha256::digest(&contents)))
+}
+
+/// Make sure `python -m venv` can work, to avoid obscure error messages
+/// from Python
+fn check_venv_modules(python_info: &PythonInfo) -> Result<(), Error> {
+    let missing = if !python_info.has_venv {
+        "venv"
+    } else if !python_info.has_ensurepip {
+        "ensurepip"
+    } else {
+        return Ok(());
+    };
+    Err(Error::MissingPythonModule {
+        python: python_info.binary.to_path_buf(),
+        version: python_info.version.to_string(),
+        module: missing.to_string(),
+    })
 }
 
 pub fn expect(venv_path: &Path) -> Result<(), Error> {
@@ -67,3 +218,90 @@ pub fn expect(venv_path: &Path) -> Result<(), Error> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Setup {
+        tmp_dir: tempdir::TempDir,
+    }
+
+    impl Setup {
+        fn new() -> Self {
+            let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+            std::fs::create_dir(tmp_dir.path().join("venv")).unwrap();
+            std::fs::write(tmp_dir.path().join("python"), "python 3.8.1").unwrap();
+            let res = Setup { tmp_dir };
+            record_base_python(&res.venv_path(), &res.python(), "3.8.1").unwrap();
+            res
+        }
+
+        fn venv_path(&self) -> PathBuf {
+            self.tmp_dir.path().join("venv")
+        }
+
+        fn python(&self) -> PathBuf {
+            self.tmp_dir.path().join("python")
+        }
+
+        fn check(&self, current_version: &str) -> Option<String> {
+            check_base(&self.venv_path(), &self.python(), current_version).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_unchanged() {
+        let setup = Setup::new();
+        assert_eq!(setup.check("3.8.1"), None);
+    }
+
+    #[test]
+    fn test_nothing_recorded() {
+        let setup = Setup::new();
+        std::fs::remove_file(setup.venv_path().join(BASE_PYTHON_FILENAME)).unwrap();
+        assert_eq!(setup.check("3.8.1"), None);
+    }
+
+    #[test]
+    fn test_upgraded_in_place() {
+        let setup = Setup::new();
+        std::fs::write(setup.python(), "python 3.8.5").unwrap();
+        let actual = setup.check("3.8.5").unwrap();
+        assert!(actual.ends_with("has been upgraded to Python 3.8.5"));
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let setup = Setup::new();
+        let lock = setup.tmp_dir.path().join("requirements.lock");
+        let files = vec![lock.clone(), setup.python()];
+        let missing = fingerprint(&files).unwrap();
+        std::fs::write(&lock, "").unwrap();
+        let empty = fingerprint(&files).unwrap();
+        assert_ne!(missing, empty);
+        std::fs::write(&lock, "attrs==19.3.0\n").unwrap();
+        let written = fingerprint(&files).unwrap();
+        assert_ne!(written, empty);
+        assert_eq!(fingerprint(&files).unwrap(), written);
+    }
+
+    #[test]
+    fn test_read_and_write_fingerprint() {
+        let setup = Setup::new();
+        assert_eq!(read_fingerprint(&setup.venv_path()).unwrap(), None);
+        write_fingerprint(&setup.venv_path(), "abc123").unwrap();
+        assert_eq!(
+            read_fingerprint(&setup.venv_path()).unwrap(),
+            Some("abc123".to_string())
+        );
+    }
+
+    #[test]
+    fn test_removed() {
+        let setup = Setup::new();
+        std::fs::remove_file(setup.python()).unwrap();
+        let actual = setup.check("3.8.5").unwrap();
+        assert!(actual.ends_with("(Python 3.8.1) no longer exists"));
+    }
+}
@@ -18,20 +18,37 @@ pub const DEV_LOCK_FILENAME: &str = "requirements.lock";
 use crate::error::*;
 
 // Container for all the PathsBuf used by the venv_manager
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Paths {
     pub project: PathBuf,
     pub venv: PathBuf,
     pub lock: PathBuf,
-    pub setup_py: PathBuf,
+}
+
+/// Get the path where the hashes of the dependencies are cached.
+// Note: like the virtualenvs created outside the project, this
+// uses app_dir UserCache
+pub fn get_hashes_cache_path() -> Result<PathBuf, Error> {
+    app_dirs::app_dir(AppDataType::UserCache, &APP_INFO, "hashes")
+        .map_err(|e| new_error(format!("Could not create dmenv hashes path: {}", e)))
+}
+
+/// Get the path of the user configuration file.
+// Note: use app_dir UserConfig so that we honor XDG spec on Linux
+// (`~/.config/dmenv/config.toml`)
+pub fn get_user_config_path() -> Result<PathBuf, Error> {
+    let config_dir = app_dirs::get_app_root(AppDataType::UserConfig, &APP_INFO)
+        .map_err(|e| new_error(format!("Could not get dmenv config path: {}", e)))?;
+    Ok(config_dir.join("config.toml"))
 }
 
 #[derive(Debug)]
 pub struct PathsResolver {
     venv_outside_project: bool,
-    production: bool,
+    venv_subdir: String,
     system_site_packages: bool,
-    python_version: String,
+    lock_name: String,
+    python_dir_name: String,
     project_path: PathBuf,
 }
 
@@ -39,29 +56,26 @@ pub struct PathsResolver {
 //
 // This makes sure that incompatible virtualenv have different paths.
 // (For instance, a "production" virtualenv must be in a different path
-// than the "development" virtualenv). Ditto when the Python version changes
+// than the "development" virtualenv, and so must the virtualenvs of any
+// other profile). Ditto when the Python version or implementation changes
 impl PathsResolver {
-    pub fn new(project_path: PathBuf, python_version: String, settings: &Settings) -> Self {
+    pub fn new(project_path: PathBuf, python_dir_name: String, settings: &Settings) -> Self {
+        let profile = settings.profile();
         PathsResolver {
             venv_outside_project: settings.venv_outside_project,
             project_path,
-            python_version,
-            production: settings.production,
+            python_dir_name,
+            venv_subdir: profile.venv_subdir,
             system_site_packages: settings.system_site_packages,
+            lock_name: profile.lock,
         }
     }
 
     pub fn paths(&self) -> Result<Paths, Error> {
-        let lock_path = if self.production {
-            PROD_LOCK_FILENAME
-        } else {
-            DEV_LOCK_FILENAME
-        };
         Ok(Paths {
             project: self.project_path.clone(),
             venv: self.get_venv_path()?,
-            lock: self.project_path.join(lock_path),
-            setup_py: self.project_path.join("setup.py"),
+            lock: self.project_path.join(&self.lock_name),
         })
     }
 
@@ -88,13 +102,8 @@ impl PathsResolver {
     // and use otherwise "expected" paths on macOS and Windows
     // (`Library/Cachches` and `AppData\Local` respectively)
     fn get_venv_path_outside(&self) -> Result<PathBuf, Error> {
-        let data_dir =
-            app_dirs::app_dir(AppDataType::UserCache, &APP_INFO, "venv").map_err(|e| {
-                new_error(format!(
-                    "Could not create dmenv cache path: {}",
-                    e.to_string()
-                ))
-            })?;
+        let data_dir = app_dirs::app_dir(AppDataType::UserCache, &APP_INFO, "venv")
+            .map_err(|e| new_error(format!("Could not create dmenv cache path: {}", e)))?;
         let subdir = &self.sub_dir();
         let project_name = self.project_path.file_name().ok_or_else(|| {
             new_error(format!(
@@ -107,13 +116,15 @@ impl PathsResolver {
     }
 
     fn sub_dir(&self) -> String {
-        let prod_or_dev = if self.production { "prod" } else { "dev" };
         let system_prefix = if self.system_site_packages {
             "-system"
         } else {
             ""
         };
-        format!("{}{}/{}", prod_or_dev, system_prefix, &self.python_version)
+        format!(
+            "{}{}/{}",
+            self.venv_subdir, system_prefix, &self.python_dir_name
+        )
     }
 }
 
@@ -160,13 +171,13 @@ mod tests {
     fn test_resolving_paths_prod_differs_from_dev() {
         let project_path = Path::new("/tmp/foo");
         let prod_settings = Settings {
-            production: true,
+            profile: "prod".to_string(),
             ..Default::default()
         };
         let prod_path = get_venv_path(project_path.to_path_buf(), prod_settings, "3.7");
 
         let dev_settings = Settings {
-            production: false,
+            profile: "dev".to_string(),
             ..Default::default()
         };
         let dev_path = get_venv_path(project_path.to_path_buf(), dev_settings, "3.7");
@@ -0,0 +1,605 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::config::{TaskCommands, TaskConfig};
+use crate::error::*;
+
+const SETUP_CFG_TASKS_SECTION: &str = "dmenv.tasks";
+
+/// How the project's metadata and dependencies are declared
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ProjectKind {
+    /// A `setup.py` file, possibly using a `setup.cfg`
+    SetupPy,
+    /// A `pyproject.toml` file with a `[project]` table, see PEP 621
+    Pyproject,
+}
+
+impl ProjectKind {
+    /// Look for a project in the given directory.
+    // Note: a `pyproject.toml` without a `[project]` table is
+    // only used to configure tools, so we fall back to `setup.py`
+    pub fn detect(project_path: &Path) -> Result<Option<Self>, Error> {
+        if has_project_table(&project_path.join("pyproject.toml"))? {
+            return Ok(Some(ProjectKind::Pyproject));
+        }
+        if project_path.join("setup.py").exists() {
+            return Ok(Some(ProjectKind::SetupPy));
+        }
+        Ok(None)
+    }
+
+    /// Same as `detect()`, but return an error when there is no project
+    pub fn expect(project_path: &Path) -> Result<Self, Error> {
+        Self::detect(project_path)?.ok_or(Error::MissingProjectFile {})
+    }
+
+    pub fn file_name(self) -> &'static str {
+        match self {
+            ProjectKind::SetupPy => "setup.py",
+            ProjectKind::Pyproject => "pyproject.toml",
+        }
+    }
+}
+
+/// Get the versions of Python supported by the project, from the
+/// `requires-python` key of `pyproject.toml`, or the `python_requires`
+/// option in `setup.cfg`
+// Note: `python_requires` passed to `setup()` in `setup.py` is not
+// taken into account, as it would require running `setup.py`
+pub fn python_requirement(project_path: &Path) -> Result<Option<String>, Error> {
+    match ProjectKind::detect(project_path)? {
+        None => Ok(None),
+        Some(ProjectKind::Pyproject) => {
+            let pyproject = read_pyproject(&project_path.join("pyproject.toml"))?;
+            let requirement = pyproject
+                .as_ref()
+                .and_then(|p| p.get("project"))
+                .and_then(|p| p.get("requires-python"))
+                .and_then(|r| r.as_str())
+                .map(|r| r.to_string());
+            Ok(requirement)
+        }
+        Some(ProjectKind::SetupPy) => {
+            let setup_cfg = read_setup_cfg(project_path)?;
+            let requirement = setup_cfg
+                .as_ref()
+                .and_then(|c| c.get("options", "python_requires"))
+                .map(|r| r.to_string());
+            Ok(requirement)
+        }
+    }
+}
+
+/// What dmenv knows about a project without installing it
+#[derive(Debug, Default, PartialEq)]
+pub struct ProjectMetadata {
+    pub name: Option<String>,
+    /// Requirements of the project, including the ones of all its extras
+    pub requirements: Vec<String>,
+}
+
+impl ProjectMetadata {
+    /// Names of the required distributions, normalized like in PEP 503
+    pub fn required_names(&self) -> Vec<String> {
+        self.requirements
+            .iter()
+            .map(|r| requirement_name(r))
+            .filter(|n| !n.is_empty())
+            .collect()
+    }
+}
+
+/// Read the name and the requirements of the project, from `pyproject.toml`
+/// or from `setup.cfg`
+// Note: like in `python_requirement()`, arguments passed to `setup()`
+// in `setup.py` are not taken into account
+pub fn read_metadata(project_path: &Path) -> Result<ProjectMetadata, Error> {
+    match ProjectKind::detect(project_path)? {
+        None => Ok(ProjectMetadata::default()),
+        Some(ProjectKind::Pyproject) => {
+            let pyproject = read_pyproject(&project_path.join("pyproject.toml"))?;
+            let project = match pyproject.as_ref().and_then(|p| p.get("project")) {
+                None => return Ok(ProjectMetadata::default()),
+                Some(project) => project,
+            };
+            let name = project
+                .get("name")
+                .and_then(|n| n.as_str())
+                .map(|n| n.to_string());
+            let mut lists = vec![];
+            if let Some(dependencies) = project.get("dependencies") {
+                lists.push(dependencies);
+            }
+            if let Some(extras) = project
+                .get("optional-dependencies")
+                .and_then(|o| o.as_table())
+            {
+                lists.extend(extras.values());
+            }
+            let requirements = lists
+                .into_iter()
+                .filter_map(|l| l.as_array())
+                .flatten()
+                .filter_map(|r| r.as_str())
+                .map(|r| r.to_string())
+                .collect();
+            Ok(ProjectMetadata { name, requirements })
+        }
+        Some(ProjectKind::SetupPy) => {
+            let setup_cfg = match read_setup_cfg(project_path)? {
+                None => return Ok(ProjectMetadata::default()),
+                Some(setup_cfg) => setup_cfg,
+            };
+            let name = setup_cfg.get("metadata", "name").map(|n| n.to_string());
+            let mut requirements = vec![];
+            for (section, key, value) in &setup_cfg.entries {
+                let is_requirement = section == "options.extras_require"
+                    || (section == "options" && key == "install_requires");
+                if is_requirement {
+                    requirements.extend(value.lines().map(|l| l.trim().to_string()));
+                }
+            }
+            requirements.retain(|r| !r.is_empty());
+            Ok(ProjectMetadata { name, requirements })
+        }
+    }
+}
+
+/// The console scripts declared by the project, as (name, object reference)
+/// pairs like `("demo", "demo:main")`. They are read from the `[project.scripts]`
+/// table of `pyproject.toml`, or from the `console_scripts` entry points in
+/// `setup.cfg`
+pub fn console_scripts(project_path: &Path) -> Result<Vec<(String, String)>, Error> {
+    match ProjectKind::detect(project_path)? {
+        None => Ok(vec![]),
+        Some(ProjectKind::Pyproject) => {
+            let pyproject = read_pyproject(&project_path.join("pyproject.toml"))?;
+            let scripts = pyproject
+                .as_ref()
+                .and_then(|p| p.get("project"))
+                .and_then(|p| p.get("scripts"))
+                .and_then(|s| s.as_table());
+            let res = scripts
+                .into_iter()
+                .flatten()
+                .filter_map(|(name, value)| Some((name.to_string(), value.as_str()?.to_string())))
+                .collect();
+            Ok(res)
+        }
+        Some(ProjectKind::SetupPy) => {
+            let setup_cfg = read_setup_cfg(project_path)?;
+            let value = setup_cfg
+                .as_ref()
+                .and_then(|c| c.get("options.entry_points", "console_scripts"))
+                .unwrap_or_default();
+            let res = value
+                .lines()
+                .filter_map(|line| {
+                    let pos = line.find('=')?;
+                    let name = line[..pos].trim().to_string();
+                    Some((name, line[pos + 1..].trim().to_string()))
+                })
+                .collect();
+            Ok(res)
+        }
+    }
+}
+
+/// Get the requirements needed to build the project, from the `[build-system]`
+/// table of `pyproject.toml`.
+// Note: like pip, fall back to setuptools and wheel when there is no such table
+pub fn build_requirements(project_path: &Path) -> Result<Vec<String>, Error> {
+    let pyproject = read_pyproject(&project_path.join("pyproject.toml"))?;
+    let requires = pyproject
+        .as_ref()
+        .and_then(|p| p.get("build-system"))
+        .and_then(|b| b.get("requires"))
+        .and_then(|r| r.as_array());
+    let res = match requires {
+        Some(requires) => requires
+            .iter()
+            .filter_map(|r| r.as_str())
+            .map(|r| r.to_string())
+            .collect(),
+        None => vec!["setuptools".to_string(), "wheel".to_string()],
+    };
+    Ok(res)
+}
+
+/// Get the name of the distribution in a requirement like `foo[bar] >= 1.0`,
+/// normalized like in PEP 503
+fn requirement_name(requirement: &str) -> String {
+    let name: String = requirement
+        .trim()
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || "-_.".contains(*c))
+        .collect();
+    normalize_name(&name)
+}
+
+pub fn normalize_name(name: &str) -> String {
+    name.to_lowercase().replace(['_', '.'], "-")
+}
+
+/// The contents of a `setup.cfg` file.
+// Note: we can't use the `ini` crate here, because it does not handle
+// values spanning several lines, like `install_requires`
+struct SetupCfg {
+    /// (section, key, value)
+    entries: Vec<(String, String, String)>,
+}
+
+impl SetupCfg {
+    fn parse(contents: &str) -> Self {
+        let mut entries: Vec<(String, String, String)> = vec![];
+        let mut section = String::new();
+        for line in contents.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with(';') {
+                continue;
+            }
+            if trimmed.starts_with('[') && trimmed.ends_with(']') {
+                section = trimmed[1..trimmed.len() - 1].trim().to_string();
+                continue;
+            }
+            let is_continuation = line.starts_with(char::is_whitespace);
+            if is_continuation {
+                if let Some((_, _, value)) = entries.last_mut() {
+                    value.push('\n');
+                    value.push_str(trimmed);
+                }
+                continue;
+            }
+            if let Some(pos) = trimmed.find(['=', ':']) {
+                let key = trimmed[..pos].trim().to_string();
+                let value = trimmed[pos + 1..].trim().to_string();
+                entries.push((section.to_string(), key, value));
+            }
+        }
+        SetupCfg { entries }
+    }
+
+    /// (key, value) entries of the given section
+    fn section(&self, section: &str) -> Vec<(&str, &str)> {
+        self.entries
+            .iter()
+            .filter(|(s, _, _)| s == section)
+            .map(|(_, k, v)| (k.as_str(), v.trim()))
+            .collect()
+    }
+
+    fn section_names(&self) -> Vec<&str> {
+        let mut res: Vec<&str> = vec![];
+        for (section, _, _) in &self.entries {
+            if !res.contains(&section.as_str()) {
+                res.push(section);
+            }
+        }
+        res
+    }
+
+    fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(s, k, _)| s == section && k == key)
+            .map(|(_, _, v)| v.trim())
+    }
+}
+
+/// Read the tasks defined in `setup.cfg`, either as `name = command`
+/// entries in the `[dmenv.tasks]` section, or in `[dmenv.tasks.<name>]`
+/// sections, with the same keys as in the configuration files. For
+/// instance:
+///
+/// ```ini
+/// [dmenv.tasks]
+/// lint = flake8 .
+///
+/// [dmenv.tasks.test]
+/// run =
+///   python -m pytest
+///   coverage report
+/// env =
+///   PYTHONWARNINGS=error
+/// depends = lint
+/// ```
+pub fn read_setup_cfg_tasks(project_path: &Path) -> Result<BTreeMap<String, TaskConfig>, Error> {
+    let mut res = BTreeMap::new();
+    let setup_cfg = match read_setup_cfg(project_path)? {
+        None => return Ok(res),
+        Some(s) => s,
+    };
+    let error = |details: String| Error::MalformedConfig {
+        path: project_path.join("setup.cfg"),
+        details,
+    };
+    let lines = |value: &str| -> Vec<String> {
+        value
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(String::from)
+            .collect()
+    };
+    for (name, value) in setup_cfg.section(SETUP_CFG_TASKS_SECTION) {
+        let task = TaskConfig {
+            description: None,
+            run: TaskCommands::Many(lines(value)),
+            env: BTreeMap::new(),
+            cwd: None,
+            depends: vec![],
+        };
+        res.insert(name.to_string(), task);
+    }
+    for section in setup_cfg.section_names() {
+        let prefix = format!("{}.", SETUP_CFG_TASKS_SECTION);
+        let name = match section.strip_prefix(&prefix) {
+            None => continue,
+            Some(name) => name,
+        };
+        let mut run = None;
+        let mut task = TaskConfig {
+            description: None,
+            run: TaskCommands::Many(vec![]),
+            env: BTreeMap::new(),
+            cwd: None,
+            depends: vec![],
+        };
+        for (key, value) in setup_cfg.section(section) {
+            match key {
+                "description" => task.description = Some(value.to_string()),
+                "run" => run = Some(TaskCommands::Many(lines(value))),
+                "cwd" => task.cwd = Some(value.to_string()),
+                "depends" => {
+                    task.depends = value
+                        .split(|c: char| c == ',' || c.is_whitespace())
+                        .filter(|n| !n.is_empty())
+                        .map(String::from)
+                        .collect()
+                }
+                "env" => {
+                    for line in lines(value) {
+                        let pos = line.find('=').ok_or_else(|| {
+                            error(format!("task '{}': expected NAME=value in env", name))
+                        })?;
+                        let var = line[..pos].trim().to_string();
+                        task.env.insert(var, line[pos + 1..].trim().to_string());
+                    }
+                }
+                other => {
+                    return Err(error(format!(
+                        "task '{}': unknown key '{}' in [{}]",
+                        name, other, section
+                    )))
+                }
+            }
+        }
+        task.run = run.ok_or_else(|| error(format!("task '{}': missing 'run' key", name)))?;
+        res.insert(name.to_string(), task);
+    }
+    Ok(res)
+}
+
+fn read_setup_cfg(project_path: &Path) -> Result<Option<SetupCfg>, Error> {
+    let setup_cfg_path = project_path.join("setup.cfg");
+    if !setup_cfg_path.exists() {
+        return Ok(None);
+    }
+    let contents =
+        std::fs::read_to_string(&setup_cfg_path).map_err(|e| new_read_error(e, &setup_cfg_path))?;
+    Ok(Some(SetupCfg::parse(&contents)))
+}
+
+fn has_project_table(pyproject_path: &Path) -> Result<bool, Error> {
+    let pyproject = read_pyproject(pyproject_path)?;
+    Ok(pyproject
+        .and_then(|p| p.get("project").cloned())
+        .is_some_and(|p| p.is_table()))
+}
+
+fn read_pyproject(pyproject_path: &Path) -> Result<Option<toml::Value>, Error> {
+    if !pyproject_path.exists() {
+        return Ok(None);
+    }
+    let contents =
+        std::fs::read_to_string(pyproject_path).map_err(|e| new_read_error(e, pyproject_path))?;
+    let value = toml::from_str(&contents).map_err(|e| Error::MalformedConfig {
+        path: pyproject_path.to_path_buf(),
+        details: e.to_string(),
+    })?;
+    Ok(Some(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(files: &[(&str, &str)]) -> Option<ProjectKind> {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        for (name, contents) in files {
+            std::fs::write(tmp_dir.path().join(name), contents).unwrap();
+        }
+        ProjectKind::detect(tmp_dir.path()).unwrap()
+    }
+
+    #[test]
+    fn test_setup_py() {
+        assert_eq!(
+            detect(&[("setup.py", "setup()\n")]),
+            Some(ProjectKind::SetupPy)
+        );
+    }
+
+    #[test]
+    fn test_pyproject() {
+        let pyproject = "[project]\nname = \"foo\"\nversion = \"0.1.0\"\n";
+        assert_eq!(
+            detect(&[("pyproject.toml", pyproject)]),
+            Some(ProjectKind::Pyproject)
+        );
+    }
+
+    #[test]
+    fn test_pyproject_without_project_table() {
+        let pyproject = "[tool.black]\nline-length = 100\n";
+        assert_eq!(detect(&[("pyproject.toml", pyproject)]), None);
+        assert_eq!(
+            detect(&[("pyproject.toml", pyproject), ("setup.py", "setup()\n")]),
+            Some(ProjectKind::SetupPy)
+        );
+    }
+
+    #[test]
+    fn test_python_requirement() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let tmp_path = tmp_dir.path();
+        std::fs::write(tmp_path.join("setup.py"), "setup()\n").unwrap();
+        assert_eq!(python_requirement(tmp_path).unwrap(), None);
+
+        let setup_cfg = "[metadata]\nname = foo\n\n[options]\npython_requires = >= 3.6\n";
+        std::fs::write(tmp_path.join("setup.cfg"), setup_cfg).unwrap();
+        assert_eq!(
+            python_requirement(tmp_path).unwrap(),
+            Some(">= 3.6".to_string())
+        );
+
+        let pyproject = "[project]\nname = \"foo\"\nrequires-python = \">=3.8\"\n";
+        std::fs::write(tmp_path.join("pyproject.toml"), pyproject).unwrap();
+        assert_eq!(
+            python_requirement(tmp_path).unwrap(),
+            Some(">=3.8".to_string())
+        );
+    }
+
+    #[test]
+    fn test_read_metadata_from_setup_cfg() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let tmp_path = tmp_dir.path();
+        std::fs::write(tmp_path.join("setup.py"), "setup()\n").unwrap();
+        let setup_cfg = r#"
+[metadata]
+name = foo
+
+[options]
+python_requires = >= 3.6
+install_requires =
+  # a comment
+  attrs >= 19.0
+  Core_Lib
+
+[options.extras_require]
+dev =
+  pytest
+"#;
+        std::fs::write(tmp_path.join("setup.cfg"), setup_cfg).unwrap();
+        let metadata = read_metadata(tmp_path).unwrap();
+        assert_eq!(metadata.name, Some("foo".to_string()));
+        assert_eq!(metadata.required_names(), &["attrs", "core-lib", "pytest"]);
+        assert_eq!(
+            python_requirement(tmp_path).unwrap(),
+            Some(">= 3.6".to_string())
+        );
+    }
+
+    #[test]
+    fn test_read_metadata_from_pyproject() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let pyproject = r#"
+[project]
+name = "foo"
+dependencies = ["attrs>=19.0", "core.lib[extra]"]
+
+[project.optional-dependencies]
+dev = ["pytest"]
+"#;
+        std::fs::write(tmp_dir.path().join("pyproject.toml"), pyproject).unwrap();
+        let metadata = read_metadata(tmp_dir.path()).unwrap();
+        assert_eq!(metadata.name, Some("foo".to_string()));
+        assert_eq!(metadata.required_names(), &["attrs", "core-lib", "pytest"]);
+    }
+
+    #[test]
+    fn test_console_scripts() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let tmp_path = tmp_dir.path();
+        std::fs::write(tmp_path.join("setup.py"), "setup()\n").unwrap();
+        let setup_cfg = "[options.entry_points]\nconsole_scripts =\n  foo = foo.cli:main\n  foo-admin=foo.admin:Admin.run\n";
+        std::fs::write(tmp_path.join("setup.cfg"), setup_cfg).unwrap();
+        assert_eq!(
+            console_scripts(tmp_path).unwrap(),
+            &[
+                ("foo".to_string(), "foo.cli:main".to_string()),
+                ("foo-admin".to_string(), "foo.admin:Admin.run".to_string()),
+            ]
+        );
+
+        let pyproject = "[project]\nname = \"foo\"\n\n[project.scripts]\nfoo = \"foo.cli:main\"\n";
+        std::fs::write(tmp_path.join("pyproject.toml"), pyproject).unwrap();
+        assert_eq!(
+            console_scripts(tmp_path).unwrap(),
+            &[("foo".to_string(), "foo.cli:main".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_build_requirements() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let tmp_path = tmp_dir.path();
+        std::fs::write(tmp_path.join("setup.py"), "setup()\n").unwrap();
+        assert_eq!(
+            build_requirements(tmp_path).unwrap(),
+            &["setuptools", "wheel"]
+        );
+
+        let pyproject = "[build-system]\nrequires = [\"hatchling>=1.0\"]\n";
+        std::fs::write(tmp_path.join("pyproject.toml"), pyproject).unwrap();
+        assert_eq!(build_requirements(tmp_path).unwrap(), &["hatchling>=1.0"]);
+    }
+
+    #[test]
+    fn test_read_setup_cfg_tasks() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let setup_cfg = r#"
+[metadata]
+name = foo
+
+[dmenv.tasks]
+lint = flake8 .
+check =
+  black --check .
+  mypy foo
+
+[dmenv.tasks.test]
+description = Run the tests
+run = python -m pytest
+env =
+  PYTHONWARNINGS=error
+  DB_URL=sqlite://
+depends = lint, check
+"#;
+        std::fs::write(tmp_dir.path().join("setup.cfg"), setup_cfg).unwrap();
+        let tasks = read_setup_cfg_tasks(tmp_dir.path()).unwrap();
+        assert_eq!(tasks.keys().collect::<Vec<_>>(), &["check", "lint", "test"]);
+        assert_eq!(tasks["check"].run.lines(), &["black --check .", "mypy foo"]);
+        let test = &tasks["test"];
+        assert_eq!(test.description, Some("Run the tests".to_string()));
+        assert_eq!(test.run.lines(), &["python -m pytest"]);
+        assert_eq!(test.env["DB_URL"], "sqlite://");
+        assert_eq!(test.depends, &["lint", "check"]);
+    }
+
+    #[test]
+    fn test_setup_cfg_task_without_run() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let setup_cfg = "[dmenv.tasks.test]\ncwd = tests\n";
+        std::fs::write(tmp_dir.path().join("setup.cfg"), setup_cfg).unwrap();
+        let error = read_setup_cfg_tasks(tmp_dir.path()).unwrap_err();
+        assert!(error.to_string().contains("missing 'run'"));
+    }
+
+    #[test]
+    fn test_nothing() {
+        assert_eq!(detect(&[]), None);
+    }
+}
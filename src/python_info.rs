@@ -1,28 +1,70 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
 use crate::error::*;
-use std::path::PathBuf;
 
 /// Represent output of the info.py script
 /// This allows dmenv to know details about
 /// the Python intrepreter it is using.
-#[derive(Debug)]
+// Note: field names match the keys of the JSON document
+// printed by info.py
+#[derive(Debug, Clone, Deserialize)]
 pub struct PythonInfo {
+    /// The binary used to run info.py
+    #[serde(skip)]
     pub binary: PathBuf,
+    /// `sys.executable` - may differ from `binary`, for instance
+    /// when `binary` is a pyenv shim
+    pub executable: PathBuf,
+    /// Something like `3.7.4`
     pub version: String,
+    /// `sys.version_info`, like `(3, 7, 4, "final", 0)`
+    pub version_info: (u64, u64, u64, String, u64),
+    /// Something like `cpython` or `pypy`
+    pub implementation_name: String,
+    /// Something like `CPython` or `PyPy`
+    pub platform_python_implementation: String,
+    /// `platform.system()`: `Linux`, `Darwin`, `Windows` ...
+    #[serde(rename = "platform_system")]
     pub platform: String,
+    pub platform_machine: String,
+    pub platform_release: String,
+    /// `sys.platform`: `linux`, `darwin`, `win32` ...
+    pub sys_platform: String,
+    /// `os.name`: `posix` or `nt`
+    pub os_name: String,
+    /// Something like `cp37`
+    pub python_tag: String,
+    /// Something like `cpython-37m-x86_64-linux-gnu`. Not set on Windows
+    pub soabi: Option<String>,
+    pub prefix: PathBuf,
+    pub base_prefix: PathBuf,
+    /// Whether the `venv` module is available - it is missing from Python2,
+    /// and sometimes packaged separately, like in Debian's `python3-venv`
+    pub has_venv: bool,
+    pub has_ensurepip: bool,
+    /// Not set if pip is not installed
+    pub pip_version: Option<String>,
 }
 
 impl PythonInfo {
-    #[allow(clippy::new_ret_no_self)]
-    pub fn new(python: &Option<String>) -> Result<Self, Error> {
-        let binary = get_python_binary(&python)?;
+    /// Run the info.py script with the given binary.
+    //
+    // Note: see `interpreters::find_python()` for how the binary is chosen
+    pub fn from_binary(binary: &Path) -> Result<Self, Error> {
         let info_script = include_str!("info.py");
 
-        let command = std::process::Command::new(&binary)
-            .args(&["-c", info_script])
+        let command = std::process::Command::new(binary)
+            .args(["-c", info_script])
             .output();
         let command = command.map_err(|e| Error::GetProcessOutputError { io_error: e })?;
         if !command.status.success() {
-            let return_code = command.status.code().unwrap();
+            // Note: candidates found by `interpreters::discover()` may get
+            // killed by a signal, in which case there is no exit code
+            let return_code = match command.status.code() {
+                Some(code) => code,
+                None => return Err(new_process_error(binary, command.status)),
+            };
             return Err(Error::RunInfoPyError {
                 message: format!(
                     "command returned with exit code: {}\n{}",
@@ -32,38 +74,99 @@ impl PythonInfo {
             });
         }
         let info_out = String::from_utf8_lossy(&command.stdout);
-        let lines: Vec<_> = info_out.split_terminator('\n').collect();
-        let expected_lines = 2; // Keep this in sync with src/info.py
-        if lines.len() != expected_lines {
-            return Err(Error::RunInfoPyError {
-                message: format!(
-                    "could not parse output:\n{}\n(expected exactly {} lines)",
-                    info_out, expected_lines,
-                ),
-            });
+        PythonInfo::parse(binary, &info_out)
+    }
+
+    fn parse(binary: &Path, info_out: &str) -> Result<Self, Error> {
+        let mut res: PythonInfo =
+            serde_json::from_str(info_out).map_err(|e| Error::RunInfoPyError {
+                message: format!("could not parse output:\n{}\n({})", info_out, e),
+            })?;
+        res.binary = binary.to_path_buf();
+        Ok(res)
+    }
+
+    /// Whether the interpreter runs from inside a virtualenv
+    pub fn in_venv(&self) -> bool {
+        self.prefix != self.base_prefix
+    }
+
+    /// Name of the virtualenv sub-directory for this interpreter.
+    // Note: CPython is the default, so its name is omitted. This keeps
+    // the paths of existing virtualenvs unchanged
+    pub fn venv_dir_name(&self) -> String {
+        if self.implementation_name == "cpython" {
+            self.version.to_string()
+        } else {
+            format!("{}-{}", self.implementation_name, self.version)
         }
-        let version = lines[0].trim().to_string();
-        let platform = lines[1].trim().to_string();
-        Ok(PythonInfo {
-            binary,
-            version,
-            platform,
-        })
     }
 }
 
-/// Look for a suitable Python binary in PATH
-// Note: doses not get called if `dmenv` was invoked with an explicit `--python`
-// option.
-fn get_python_binary(requested_python: &Option<String>) -> Result<PathBuf, Error> {
-    if let Some(python) = requested_python {
-        return Ok(PathBuf::from(python));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINUX_INFO: &str = r#"{
+        "executable": "/usr/bin/python3.7",
+        "version": "3.7.4",
+        "version_info": [3, 7, 4, "final", 0],
+        "implementation_name": "cpython",
+        "platform_python_implementation": "CPython",
+        "platform_system": "Linux",
+        "platform_machine": "x86_64",
+        "platform_release": "5.3.0",
+        "sys_platform": "linux",
+        "os_name": "posix",
+        "python_tag": "cp37",
+        "soabi": "cpython-37m-x86_64-linux-gnu",
+        "prefix": "/usr",
+        "base_prefix": "/usr",
+        "has_venv": true,
+        "has_ensurepip": false,
+        "pip_version": null
+    }"#;
+
+    #[test]
+    fn test_parse() {
+        let info = PythonInfo::parse(Path::new("python3"), LINUX_INFO).unwrap();
+        assert_eq!(info.binary, PathBuf::from("python3"));
+        assert_eq!(info.executable, PathBuf::from("/usr/bin/python3.7"));
+        assert_eq!(info.version_info, (3, 7, 4, "final".to_string(), 0));
+        assert_eq!(info.platform, "Linux");
+        assert_eq!(info.sys_platform, "linux");
+        assert!(!info.has_ensurepip);
+        assert_eq!(info.pip_version, None);
+        assert!(!info.in_venv());
+        assert_eq!(info.venv_dir_name(), "3.7.4");
+    }
+
+    #[test]
+    fn test_venv_dir_name_for_pypy() {
+        let mut info = PythonInfo::parse(Path::new("pypy3"), LINUX_INFO).unwrap();
+        info.implementation_name = "pypy".to_string();
+        assert_eq!(info.venv_dir_name(), "pypy-3.7.4");
     }
 
-    if let Ok(python3) = which::which("python3") {
-        return Ok(python3);
+    #[test]
+    fn test_parse_error() {
+        let err = PythonInfo::parse(Path::new("python3"), "3.7.4\nLinux\n").unwrap_err();
+        assert!(err.to_string().contains("could not parse output"));
     }
 
-    which::which("python")
-        .map_err(|_| new_error("Neither `python3` nor `python` found in PATH".to_string()))
+    #[cfg(unix)]
+    #[test]
+    fn test_interpreter_killed_by_a_signal() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let binary = tmp_dir.path().join("python3");
+        std::fs::write(&binary, "#!/bin/sh\nkill -9 $$\n").unwrap();
+        std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        match PythonInfo::from_binary(&binary) {
+            Err(Error::ProcessFailed { signal, .. }) => assert_eq!(signal, Some(9)),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
 }
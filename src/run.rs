@@ -1,19 +1,36 @@
+use std::ffi::OsString;
 use std::path::{Path, PathBuf};
 
 use colored::*;
 
+use crate::activate::Activation;
 use crate::error::*;
 #[cfg(unix)]
 use crate::execv::execv;
+#[cfg(unix)]
+use crate::signals::SignalForwarder;
 #[cfg(windows)]
 use crate::win_job;
 
 use crate::paths::SCRIPTS_SUBDIR;
+use crate::project;
+use crate::ui::{print_progress, progress_on_stderr};
 
-#[derive(Debug)]
+/// Changes to the environment of a process, `None` meaning
+/// the variable must be removed
+pub type EnvVars = Vec<(String, Option<OsString>)>;
+
+/// Run binaries from the virtualenv, with the virtualenv activated
+#[derive(Debug, Clone)]
 pub struct VenvRunner {
     project_path: PathBuf,
     venv_path: PathBuf,
+    /// Where the commands are run, see `in_dir()`
+    working_path: PathBuf,
+    /// Set after the variables activating the virtualenv, see `with_env()`
+    extra_env: Vec<(String, String)>,
+    /// See `forwarding_signals()`
+    forward_signals: bool,
 }
 
 #[derive(Debug)]
@@ -37,12 +54,12 @@ impl RunnableCommand {
     }
 
     pub fn print_self(&self) {
-        println!(
+        print_progress(&format!(
             "{} {} {}",
             "$".blue(),
             self.binary_path.display(),
             self.args.join(" ")
-        );
+        ));
     }
 }
 
@@ -51,7 +68,62 @@ impl VenvRunner {
         VenvRunner {
             project_path: project_path.to_path_buf(),
             venv_path: venv_path.to_path_buf(),
+            working_path: project_path.to_path_buf(),
+            extra_env: vec![],
+            forward_signals: false,
+        }
+    }
+
+    /// Return a new runner that also sets the given variables, for instance
+    /// the ones read from `.env` files
+    pub fn with_env(&self, extra_env: Vec<(String, String)>) -> Self {
+        let mut res = self.clone();
+        res.extra_env.extend(extra_env);
+        res
+    }
+
+    /// Return a new runner that runs the commands in the given directory,
+    /// instead of the project directory
+    pub fn in_dir(&self, working_path: &Path) -> Self {
+        VenvRunner {
+            working_path: working_path.to_path_buf(),
+            ..self.clone()
+        }
+    }
+
+    /// Return a new runner that forwards SIGINT and SIGTERM to the
+    /// commands it runs, on Unix
+    // Note: the signal handlers are global to the process, so this
+    // must not be used when several commands may run at once
+    pub fn forwarding_signals(&self) -> Self {
+        VenvRunner {
+            forward_signals: true,
+            ..self.clone()
+        }
+    }
+
+    pub fn activation(&self) -> Activation {
+        let prompt = self
+            .project_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "dmenv".to_string());
+        Activation {
+            venv_path: self.venv_path.to_path_buf(),
+            bin_path: self.binaries_path(),
+            prompt,
+        }
+    }
+
+    /// Changes to the environment of the processes we spawn, so that
+    /// they see the virtualenv as activated. This way, subprocesses
+    /// calling `python` use the virtualenv too
+    pub fn env_vars(&self) -> Result<EnvVars, Error> {
+        let mut res = self.activation().apply(|name| std::env::var_os(name))?;
+        for (name, value) in &self.extra_env {
+            res.push((name.to_string(), Some(value.into())));
         }
+        Ok(res)
     }
 
     pub fn run_and_die<T: AsRef<str>>(&self, cmd: &[T]) -> Result<(), Error> {
@@ -72,7 +144,14 @@ impl VenvRunner {
             let arg0 = arg0
                 .to_str()
                 .ok_or_else(|| new_error(format!("Could not convert {:?} to string", arg0)))?;
-            cmd.insert(0, &arg0);
+            cmd.insert(0, arg0);
+            // Note: execv() keeps the environment of the current process
+            for (name, value) in self.env_vars()? {
+                match value {
+                    Some(value) => std::env::set_var(name, value),
+                    None => std::env::remove_var(name),
+                }
+            }
             execv(arg0, &cmd)
         }
     }
@@ -80,27 +159,72 @@ impl VenvRunner {
     pub fn run<T: AsRef<str>>(&self, cmd: &[T]) -> Result<(), Error> {
         let runnable = self.get_runnable(cmd)?;
         runnable.print_self();
-        run(&self.project_path, &runnable.binary_path, &runnable.args)
+        let env_vars = self.env_vars()?;
+        run_with_env(
+            &self.working_path,
+            &runnable.binary_path,
+            &runnable.args,
+            &env_vars,
+            self.forward_signals,
+        )
     }
 
     pub fn get_output<T: AsRef<str>>(&self, cmd: &[T]) -> Result<String, Error> {
         let runnable = self.get_runnable(cmd)?;
-        get_output(&self.project_path, &runnable.binary_path, &runnable.args)
+        let env_vars = self.env_vars()?;
+        get_output(
+            &self.working_path,
+            &runnable.binary_path,
+            &runnable.args,
+            &env_vars,
+        )
     }
 
     fn get_runnable<T: AsRef<str>>(&self, cmd: &[T]) -> Result<RunnableCommand, Error> {
-        let first_arg = &cmd[0].as_ref();
+        let current_dir =
+            std::env::current_dir().map_err(|e| Error::NoWorkingDirectory { io_error: e })?;
+        self.get_runnable_from(cmd, &current_dir)
+    }
+
+    /// Resolve the binary to run, in this order:
+    ///  * `.py` scripts, relative to `current_dir`, then to the project
+    ///  * binaries from the virtualenv
+    ///  * console scripts declared by the project, so that they can be
+    ///    used even if the project is not installed yet
+    fn get_runnable_from<T: AsRef<str>>(
+        &self,
+        cmd: &[T],
+        current_dir: &Path,
+    ) -> Result<RunnableCommand, Error> {
+        let first_arg = cmd[0].as_ref();
+        let args: Vec<&str> = cmd[1..].iter().map(AsRef::as_ref).collect();
+        let python_binary = self.get_binary_path("python");
         if first_arg.ends_with(".py") {
-            let script_path = self.project_path.join(first_arg);
-            if script_path.exists() {
-                let python_binary = self.get_binary_path("python");
-                return RunnableCommand::new(&python_binary, &cmd);
+            let candidates = [
+                current_dir.join(first_arg),
+                self.project_path.join(first_arg),
+            ];
+            if let Some(script_path) = candidates.iter().find(|p| p.is_file()) {
+                // Note: use an absolute path, as the script may not
+                // be run from `current_dir`
+                let mut python_args = vec![script_path.to_string_lossy().to_string()];
+                python_args.extend(args.iter().map(|a| a.to_string()));
+                return RunnableCommand::new(&python_binary, &python_args);
             }
         }
 
-        let binary_path = self.get_binary_path(&cmd[0].as_ref());
-        let args = &cmd[1..];
-        RunnableCommand::new(&binary_path, args)
+        let binary_path = self.get_binary_path(first_arg);
+        if !binary_path.exists() {
+            let console_scripts = project::console_scripts(&self.project_path)?;
+            let reference = console_scripts.iter().find(|(n, _)| n == first_arg);
+            if let Some((name, reference)) = reference {
+                let code = entry_point_code(name, reference, &self.project_path)?;
+                let mut python_args = vec!["-c".to_string(), code];
+                python_args.extend(args.iter().map(|a| a.to_string()));
+                return RunnableCommand::new(&python_binary, &python_args);
+            }
+        }
+        RunnableCommand::new(&binary_path, &args)
     }
 
     pub fn binaries_path(&self) -> PathBuf {
@@ -121,19 +245,83 @@ impl VenvRunner {
     }
 }
 
+/// Python code calling the object an entry point refers to, like
+/// `foo.cli:main`, the way the scripts generated by pip do. The project
+/// directory, and its `src` sub-directory if any, are added to `sys.path`
+fn entry_point_code(name: &str, reference: &str, project_path: &Path) -> Result<String, Error> {
+    // Note: drop the extras, as in `foo.cli:main [cli]`
+    let reference = reference.split('[').next().unwrap_or_default().trim();
+    let (module, attributes) = match reference.find(':') {
+        Some(pos) => (&reference[..pos], &reference[pos + 1..]),
+        None => {
+            return Err(new_error(format!(
+                "invalid entry point for {}: '{}' (should look like 'module:function')",
+                name, reference
+            )))
+        }
+    };
+    let mut paths = vec![project_path.to_path_buf()];
+    let src_path = project_path.join("src");
+    if src_path.is_dir() {
+        paths.push(src_path);
+    }
+    let paths: Vec<_> = paths
+        .iter()
+        .map(|p| python_literal(&p.to_string_lossy()))
+        .collect();
+    let attributes: Vec<_> = attributes.split('.').map(python_literal).collect();
+    Ok(format!(
+        "import functools, importlib, sys; sys.path[:0] = [{}]; sys.argv[0] = {}; sys.exit(functools.reduce(getattr, [{}], importlib.import_module({}))())",
+        paths.join(", "),
+        python_literal(name),
+        attributes.join(", "),
+        python_literal(module.trim())
+    ))
+}
+
+fn python_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
 pub fn run<T: AsRef<str>>(
     working_path: &Path,
     binary_path: &Path,
     args: &[T],
+) -> Result<(), Error> {
+    run_with_env(working_path, binary_path, args, &[], false)
+}
+
+fn run_with_env<T: AsRef<str>>(
+    working_path: &Path,
+    binary_path: &Path,
+    args: &[T],
+    env_vars: &[(String, Option<OsString>)],
+    forward_signals: bool,
 ) -> Result<(), Error> {
     let args: Vec<&str> = args.iter().map(AsRef::as_ref).collect();
-    let command = std::process::Command::new(binary_path)
-        .args(args)
-        .current_dir(working_path)
-        .status();
-    let command = command.map_err(|e| Error::WaitProcessError { io_error: e })?;
-    if !command.success() {
-        return Err(new_error("command failed".to_string()));
+    let mut command = std::process::Command::new(binary_path);
+    command.args(args).current_dir(working_path);
+    set_env_vars(&mut command, env_vars);
+    if progress_on_stderr() {
+        command.stdout(std::process::Stdio::from(std::io::stderr()));
+    }
+    let mut child = command.spawn().map_err(|e| Error::StartProcessError {
+        message: format!("{}: {}", binary_path.display(), e),
+    })?;
+    #[cfg(unix)]
+    let _forwarder = if forward_signals {
+        Some(SignalForwarder::new(child.id()))
+    } else {
+        None
+    };
+    // Note: there are no signals to forward on Windows
+    #[cfg(windows)]
+    let _ = forward_signals;
+    let status = child
+        .wait()
+        .map_err(|e| Error::WaitProcessError { io_error: e })?;
+    if !status.success() {
+        return Err(new_process_error(binary_path, status));
     }
     Ok(())
 }
@@ -142,13 +330,14 @@ fn get_output<T: AsRef<str>>(
     working_path: &Path,
     binary_path: &Path,
     args: &[T],
+    env_vars: &[(String, Option<OsString>)],
 ) -> Result<String, Error> {
     let args: Vec<&str> = args.iter().map(AsRef::as_ref).collect();
     let cmd_str = format!("{} {}", binary_path.display(), args.join(" "));
-    let command = std::process::Command::new(binary_path)
-        .args(args)
-        .current_dir(working_path)
-        .output();
+    let mut command = std::process::Command::new(binary_path);
+    command.args(args).current_dir(working_path);
+    set_env_vars(&mut command, env_vars);
+    let command = command.output();
 
     let command = command.map_err(|e| Error::GetProcessOutputError { io_error: e })?;
     if !command.status.success() {
@@ -161,6 +350,15 @@ fn get_output<T: AsRef<str>>(
     Ok(String::from_utf8_lossy(&command.stdout).to_string())
 }
 
+fn set_env_vars(command: &mut std::process::Command, env_vars: &[(String, Option<OsString>)]) {
+    for (name, value) in env_vars {
+        match value {
+            Some(value) => command.env(name, value),
+            None => command.env_remove(name),
+        };
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -229,10 +427,61 @@ mod tests {
         let fs = FileSystem::new();
         fs.add_script_in_project("foo.py");
         let venv_runner = VenvRunner::new(&fs.project, &fs.venv);
-        let runnable = venv_runner.get_runnable(&["foo.py"]).unwrap();
+        let runnable = venv_runner
+            .get_runnable_from(&["foo.py", "--verbose"], &fs.venv)
+            .unwrap();
         let expected_binary = venv_runner.get_binary_path("python");
         runnable.assert_binary(&expected_binary);
-        runnable.assert_args(&["foo.py"]);
+        let script_path = fs.project.join("foo.py");
+        runnable.assert_args(&[&script_path.to_string_lossy(), "--verbose"]);
+    }
+
+    #[test]
+    fn test_run_script_in_current_dir() {
+        let fs = FileSystem::new();
+        fs.add_script_in_project("foo.py");
+        let sub_dir = fs.project.join("scripts");
+        std::fs::create_dir(&sub_dir).unwrap();
+        std::fs::write(sub_dir.join("foo.py"), "").unwrap();
+        let venv_runner = VenvRunner::new(&fs.project, &fs.venv);
+        let runnable = venv_runner
+            .get_runnable_from(&["foo.py"], &sub_dir)
+            .unwrap();
+        runnable.assert_args(&[&sub_dir.join("foo.py").to_string_lossy()]);
+    }
+
+    #[test]
+    fn test_run_console_script_from_project() {
+        let fs = FileSystem::new();
+        fs.add_script_in_project("setup.py");
+        let setup_cfg = "[options.entry_points]\nconsole_scripts = foo = foo.cli:main\n";
+        std::fs::write(fs.project.join("setup.cfg"), setup_cfg).unwrap();
+        let venv_runner = VenvRunner::new(&fs.project, &fs.venv);
+        let runnable = venv_runner
+            .get_runnable_from(&["foo", "--help"], &fs.project)
+            .unwrap();
+        runnable.assert_binary(&venv_runner.get_binary_path("python"));
+        assert_eq!(runnable.args[0], "-c");
+        assert!(runnable.args[1].contains("importlib.import_module('foo.cli')"));
+        assert_eq!(runnable.args[2], "--help");
+
+        // Note: once the project is installed, use the generated script
+        let foo_script = fs.add_binary_in_venv("foo");
+        let runnable = venv_runner
+            .get_runnable_from(&["foo"], &fs.project)
+            .unwrap();
+        runnable.assert_binary(&foo_script);
+    }
+
+    #[test]
+    fn test_entry_point_code() {
+        let code =
+            entry_point_code("foo", "foo.cli:Main.run [cli]", Path::new("/work/it's")).unwrap();
+        assert_eq!(
+            code,
+            "import functools, importlib, sys; sys.path[:0] = ['/work/it\\'s']; sys.argv[0] = 'foo'; sys.exit(functools.reduce(getattr, ['Main', 'run'], importlib.import_module('foo.cli'))())"
+        );
+        entry_point_code("foo", "foo.cli", Path::new("/work")).unwrap_err();
     }
 
     #[test]
@@ -1,4 +1,10 @@
-use crate::cli::syntax::Command;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use crate::cli::syntax::{Command, SubCommand};
+use crate::config::{self, ConfigFile, ProfileConfig, TaskConfig};
+use crate::error::*;
+use crate::paths::{self, DEV_LOCK_FILENAME, PROD_LOCK_FILENAME};
 
 #[derive(Debug, Clone)]
 /// Represent variables that change behavior of
@@ -6,8 +12,75 @@ use crate::cli::syntax::Command;
 pub struct Settings {
     pub venv_from_stdlib: bool,
     pub venv_outside_project: bool,
-    pub production: bool,
+    pub python: Option<String>,
+    /// Name of the profile in use, see `Settings::profile()`
+    pub profile: String,
     pub system_site_packages: bool,
+    /// Re-create the virtualenv when its base interpreter has changed,
+    /// instead of failing
+    pub recreate_if_stale: bool,
+    /// Sync the virtualenv when the lock or the project files have changed
+    /// since the last install, instead of just printing a warning
+    pub auto_sync: bool,
+    pub dev_extra: String,
+    pub prod_extra: String,
+    pub dev_lock: String,
+    pub prod_lock: String,
+    pub index_url: Option<String>,
+    pub extra_index_urls: Vec<String>,
+    /// Local directories or URLs of pages with links to archives
+    pub find_links: Vec<String>,
+    pub trusted_hosts: Vec<String>,
+    /// Paths of constraints files, relative to the project
+    pub constraints: Vec<String>,
+    /// Write the index options at the top of the lock, so that
+    /// `pip install --requirement` uses them too
+    pub record_index_options: bool,
+    /// Directory populated by `dmenv download`, relative to the project
+    pub wheelhouse: String,
+    /// Install packages from the wheelhouse only, without using any index
+    pub offline: bool,
+    pub profiles: BTreeMap<String, ProfileConfig>,
+    /// Tasks defined in the configuration files, see `dmenv task`
+    pub tasks: BTreeMap<String, TaskConfig>,
+    /// Where each setting that is not set to its default value comes from
+    pub sources: HashMap<&'static str, Source>,
+}
+
+/// What to install, and where, see the `--profile` option
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    /// Extras to install along with the project
+    pub extras: Vec<String>,
+    /// File name of the lock, relative to the project path
+    pub lock: String,
+    /// Sub-directory of the virtualenv, before the Python version
+    pub venv_subdir: String,
+}
+
+pub const DEV_PROFILE: &str = "dev";
+pub const PROD_PROFILE: &str = "prod";
+pub const DEFAULT_WHEELHOUSE: &str = "wheelhouse";
+
+/// Where the value of a setting comes from, see `Settings::load()`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    Default,
+    File(PathBuf),
+    Environment(&'static str),
+    CommandLine(&'static str),
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::File(path) => write!(f, "{}", path.display()),
+            Source::Environment(var) => write!(f, "environment ({})", var),
+            Source::CommandLine(flag) => write!(f, "command line ({})", flag),
+        }
+    }
 }
 
 impl Default for Settings {
@@ -15,30 +88,688 @@ impl Default for Settings {
         Settings {
             venv_from_stdlib: true,
             venv_outside_project: false,
-            production: false,
+            python: None,
+            profile: DEV_PROFILE.to_string(),
             system_site_packages: false,
+            recreate_if_stale: false,
+            auto_sync: false,
+            dev_extra: "dev".to_string(),
+            prod_extra: "prod".to_string(),
+            dev_lock: DEV_LOCK_FILENAME.to_string(),
+            prod_lock: PROD_LOCK_FILENAME.to_string(),
+            index_url: None,
+            extra_index_urls: vec![],
+            find_links: vec![],
+            trusted_hosts: vec![],
+            constraints: vec![],
+            record_index_options: false,
+            wheelhouse: DEFAULT_WHEELHOUSE.to_string(),
+            offline: false,
+            profiles: BTreeMap::new(),
+            tasks: BTreeMap::new(),
+            sources: HashMap::new(),
         }
     }
 }
 
 impl Settings {
-    /// Construct a new Settings instance using
-    /// options from the command line (the `cmd` parameter)
-    /// and environment variables.
+    /// Construct a new Settings instance by applying, in this order:
+    ///  * the built-in defaults
+    ///  * the user configuration file
+    ///  * the project configuration file (`dmenv.toml`, or the
+    ///    `[tool.dmenv]` section of `pyproject.toml`)
+    ///  * environment variables
+    ///  * options from the command line (the `cmd` parameter)
     //
-    // Note:  Called in `main()` and in test heplers.
-    pub fn from_shell(cmd: &Command) -> Settings {
-        let mut res = Settings {
-            production: cmd.production,
-            system_site_packages: cmd.system_site_packages,
-            ..Default::default()
+    // Note:  Called in `get_context()`, once the project path is known
+    pub fn load(cmd: &Command, project_path: &Path) -> Result<Settings, Error> {
+        let mut res = Settings::default();
+        let user_config_path = paths::get_user_config_path()?;
+        if let Some(config) = config::read_user_config(&user_config_path)? {
+            res.apply_config(config, &user_config_path);
+        }
+        if let Some((path, config)) = config::read_project_config(project_path)? {
+            res.apply_config(config, &path);
+        }
+        res.apply_env(|name| std::env::var(name).ok());
+        res.apply_cmd(cmd);
+        res.check_profile()?;
+        Ok(res)
+    }
+
+    pub fn is_production(&self) -> bool {
+        self.profile == PROD_PROFILE
+    }
+
+    /// Resolve the profile in use.
+    //
+    // Note: the `dev` and `prod` profiles always exist, and use the
+    // `dev_extra`, `dev_lock`, `prod_extra` and `prod_lock` settings
+    // unless they are overridden in a `[profiles]` section
+    pub fn profile(&self) -> Profile {
+        let name = &self.profile;
+        let (extra, lock) = match name.as_str() {
+            DEV_PROFILE => (self.dev_extra.to_string(), self.dev_lock.to_string()),
+            PROD_PROFILE => (self.prod_extra.to_string(), self.prod_lock.to_string()),
+            _ => (name.to_string(), format!("{}.lock", name)),
         };
-        if std::env::var("DMENV_NO_VENV_STDLIB").is_ok() {
-            res.venv_from_stdlib = false;
+        let config = self.profiles.get(name).cloned().unwrap_or_default();
+        Profile {
+            name: name.to_string(),
+            extras: config.extras.unwrap_or_else(|| vec![extra]),
+            lock: config.lock.unwrap_or(lock),
+            venv_subdir: config.venv_subdir.unwrap_or_else(|| name.to_string()),
         }
-        if std::env::var("DMENV_VENV_OUTSIDE_PROJECT").is_ok() {
-            res.venv_outside_project = true;
+    }
+
+    /// Names of all the known profiles
+    pub fn profile_names(&self) -> Vec<String> {
+        let mut res = vec![DEV_PROFILE.to_string(), PROD_PROFILE.to_string()];
+        for name in self.profiles.keys() {
+            if !res.contains(name) {
+                res.push(name.to_string());
+            }
         }
         res
     }
+
+    fn check_profile(&self) -> Result<(), Error> {
+        if self.profile_names().contains(&self.profile) {
+            return Ok(());
+        }
+        Err(Error::UnknownProfile {
+            name: self.profile.to_string(),
+            known: self.profile_names(),
+        })
+    }
+
+    /// Options to pass to every `pip` command that installs or
+    /// downloads packages
+    // Note: in offline mode, packages only come from the wheelhouse
+    pub fn pip_options(&self) -> Vec<String> {
+        if !self.offline {
+            return self.download_options();
+        }
+        let mut res = vec![
+            "--no-index".to_string(),
+            "--find-links".to_string(),
+            self.wheelhouse.to_string(),
+        ];
+        res.extend(self.constraint_options());
+        res
+    }
+
+    /// Same as `pip_options()`, but always using the indexes, see `dmenv download`
+    pub fn download_options(&self) -> Vec<String> {
+        let mut res = vec![];
+        for (option, value) in self.index_options() {
+            res.push(option.to_string());
+            res.push(value.to_string());
+        }
+        res.extend(self.constraint_options());
+        res
+    }
+
+    fn constraint_options(&self) -> Vec<String> {
+        let mut res = vec![];
+        for path in &self.constraints {
+            res.push("--constraint".to_string());
+            res.push(path.to_string());
+        }
+        res
+    }
+
+    /// Lines to write at the top of the lock, see `record_index_options`
+    pub fn lock_options(&self) -> Vec<String> {
+        self.index_options()
+            .into_iter()
+            .map(|(option, value)| format!("{} {}", option, value))
+            .collect()
+    }
+
+    // Note: constraints are not part of these, as they are of no use
+    // once the lock is generated
+    fn index_options(&self) -> Vec<(&'static str, &str)> {
+        let mut res = vec![];
+        if let Some(index_url) = &self.index_url {
+            res.push(("--index-url", index_url.as_str()));
+        }
+        for url in &self.extra_index_urls {
+            res.push(("--extra-index-url", url.as_str()));
+        }
+        for location in &self.find_links {
+            res.push(("--find-links", location.as_str()));
+        }
+        for host in &self.trusted_hosts {
+            res.push(("--trusted-host", host.as_str()));
+        }
+        res
+    }
+
+    pub fn source(&self, key: &str) -> Source {
+        self.sources.get(key).cloned().unwrap_or(Source::Default)
+    }
+
+    /// Return the name and value of every setting, for `dmenv show:config`
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        let optional = |x: &Option<String>| x.clone().unwrap_or_default();
+        vec![
+            (
+                "venv_outside_project",
+                self.venv_outside_project.to_string(),
+            ),
+            ("venv_from_stdlib", self.venv_from_stdlib.to_string()),
+            ("python", optional(&self.python)),
+            ("profile", self.profile.to_string()),
+            (
+                "system_site_packages",
+                self.system_site_packages.to_string(),
+            ),
+            ("recreate_if_stale", self.recreate_if_stale.to_string()),
+            ("auto_sync", self.auto_sync.to_string()),
+            ("dev_extra", self.dev_extra.to_string()),
+            ("prod_extra", self.prod_extra.to_string()),
+            ("dev_lock", self.dev_lock.to_string()),
+            ("prod_lock", self.prod_lock.to_string()),
+            ("index_url", optional(&self.index_url)),
+            ("extra_index_urls", self.extra_index_urls.join(" ")),
+            ("find_links", self.find_links.join(" ")),
+            ("trusted_hosts", self.trusted_hosts.join(" ")),
+            ("constraints", self.constraints.join(" ")),
+            (
+                "record_index_options",
+                self.record_index_options.to_string(),
+            ),
+            ("wheelhouse", self.wheelhouse.to_string()),
+            ("offline", self.offline.to_string()),
+        ]
+    }
+
+    fn apply_config(&mut self, config: ConfigFile, path: &Path) {
+        let ConfigFile {
+            venv_outside_project,
+            venv_from_stdlib,
+            python,
+            production,
+            profile,
+            system_site_packages,
+            recreate_if_stale,
+            auto_sync,
+            dev_extra,
+            prod_extra,
+            dev_lock,
+            prod_lock,
+            index_url,
+            extra_index_urls,
+            find_links,
+            trusted_hosts,
+            constraints,
+            record_index_options,
+            wheelhouse,
+            offline,
+            profiles,
+            tasks,
+        } = config;
+        let source = Source::File(path.to_path_buf());
+        let sources = &mut self.sources;
+        override_with(
+            sources,
+            &source,
+            "venv_outside_project",
+            venv_outside_project,
+            &mut self.venv_outside_project,
+        );
+        override_with(
+            sources,
+            &source,
+            "venv_from_stdlib",
+            venv_from_stdlib,
+            &mut self.venv_from_stdlib,
+        );
+        override_with(
+            sources,
+            &source,
+            "python",
+            python.map(Some),
+            &mut self.python,
+        );
+        // Note: `production = true` is a shortcut for `profile = "prod"`
+        let production = production.map(|p| if p { PROD_PROFILE } else { DEV_PROFILE });
+        let profile = profile.or_else(|| production.map(|p| p.to_string()));
+        override_with(sources, &source, "profile", profile, &mut self.profile);
+        override_with(
+            sources,
+            &source,
+            "system_site_packages",
+            system_site_packages,
+            &mut self.system_site_packages,
+        );
+        override_with(
+            sources,
+            &source,
+            "recreate_if_stale",
+            recreate_if_stale,
+            &mut self.recreate_if_stale,
+        );
+        override_with(
+            sources,
+            &source,
+            "auto_sync",
+            auto_sync,
+            &mut self.auto_sync,
+        );
+        override_with(
+            sources,
+            &source,
+            "dev_extra",
+            dev_extra,
+            &mut self.dev_extra,
+        );
+        override_with(
+            sources,
+            &source,
+            "prod_extra",
+            prod_extra,
+            &mut self.prod_extra,
+        );
+        override_with(sources, &source, "dev_lock", dev_lock, &mut self.dev_lock);
+        override_with(
+            sources,
+            &source,
+            "prod_lock",
+            prod_lock,
+            &mut self.prod_lock,
+        );
+        override_with(
+            sources,
+            &source,
+            "index_url",
+            index_url.map(Some),
+            &mut self.index_url,
+        );
+        override_with(
+            sources,
+            &source,
+            "extra_index_urls",
+            extra_index_urls,
+            &mut self.extra_index_urls,
+        );
+        override_with(
+            sources,
+            &source,
+            "find_links",
+            find_links,
+            &mut self.find_links,
+        );
+        override_with(
+            sources,
+            &source,
+            "trusted_hosts",
+            trusted_hosts,
+            &mut self.trusted_hosts,
+        );
+        override_with(
+            sources,
+            &source,
+            "constraints",
+            constraints,
+            &mut self.constraints,
+        );
+        override_with(
+            sources,
+            &source,
+            "record_index_options",
+            record_index_options,
+            &mut self.record_index_options,
+        );
+        override_with(
+            sources,
+            &source,
+            "wheelhouse",
+            wheelhouse,
+            &mut self.wheelhouse,
+        );
+        override_with(sources, &source, "offline", offline, &mut self.offline);
+        // Note: profiles are merged, a profile defined in the project
+        // configuration replaces the one with the same name in the user
+        // configuration
+        if let Some(profiles) = profiles {
+            self.profiles.extend(profiles);
+        }
+        // Note: same for tasks
+        if let Some(tasks) = tasks {
+            self.tasks.extend(tasks);
+        }
+    }
+
+    // Note: `get_var` is a parameter so that this can be tested without
+    // touching the environment of the test process
+    fn apply_env<F>(&mut self, get_var: F)
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if get_var("DMENV_NO_VENV_STDLIB").is_some() {
+            self.venv_from_stdlib = false;
+            self.set_source(
+                "venv_from_stdlib",
+                Source::Environment("DMENV_NO_VENV_STDLIB"),
+            );
+        }
+        if get_var("DMENV_VENV_OUTSIDE_PROJECT").is_some() {
+            self.venv_outside_project = true;
+            self.set_source(
+                "venv_outside_project",
+                Source::Environment("DMENV_VENV_OUTSIDE_PROJECT"),
+            );
+        }
+        if let Some(python) = get_var("DMENV_PYTHON") {
+            self.python = Some(python);
+            self.set_source("python", Source::Environment("DMENV_PYTHON"));
+        }
+        if let Some(profile) = get_var("DMENV_PROFILE") {
+            self.profile = profile;
+            self.set_source("profile", Source::Environment("DMENV_PROFILE"));
+        }
+        if get_var("DMENV_AUTO_SYNC").is_some() {
+            self.auto_sync = true;
+            self.set_source("auto_sync", Source::Environment("DMENV_AUTO_SYNC"));
+        }
+        if let Some(index_url) = get_var("DMENV_INDEX_URL") {
+            self.index_url = Some(index_url);
+            self.set_source("index_url", Source::Environment("DMENV_INDEX_URL"));
+        }
+        if get_var("DMENV_OFFLINE").is_some() {
+            self.offline = true;
+            self.set_source("offline", Source::Environment("DMENV_OFFLINE"));
+        }
+    }
+
+    // Note: flags only override the other layers when they are given
+    fn apply_cmd(&mut self, cmd: &Command) {
+        if let Some(index_url) = &cmd.index_url {
+            self.index_url = Some(index_url.to_string());
+            self.set_source("index_url", Source::CommandLine("--index-url"));
+        }
+        if !cmd.extra_index_urls.is_empty() {
+            self.extra_index_urls = cmd.extra_index_urls.clone();
+            self.set_source("extra_index_urls", Source::CommandLine("--extra-index-url"));
+        }
+        if !cmd.find_links.is_empty() {
+            self.find_links = cmd.find_links.clone();
+            self.set_source("find_links", Source::CommandLine("--find-links"));
+        }
+        if !cmd.trusted_hosts.is_empty() {
+            self.trusted_hosts = cmd.trusted_hosts.clone();
+            self.set_source("trusted_hosts", Source::CommandLine("--trusted-host"));
+        }
+        if !cmd.constraints.is_empty() {
+            self.constraints = cmd.constraints.clone();
+            self.set_source("constraints", Source::CommandLine("--constraint"));
+        }
+        if cmd.record_index_options {
+            self.record_index_options = true;
+            self.set_source(
+                "record_index_options",
+                Source::CommandLine("--record-index-options"),
+            );
+        }
+        if let Some(wheelhouse) = &cmd.wheelhouse {
+            self.wheelhouse = wheelhouse.to_string();
+            self.set_source("wheelhouse", Source::CommandLine("--wheelhouse"));
+        }
+        match &cmd.sub_cmd {
+            SubCommand::Install { offline: true, .. } | SubCommand::Lock { offline: true, .. } => {
+                self.offline = true;
+                self.set_source("offline", Source::CommandLine("--offline"));
+            }
+            _ => (),
+        }
+        if let Some(python) = &cmd.python_binary {
+            self.python = Some(python.to_string());
+            self.set_source("python", Source::CommandLine("--python"));
+        }
+        if let Some(profile) = &cmd.profile {
+            self.profile = profile.to_string();
+            self.set_source("profile", Source::CommandLine("--profile"));
+        }
+        if cmd.production {
+            self.profile = PROD_PROFILE.to_string();
+            self.set_source("profile", Source::CommandLine("--production"));
+        }
+        if cmd.recreate_if_stale {
+            self.recreate_if_stale = true;
+            self.set_source(
+                "recreate_if_stale",
+                Source::CommandLine("--recreate-if-stale"),
+            );
+        }
+        if cmd.system_site_packages {
+            self.system_site_packages = true;
+            self.set_source(
+                "system_site_packages",
+                Source::CommandLine("--system-site-packages"),
+            );
+        }
+    }
+
+    fn set_source(&mut self, key: &'static str, source: Source) {
+        self.sources.insert(key, source);
+    }
+}
+
+/// Set `field` to `value` and record where it comes from, unless
+/// `value` is None
+fn override_with<T>(
+    sources: &mut HashMap<&'static str, Source>,
+    source: &Source,
+    key: &'static str,
+    value: Option<T>,
+    field: &mut T,
+) {
+    if let Some(value) = value {
+        *field = value;
+        sources.insert(key, source.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structopt::StructOpt;
+
+    fn parse_config(contents: &str) -> ConfigFile {
+        ConfigFile::parse(Path::new("dmenv.toml"), contents).unwrap()
+    }
+
+    #[test]
+    fn test_defaults() {
+        let settings = Settings::default();
+        let profile = settings.profile();
+        assert_eq!(profile.extras, &["dev"]);
+        assert_eq!(profile.lock, DEV_LOCK_FILENAME);
+        assert_eq!(profile.venv_subdir, "dev");
+        assert_eq!(settings.source("dev_lock"), Source::Default);
+        assert!(settings.pip_options().is_empty());
+    }
+
+    #[test]
+    fn test_project_config_overrides_user_config() {
+        let mut settings = Settings::default();
+        let user_config = parse_config("production = true\nprod_extra = \"release\"\n");
+        settings.apply_config(user_config, Path::new("config.toml"));
+        let project_config = parse_config("prod_extra = \"deploy\"\n");
+        settings.apply_config(project_config, Path::new("dmenv.toml"));
+
+        assert_eq!(settings.profile().extras, &["deploy"]);
+        assert_eq!(
+            settings.source("profile"),
+            Source::File(PathBuf::from("config.toml"))
+        );
+        assert_eq!(
+            settings.source("prod_extra"),
+            Source::File(PathBuf::from("dmenv.toml"))
+        );
+    }
+
+    #[test]
+    fn test_env_overrides_config() {
+        let mut settings = Settings::default();
+        let config = parse_config("python = \"python3.6\"\nvenv_from_stdlib = true\n");
+        settings.apply_config(config, Path::new("dmenv.toml"));
+        settings.apply_env(|name| match name {
+            "DMENV_PYTHON" => Some("python3.8".to_string()),
+            _ => None,
+        });
+
+        assert_eq!(settings.python, Some("python3.8".to_string()));
+        assert_eq!(
+            settings.source("python"),
+            Source::Environment("DMENV_PYTHON")
+        );
+        assert!(settings.venv_from_stdlib);
+    }
+
+    #[test]
+    fn test_cmd_overrides_everything() {
+        let mut settings = Settings::default();
+        let config = parse_config("python = \"python3.6\"\nproduction = true\n");
+        settings.apply_config(config, Path::new("dmenv.toml"));
+        let cmd = Command::from_iter(&["dmenv", "--python", "python3.7", "show:config"]);
+        settings.apply_cmd(&cmd);
+
+        assert_eq!(settings.python, Some("python3.7".to_string()));
+        assert_eq!(settings.source("python"), Source::CommandLine("--python"));
+        // --production was not given, so the value from the file is kept
+        assert!(settings.is_production());
+    }
+
+    #[test]
+    fn test_custom_profile() {
+        let mut settings = Settings::default();
+        let config = parse_config("[profiles.docs]\nextras = [\"docs\", \"dev\"]\n");
+        settings.apply_config(config, Path::new("dmenv.toml"));
+        let cmd = Command::from_iter(&["dmenv", "--profile", "docs", "show:config"]);
+        settings.apply_cmd(&cmd);
+        settings.check_profile().unwrap();
+
+        assert_eq!(
+            settings.profile(),
+            Profile {
+                name: "docs".to_string(),
+                extras: vec!["docs".to_string(), "dev".to_string()],
+                lock: "docs.lock".to_string(),
+                venv_subdir: "docs".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_override_builtin_profile() {
+        let mut settings = Settings {
+            profile: PROD_PROFILE.to_string(),
+            ..Default::default()
+        };
+        let config =
+            parse_config("prod_lock = \"prod.lock\"\n[profiles.prod]\nvenv_subdir = \"release\"\n");
+        settings.apply_config(config, Path::new("dmenv.toml"));
+
+        let profile = settings.profile();
+        assert_eq!(profile.extras, &["prod"]);
+        assert_eq!(profile.lock, "prod.lock");
+        assert_eq!(profile.venv_subdir, "release");
+    }
+
+    #[test]
+    fn test_unknown_profile() {
+        let settings = Settings {
+            profile: "docs".to_string(),
+            ..Default::default()
+        };
+        let error = settings.check_profile().unwrap_err();
+        assert!(error.to_string().contains("docs"));
+    }
+
+    #[test]
+    fn test_pip_options() {
+        let settings = Settings {
+            index_url: Some("https://pypi.example.com/simple".to_string()),
+            extra_index_urls: vec!["https://mirror.example.com/simple".to_string()],
+            find_links: vec!["wheels".to_string()],
+            trusted_hosts: vec!["pypi.example.com".to_string()],
+            constraints: vec!["constraints.txt".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            settings.pip_options(),
+            &[
+                "--index-url",
+                "https://pypi.example.com/simple",
+                "--extra-index-url",
+                "https://mirror.example.com/simple",
+                "--find-links",
+                "wheels",
+                "--trusted-host",
+                "pypi.example.com",
+                "--constraint",
+                "constraints.txt",
+            ]
+        );
+        assert_eq!(
+            settings.lock_options(),
+            &[
+                "--index-url https://pypi.example.com/simple",
+                "--extra-index-url https://mirror.example.com/simple",
+                "--find-links wheels",
+                "--trusted-host pypi.example.com",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_offline() {
+        let mut settings = Settings::default();
+        let config = parse_config(
+            "index_url = \"https://pypi.example.com/simple\"\nwheelhouse = \"vendor\"\n",
+        );
+        settings.apply_config(config, Path::new("dmenv.toml"));
+        let cmd = Command::from_iter(&["dmenv", "install", "--offline"]);
+        settings.apply_cmd(&cmd);
+
+        assert_eq!(settings.source("offline"), Source::CommandLine("--offline"));
+        assert_eq!(
+            settings.pip_options(),
+            &["--no-index", "--find-links", "vendor"]
+        );
+        assert_eq!(
+            settings.download_options(),
+            &["--index-url", "https://pypi.example.com/simple"]
+        );
+    }
+
+    #[test]
+    fn test_index_options_from_command_line() {
+        let mut settings = Settings::default();
+        let config = parse_config("extra_index_urls = [\"https://old.example.com\"]\n");
+        settings.apply_config(config, Path::new("dmenv.toml"));
+        let cmd = Command::from_iter(&[
+            "dmenv",
+            "--extra-index-url",
+            "https://a.example.com",
+            "--extra-index-url",
+            "https://b.example.com",
+            "--find-links",
+            "wheels",
+            "show:config",
+        ]);
+        settings.apply_cmd(&cmd);
+        assert_eq!(
+            settings.extra_index_urls,
+            &["https://a.example.com", "https://b.example.com"]
+        );
+        assert_eq!(settings.find_links, &["wheels"]);
+        assert_eq!(
+            settings.source("extra_index_urls"),
+            Source::CommandLine("--extra-index-url")
+        );
+    }
 }
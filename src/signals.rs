@@ -0,0 +1,83 @@
+//! Forward the signals dmenv receives to the process it is waiting for,
+//! so that `dmenv run --no-exec` can be stopped like the process itself.
+use std::sync::atomic::{AtomicI32, Ordering};
+
+use libc::{c_int, c_void, siginfo_t};
+
+const FORWARDED_SIGNALS: [c_int; 2] = [libc::SIGINT, libc::SIGTERM];
+
+/// Pid of the process to forward signals to, 0 if none.
+// Notes:
+//  * this has to be a static, since it is read in a signal handler
+//  * so there can only be one SignalForwarder at a time, see
+//    `VenvRunner::forwarding_signals()`
+static CHILD_PID: AtomicI32 = AtomicI32::new(0);
+
+/// Forward SIGINT and SIGTERM to the given child process, until dropped.
+/// The previous signal handlers are then restored.
+pub struct SignalForwarder {
+    previous: Vec<(c_int, libc::sigaction)>,
+}
+
+impl SignalForwarder {
+    pub fn new(child_pid: u32) -> Self {
+        CHILD_PID.store(child_pid as i32, Ordering::SeqCst);
+        let mut previous = vec![];
+        for &signal in &FORWARDED_SIGNALS {
+            unsafe {
+                let mut action: libc::sigaction = std::mem::zeroed();
+                action.sa_sigaction = forward as *const () as usize;
+                action.sa_flags = libc::SA_SIGINFO | libc::SA_RESTART;
+                libc::sigemptyset(&mut action.sa_mask);
+                let mut old_action: libc::sigaction = std::mem::zeroed();
+                if libc::sigaction(signal, &action, &mut old_action) != 0 {
+                    continue;
+                }
+                // Note: signals ignored by dmenv, for instance when it runs
+                // in the background, must stay ignored
+                if old_action.sa_sigaction == libc::SIG_IGN {
+                    libc::sigaction(signal, &old_action, std::ptr::null_mut());
+                    continue;
+                }
+                previous.push((signal, old_action));
+            }
+        }
+        SignalForwarder { previous }
+    }
+}
+
+impl Drop for SignalForwarder {
+    fn drop(&mut self) {
+        for (signal, old_action) in &self.previous {
+            unsafe {
+                libc::sigaction(*signal, old_action, std::ptr::null_mut());
+            }
+        }
+        CHILD_PID.store(0, Ordering::SeqCst);
+    }
+}
+
+extern "C" fn forward(signal: c_int, info: *mut siginfo_t, _context: *mut c_void) {
+    let pid = CHILD_PID.load(Ordering::SeqCst);
+    if pid <= 0 || sent_by_terminal(signal, info) {
+        return;
+    }
+    unsafe {
+        libc::kill(pid, signal);
+    }
+}
+
+/// Whether the signal comes from the terminal, for instance when Ctrl-C
+/// is pressed. The terminal sends it to every process of the foreground
+/// group, so the child already got it and must not get it twice.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn sent_by_terminal(_signal: c_int, info: *mut siginfo_t) -> bool {
+    !info.is_null() && unsafe { (*info).si_code } == libc::SI_KERNEL
+}
+
+// Note: other systems do not tell signals sent by the terminal apart,
+// and SIGINT usually comes from the terminal
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn sent_by_terminal(signal: c_int, _info: *mut siginfo_t) -> bool {
+    signal == libc::SIGINT
+}
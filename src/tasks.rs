@@ -0,0 +1,190 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::config::TaskConfig;
+use crate::error::*;
+use crate::project;
+
+/// The tasks defined for a project, see `dmenv task`
+#[derive(Debug)]
+pub struct Tasks {
+    tasks: BTreeMap<String, TaskConfig>,
+}
+
+impl Tasks {
+    /// Read the tasks defined in `setup.cfg`, then add the ones
+    /// from the configuration files
+    // Note: a task defined in a configuration file replaces the
+    // one with the same name in `setup.cfg`
+    pub fn load(
+        project_path: &Path,
+        configured: &BTreeMap<String, TaskConfig>,
+    ) -> Result<Self, Error> {
+        let mut tasks = project::read_setup_cfg_tasks(project_path)?;
+        tasks.extend(configured.clone());
+        Ok(Tasks { tasks })
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.tasks.keys().cloned().collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &TaskConfig)> {
+        self.tasks.iter()
+    }
+
+    pub fn get(&self, name: &str) -> Result<&TaskConfig, Error> {
+        self.tasks.get(name).ok_or_else(|| Error::UnknownTask {
+            name: name.to_string(),
+            known: self.names(),
+        })
+    }
+
+    /// Names of the tasks to run for the given task: its dependencies
+    /// first, recursively, then the task itself. Each task appears once.
+    pub fn plan(&self, name: &str) -> Result<Vec<String>, Error> {
+        let mut res = vec![];
+        self.visit(name, &mut vec![], &mut res)?;
+        Ok(res)
+    }
+
+    fn visit(
+        &self,
+        name: &str,
+        stack: &mut Vec<String>,
+        res: &mut Vec<String>,
+    ) -> Result<(), Error> {
+        if res.iter().any(|n| n == name) {
+            return Ok(());
+        }
+        if let Some(pos) = stack.iter().position(|n| n == name) {
+            let mut names = stack[pos..].to_vec();
+            names.push(name.to_string());
+            return Err(Error::TaskCycle { names });
+        }
+        let task = self.get(name)?;
+        stack.push(name.to_string());
+        for dependency in &task.depends {
+            self.visit(dependency, stack, res)?;
+        }
+        stack.pop();
+        res.push(name.to_string());
+        Ok(())
+    }
+}
+
+/// Split a command line into arguments, handling quotes and backslashes
+/// like a POSIX shell does. There is no expansion of any kind, though.
+pub fn split_command(line: &str) -> Result<Vec<String>, String> {
+    let mut res = vec![];
+    let mut current: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(arg) = current.take() {
+                    res.push(arg);
+                }
+            }
+            '\'' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        None => return Err(format!("missing closing quote in `{}`", line)),
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                    }
+                }
+            }
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        None => return Err(format!("missing closing quote in `{}`", line)),
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) if c == '"' || c == '\\' => arg.push(c),
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err(format!("missing closing quote in `{}`", line)),
+                        },
+                        Some(c) => arg.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                let arg = current.get_or_insert_with(String::new);
+                if let Some(c) = chars.next() {
+                    arg.push(c);
+                }
+            }
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(arg) = current {
+        res.push(arg);
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TaskCommands;
+
+    fn tasks(definitions: &[(&str, &[&str])]) -> Tasks {
+        let mut tasks = BTreeMap::new();
+        for (name, depends) in definitions {
+            let task = TaskConfig {
+                description: None,
+                run: TaskCommands::One(format!("echo {}", name)),
+                env: BTreeMap::new(),
+                cwd: None,
+                depends: depends.iter().map(|d| d.to_string()).collect(),
+            };
+            tasks.insert(name.to_string(), task);
+        }
+        Tasks { tasks }
+    }
+
+    #[test]
+    fn test_plan() {
+        let tasks = tasks(&[
+            ("ci", &["lint", "test"]),
+            ("lint", &[]),
+            ("test", &["lint", "build"]),
+            ("build", &[]),
+        ]);
+        assert_eq!(tasks.plan("ci").unwrap(), &["lint", "build", "test", "ci"]);
+        assert_eq!(tasks.plan("lint").unwrap(), &["lint"]);
+    }
+
+    #[test]
+    fn test_plan_unknown_task() {
+        let tasks = tasks(&[("test", &["build"])]);
+        let error = tasks.plan("test").unwrap_err();
+        assert!(error.to_string().contains("unknown task: 'build'"));
+    }
+
+    #[test]
+    fn test_plan_cycle() {
+        let tasks = tasks(&[("a", &["b"]), ("b", &["c"]), ("c", &["b"])]);
+        let error = tasks.plan("a").unwrap_err();
+        assert!(error.to_string().contains("b -> c -> b"));
+    }
+
+    #[test]
+    fn test_split_command() {
+        assert_eq!(
+            split_command("  pytest -k 'not slow' tests/").unwrap(),
+            &["pytest", "-k", "not slow", "tests/"]
+        );
+        assert_eq!(
+            split_command(r#"python -c "print(\"hi\")" a\ b ''"#).unwrap(),
+            &["python", "-c", "print(\"hi\")", "a b", ""]
+        );
+        split_command("echo 'oops").unwrap_err();
+    }
+}
@@ -1,4 +1,34 @@
 use colored::*;
+use std::cell::Cell;
+
+thread_local! {
+    static PROGRESS_ON_STDERR: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Run `f` with progress messages, and the output of the commands run
+/// in the virtualenv, written to stderr instead of stdout.
+// Note: used when a command has to update the virtualenv before doing
+// its job, so that its own output stays clean. The flag is per thread,
+// so members of a workspace processed in parallel don't interfere
+pub fn with_progress_on_stderr<T>(f: impl FnOnce() -> T) -> T {
+    let previous = PROGRESS_ON_STDERR.with(|p| p.replace(true));
+    let res = f();
+    PROGRESS_ON_STDERR.with(|p| p.set(previous));
+    res
+}
+
+pub fn progress_on_stderr() -> bool {
+    PROGRESS_ON_STDERR.with(|p| p.get())
+}
+
+/// Print a progress message, see `with_progress_on_stderr()`
+pub fn print_progress(message: &str) {
+    if progress_on_stderr() {
+        eprintln!("{}", message);
+    } else {
+        println!("{}", message);
+    }
+}
 
 pub fn print_error(description: &str) {
     eprintln!("{}: {}", "Error".bold().red(), description);
@@ -9,9 +39,160 @@ pub fn print_warning(description: &str) {
 }
 
 pub fn print_info_1(message: &str) {
-    println!("{} {}", "::".blue(), message);
+    print_progress(&format!("{} {}", "::".blue(), message));
 }
 
 pub fn print_info_2(message: &str) {
-    println!("{} {}", "->".blue(), message);
+    print_progress(&format!("{} {}", "->".blue(), message));
+}
+
+/// Print the changes between two versions of the file `name`, as a unified diff
+pub fn print_diff(name: &str, old: &str, new: &str) {
+    let diff = unified_diff(name, old, new);
+    for line in diff.lines() {
+        if line.starts_with("---") || line.starts_with("+++") {
+            println!("{}", line.bold());
+        } else if line.starts_with("@@") {
+            println!("{}", line.cyan());
+        } else if line.starts_with('-') {
+            println!("{}", line.red());
+        } else if line.starts_with('+') {
+            println!("{}", line.green());
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+/// Align the given rows in columns, separated by two spaces
+pub fn format_table(rows: &[Vec<String>]) -> String {
+    let columns = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+    let widths: Vec<_> = (0..columns)
+        .map(|i| {
+            let cell_width = |r: &Vec<String>| r.get(i).map_or(0, |c| c.len());
+            rows.iter().map(cell_width).max().unwrap_or(0)
+        })
+        .collect();
+    let mut res = String::new();
+    for row in rows {
+        let cells: Vec<_> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        res.push_str(cells.join("  ").trim_end());
+        res.push('\n');
+    }
+    res
+}
+
+const DIFF_CONTEXT: usize = 3;
+
+fn unified_diff(name: &str, old: &str, new: &str) -> String {
+    // Tag each line with its prefix and its line numbers in the old
+    // and the new file (starting at zero)
+    let mut lines = vec![];
+    let (mut old_index, mut new_index) = (0, 0);
+    let old_lines: Vec<_> = old.lines().collect();
+    let new_lines: Vec<_> = new.lines().collect();
+    for result in diff::slice(&old_lines, &new_lines) {
+        let line = match result {
+            diff::Result::Left(l) => ('-', *l),
+            diff::Result::Right(r) => ('+', *r),
+            diff::Result::Both(l, _) => (' ', *l),
+        };
+        lines.push((line, old_index, new_index));
+        match line.0 {
+            '-' => old_index += 1,
+            '+' => new_index += 1,
+            _ => {
+                old_index += 1;
+                new_index += 1;
+            }
+        }
+    }
+
+    // Group changes that are close to each other in hunks
+    let changed: Vec<_> = (0..lines.len())
+        .filter(|i| (lines[*i].0).0 != ' ')
+        .collect();
+    if changed.is_empty() {
+        return String::new();
+    }
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for i in changed {
+        let start = i.saturating_sub(DIFF_CONTEXT);
+        let end = (i + DIFF_CONTEXT + 1).min(lines.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let mut res = format!("--- {}\n+++ {}\n", name, name);
+    for (start, end) in hunks {
+        let hunk = &lines[start..end];
+        let old_len = hunk.iter().filter(|l| (l.0).0 != '+').count();
+        let new_len = hunk.iter().filter(|l| (l.0).0 != '-').count();
+        // Note: like `diff -u`, empty ranges start at the line before
+        let old_start = if old_len == 0 {
+            hunk[0].1
+        } else {
+            hunk[0].1 + 1
+        };
+        let new_start = if new_len == 0 {
+            hunk[0].2
+        } else {
+            hunk[0].2 + 1
+        };
+        res.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_start, old_len, new_start, new_len
+        ));
+        for ((prefix, text), _, _) in hunk {
+            res.push_str(&format!("{}{}\n", prefix, text));
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unified_diff_with_context() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+        let expected = "\
+--- requirements.lock
++++ requirements.lock
+@@ -1,5 +1,5 @@
+ a
+-b
++B
+ c
+ d
+ e
+@@ -8,3 +8,4 @@
+ h
+ i
+ j
++k
+";
+        assert_eq!(unified_diff("requirements.lock", old, new), expected);
+    }
+
+    #[test]
+    fn unified_diff_no_changes() {
+        assert_eq!(unified_diff("foo", "a\n", "a\n"), "");
+    }
+
+    #[test]
+    fn unified_diff_from_empty_file() {
+        assert_eq!(
+            unified_diff("foo", "", "a\n"),
+            "--- foo\n+++ foo\n@@ -0,0 +1,1 @@\n+a\n"
+        );
+    }
 }
@@ -0,0 +1,319 @@
+use std::path::Path;
+
+use crate::dependencies::LockedDependency;
+use crate::error::*;
+use crate::lock::Lock;
+use crate::markers::Environment;
+use crate::project::normalize_name;
+
+const SDIST_EXTENSIONS: &[&str] = &[".tar.gz", ".tar.bz2", ".tar.xz", ".tgz", ".zip"];
+
+/// Return the lines of the lock that apply to the given environment,
+/// but have no matching archive in the wheelhouse, formatted like
+/// `name==version`, or as is for packages installed from git
+//
+// Notes:
+//  * names are normalized like in PEP 503 before being compared
+//  * wheels built for another interpreter or another platform
+//    don't count, see `is_compatible()`
+//  * archives of packages installed from git are named after the
+//    version in their metadata, not the git ref, so only their name
+//    is compared
+pub fn missing(
+    wheelhouse: &Path,
+    lock: &Lock,
+    environment: &Environment,
+) -> Result<Vec<String>, Error> {
+    let available: Vec<_> = list_archives(wheelhouse)?
+        .into_iter()
+        .filter(|a| match &a.tags {
+            None => true,
+            Some(tags) => is_compatible(tags, environment),
+        })
+        .collect();
+    let is_available = |dep: &LockedDependency| {
+        let name = normalize_name(&dep.name());
+        available.iter().any(|a| {
+            a.name == name
+                && match dep {
+                    LockedDependency::Git(_) => true,
+                    LockedDependency::Simple(_) => a.version.eq_ignore_ascii_case(&dep.version()),
+                }
+        })
+    };
+    let res = lock
+        .dependencies()
+        .filter(|d| d.applies_to(environment))
+        .filter(|d| !is_available(d))
+        .map(|d| match d {
+            LockedDependency::Git(_) => d.line(),
+            LockedDependency::Simple(_) => format!("{}=={}", d.name(), d.version()),
+        })
+        .collect();
+    Ok(res)
+}
+
+/// A wheel or a source distribution found in the wheelhouse
+#[derive(Debug, PartialEq)]
+struct Archive {
+    /// Normalized name of the distribution
+    name: String,
+    version: String,
+    /// Python, ABI and platform tags, for wheels only
+    tags: Option<(String, String, String)>,
+}
+
+/// Get every archive in the wheelhouse.
+// Note: a missing wheelhouse is the same as an empty one
+fn list_archives(wheelhouse: &Path) -> Result<Vec<Archive>, Error> {
+    if !wheelhouse.exists() {
+        return Ok(vec![]);
+    }
+    let entries = std::fs::read_dir(wheelhouse).map_err(|e| new_read_error(e, wheelhouse))?;
+    let mut res = vec![];
+    for entry in entries {
+        let entry = entry.map_err(|e| new_read_error(e, wheelhouse))?;
+        let file_name = entry.file_name();
+        if let Some(archive) = parse_archive_name(&file_name.to_string_lossy()) {
+            res.push(archive);
+        }
+    }
+    Ok(res)
+}
+
+/// Get the normalized name, the version and the tags of a wheel or of a
+/// source distribution from its file name, like `foo-bar`, `1.0` and
+/// `(py3, none, any)` for `foo_bar-1.0-py3-none-any.whl`
+fn parse_archive_name(file_name: &str) -> Option<Archive> {
+    // Note: in wheel names, dashes are only used to separate the
+    // components, and there may be a build tag after the version,
+    // see PEP 427
+    if let Some(stem) = file_name.strip_suffix(".whl") {
+        let parts: Vec<_> = stem.split('-').collect();
+        if parts.len() != 5 && parts.len() != 6 {
+            return None;
+        }
+        let n = parts.len();
+        return Some(Archive {
+            name: normalize_name(parts[0]),
+            version: parts[1].to_string(),
+            tags: Some((
+                parts[n - 3].to_string(),
+                parts[n - 2].to_string(),
+                parts[n - 1].to_string(),
+            )),
+        });
+    }
+    // Note: names of source distributions may contain dashes, but
+    // versions can't
+    let stem = SDIST_EXTENSIONS
+        .iter()
+        .find_map(|ext| file_name.strip_suffix(ext))?;
+    let pos = stem.rfind('-')?;
+    Some(Archive {
+        name: normalize_name(&stem[..pos]),
+        version: stem[pos + 1..].to_string(),
+        tags: None,
+    })
+}
+
+/// Whether pip would install a wheel with the given Python, ABI and
+/// platform tags, like `("py2.py3", "none", "any")`, in the given environment
+//
+// Notes:
+//  * each tag may be a set of values separated by dots, see PEP 425
+//  * this does not check the minimum version of macOS or glibc
+//    required by the wheel
+fn is_compatible(tags: &(String, String, String), environment: &Environment) -> bool {
+    let (python_tags, abi_tags, platform_tags) = tags;
+    let implementation = environment.get("implementation_name").unwrap_or("");
+    let short_implementation = match implementation {
+        "cpython" => "cp",
+        "pypy" => "pp",
+        "ironpython" => "ip",
+        "jython" => "jy",
+        other => other,
+    };
+    let python_version = environment.get("python_version").unwrap_or("");
+    let mut version = python_version.splitn(2, '.');
+    let major = version.next().unwrap_or("");
+    let minor: u64 = match version.next().and_then(|m| m.parse().ok()) {
+        Some(m) => m,
+        None => return false,
+    };
+    let digits = format!("{}{}", major, minor);
+
+    let python_matches = |tag: &str| {
+        if tag == format!("py{}", major) || tag == format!("py{}", digits) {
+            return true;
+        }
+        if tag == format!("{}{}", short_implementation, digits) {
+            return true;
+        }
+        // Note: wheels using the stable ABI of CPython work with the
+        // later versions of CPython too
+        match tag.strip_prefix(&format!("cp{}", major)) {
+            Some(older) if implementation == "cpython" => {
+                older.parse::<u64>().is_ok_and(|m| m <= minor)
+            }
+            _ => false,
+        }
+    };
+    let abi_matches = |tag: &str| {
+        tag == "none"
+            || (tag == "abi3" && implementation == "cpython")
+            || tag.starts_with(&format!("{}{}", short_implementation, digits))
+            || tag.starts_with(&format!("{}{}", implementation, digits))
+    };
+    let platform_matches = |tag: &str| {
+        if tag == "any" {
+            return true;
+        }
+        let (sys_platform, machine) = match parse_platform_tag(tag) {
+            None => return false,
+            Some(x) => x,
+        };
+        let current_machine = environment.get("platform_machine").unwrap_or("");
+        let universal = sys_platform == "darwin"
+            && ["universal", "universal2", "intel"].contains(&machine.as_str());
+        Some(sys_platform) == environment.get("sys_platform")
+            && (universal || machine.eq_ignore_ascii_case(current_machine))
+    };
+    python_tags.split('.').any(python_matches)
+        && abi_tags.split('.').any(abi_matches)
+        && platform_tags.split('.').any(platform_matches)
+}
+
+/// Get the `sys_platform` and the machine a platform tag is for, like
+/// `("linux", "x86_64")` for `manylinux2014_x86_64`
+// Note: machine names of Windows are upper case, like
+// `platform.machine()` returns them
+pub fn parse_platform_tag(tag: &str) -> Option<(&'static str, String)> {
+    if tag == "win32" {
+        return Some(("win32", "x86".to_string()));
+    }
+    let mut parts = tag.split('_');
+    let sys_platform = match parts.next()? {
+        "win" => "win32",
+        "macosx" => "darwin",
+        p if p.starts_with("linux") || p.starts_with("manylinux") || p.starts_with("musllinux") => {
+            "linux"
+        }
+        _ => return None,
+    };
+    // Note: skip the versions in the tag, like `2_17` in `manylinux_2_17_x86_64`
+    let machine: Vec<_> = parts
+        .skip_while(|p| p.chars().all(|c| c.is_ascii_digit()))
+        .collect();
+    if machine.is_empty() {
+        return None;
+    }
+    let machine = machine.join("_");
+    if sys_platform == "win32" {
+        return Some((sys_platform, machine.to_uppercase()));
+    }
+    Some((sys_platform, machine))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lock::parse;
+
+    fn linux_environment() -> Environment {
+        let mut environment = Environment::new();
+        environment
+            .set("sys_platform", "linux")
+            .set("platform_machine", "x86_64")
+            .set("python_version", "3.8")
+            .set("implementation_name", "cpython");
+        environment
+    }
+
+    fn tags(python: &str, abi: &str, platform: &str) -> (String, String, String) {
+        (python.to_string(), abi.to_string(), platform.to_string())
+    }
+
+    #[test]
+    fn test_parse_archive_name() {
+        assert_eq!(
+            parse_archive_name("Foo_Bar-1.0-1-py3-none-any.whl"),
+            Some(Archive {
+                name: "foo-bar".to_string(),
+                version: "1.0".to_string(),
+                tags: Some(tags("py3", "none", "any")),
+            })
+        );
+        assert_eq!(
+            parse_archive_name("foo-bar-2.0rc1.tar.gz"),
+            Some(Archive {
+                name: "foo-bar".to_string(),
+                version: "2.0rc1".to_string(),
+                tags: None,
+            })
+        );
+        assert_eq!(parse_archive_name("README.txt"), None);
+        assert_eq!(parse_archive_name("foo-1.0.whl"), None);
+    }
+
+    #[test]
+    fn test_parse_platform_tag() {
+        let parse = |tag| parse_platform_tag(tag).map(|(p, m)| (p, m.to_string()));
+        assert_eq!(parse("win_amd64"), Some(("win32", "AMD64".to_string())));
+        assert_eq!(parse("win32"), Some(("win32", "x86".to_string())));
+        assert_eq!(
+            parse("macosx_10_9_x86_64"),
+            Some(("darwin", "x86_64".to_string()))
+        );
+        assert_eq!(
+            parse("manylinux_2_17_aarch64"),
+            Some(("linux", "aarch64".to_string()))
+        );
+        assert_eq!(
+            parse("manylinux2014_x86_64"),
+            Some(("linux", "x86_64".to_string()))
+        );
+        assert_eq!(parse("any"), None);
+    }
+
+    #[test]
+    fn test_is_compatible() {
+        let environment = linux_environment();
+        let compatible =
+            |python, abi, platform| is_compatible(&tags(python, abi, platform), &environment);
+        assert!(compatible("py2.py3", "none", "any"));
+        assert!(compatible(
+            "cp38",
+            "cp38",
+            "manylinux1_x86_64.manylinux2010_x86_64"
+        ));
+        assert!(compatible("cp36", "abi3", "manylinux2014_x86_64"));
+        assert!(!compatible("py2", "none", "any"));
+        assert!(!compatible("cp37", "cp37m", "manylinux1_x86_64"));
+        assert!(!compatible("cp38", "cp38", "win_amd64"));
+        assert!(!compatible("cp38", "cp38", "manylinux2014_aarch64"));
+        assert!(!compatible("cp39", "abi3", "manylinux2014_x86_64"));
+    }
+
+    #[test]
+    fn test_missing() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let wheelhouse = tmp_dir.path();
+        std::fs::write(wheelhouse.join("attrs-19.1.0-py2.py3-none-any.whl"), "").unwrap();
+        std::fs::write(wheelhouse.join("zope.interface-4.6.tar.gz"), "").unwrap();
+        std::fs::write(wheelhouse.join("pytest-4.5.0-py3-none-any.whl"), "").unwrap();
+        std::fs::write(wheelhouse.join("lxml-4.4.0-cp38-cp38-win_amd64.whl"), "").unwrap();
+        let lock = parse(
+            "attrs==19.1.0\n\
+             zope-interface==4.6\n\
+             pytest==5.0.0\n\
+             lxml==4.4.0\n\
+             pywin32==224 ; sys_platform == \"win32\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            missing(wheelhouse, &lock, &linux_environment()).unwrap(),
+            &["pytest==5.0.0", "lxml==4.4.0"]
+        );
+    }
+}
@@ -0,0 +1,289 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use crate::error::*;
+use crate::project::{self, normalize_name};
+
+pub const WORKSPACE_FILENAME: &str = "dmenv-workspace.toml";
+
+/// Contents of the workspace file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WorkspaceFile {
+    /// Paths of the member projects, relative to the workspace file
+    members: Vec<String>,
+    #[serde(default)]
+    shared_venv: bool,
+}
+
+/// A set of projects listed in a `dmenv-workspace.toml` file,
+/// usually at the root of a monorepo
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    pub root: PathBuf,
+    pub members: Vec<Member>,
+    /// Install all the members in a single virtualenv, using
+    /// a combined lock at the root of the workspace
+    pub shared_venv: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+    /// Canonical path of the project
+    pub path: PathBuf,
+    /// Normalized name of the project, when it can be read
+    /// from `pyproject.toml` or `setup.cfg`
+    pub name: Option<String>,
+    /// Normalized names of the distributions required by the project
+    pub requires: Vec<String>,
+}
+
+impl Workspace {
+    /// Look for a workspace file in the given directory and its parents
+    pub fn find(start: &Path) -> Result<Option<Workspace>, Error> {
+        match Workspace::find_root(start)? {
+            None => Ok(None),
+            Some(root) => Ok(Some(Workspace::read(&root)?)),
+        }
+    }
+
+    /// Look for the directory containing a workspace file, starting
+    /// from the given directory
+    pub fn find_root(start: &Path) -> Result<Option<PathBuf>, Error> {
+        let start = start.canonicalize().map_err(|e| new_read_error(e, start))?;
+        let root = start
+            .ancestors()
+            .find(|candidate| candidate.join(WORKSPACE_FILENAME).exists());
+        Ok(root.map(|r| r.to_path_buf()))
+    }
+
+    /// Whether the given project is the root of the workspace in the
+    /// `root` directory, or one of its members
+    // Note: unlike `read()`, this only looks at the paths of the members,
+    // so it still works when one of them is broken. When the workspace
+    // file itself can't be parsed, there's no way to tell, and
+    // only the root is considered part of the workspace
+    pub fn contains(root: &Path, project_path: &Path) -> bool {
+        let project_path = match project_path.canonicalize() {
+            Ok(p) => p,
+            Err(_) => return false,
+        };
+        if project_path == root {
+            return true;
+        }
+        let contents = match std::fs::read_to_string(root.join(WORKSPACE_FILENAME)) {
+            Ok(c) => c,
+            Err(_) => return false,
+        };
+        let workspace_file: WorkspaceFile = match toml::from_str(&contents) {
+            Ok(w) => w,
+            Err(_) => return false,
+        };
+        workspace_file
+            .members
+            .iter()
+            .any(|m| root.join(m).canonicalize().ok().as_ref() == Some(&project_path))
+    }
+
+    /// Read the workspace file in the `root` directory, along with
+    /// the metadata of every member
+    pub fn read(root: &Path) -> Result<Workspace, Error> {
+        let path = root.join(WORKSPACE_FILENAME);
+        let contents = std::fs::read_to_string(&path).map_err(|e| new_read_error(e, &path))?;
+        let workspace_file: WorkspaceFile =
+            toml::from_str(&contents).map_err(|e| Error::MalformedConfig {
+                path: path.to_path_buf(),
+                details: e.to_string(),
+            })?;
+        let mut members = vec![];
+        for member in workspace_file.members {
+            let member_path =
+                root.join(&member)
+                    .canonicalize()
+                    .map_err(|_| Error::MalformedConfig {
+                        path: path.to_path_buf(),
+                        details: format!("member '{}' does not exist", member),
+                    })?;
+            let metadata = project::read_metadata(&member_path)?;
+            members.push(Member {
+                name: metadata.name.as_deref().map(normalize_name),
+                requires: metadata.required_names(),
+                path: member_path,
+            });
+        }
+        Ok(Workspace {
+            root: root.to_path_buf(),
+            members,
+            shared_venv: workspace_file.shared_venv,
+        })
+    }
+
+    pub fn member_paths(&self) -> Vec<PathBuf> {
+        self.members.iter().map(|m| m.path.to_path_buf()).collect()
+    }
+
+    /// Whether the given project should use the shared virtualenv
+    /// of the workspace - this is also true for the workspace root
+    pub fn is_shared_by(&self, project_path: &Path) -> bool {
+        if !self.shared_venv {
+            return false;
+        }
+        let project_path = match project_path.canonicalize() {
+            Ok(p) => p,
+            Err(_) => return false,
+        };
+        project_path == self.root || self.members.iter().any(|m| m.path == project_path)
+    }
+
+    /// The versions of Python supported by all the members, see
+    /// `project::python_requirement()`
+    pub fn python_requirement(&self) -> Result<Option<String>, Error> {
+        let mut requirements = vec![];
+        for member in &self.members {
+            if let Some(requirement) = project::python_requirement(&member.path)? {
+                requirements.push(requirement);
+            }
+        }
+        if requirements.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(requirements.join(", ")))
+    }
+
+    /// Paths of the other members required by the given project,
+    /// directly or not. They are installed in editable mode from
+    /// the workspace rather than from the package index.
+    pub fn local_dependencies(&self, project_path: &Path) -> Vec<PathBuf> {
+        let project_path = match project_path.canonicalize() {
+            Ok(p) => p,
+            Err(_) => return vec![],
+        };
+        let project = match self.members.iter().find(|m| m.path == project_path) {
+            None => return vec![],
+            Some(member) => member,
+        };
+        let mut res: Vec<&Member> = vec![];
+        let mut to_visit = vec![project];
+        while let Some(current) = to_visit.pop() {
+            for name in &current.requires {
+                let dependency = self.members.iter().find(|m| m.name.as_ref() == Some(name));
+                if let Some(dependency) = dependency {
+                    if dependency != project && !res.contains(&dependency) {
+                        res.push(dependency);
+                        to_visit.push(dependency);
+                    }
+                }
+            }
+        }
+        res.iter().map(|m| m.path.to_path_buf()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Setup {
+        tmp_dir: tempdir::TempDir,
+    }
+
+    impl Setup {
+        fn new(members: &[(&str, &str)]) -> Self {
+            let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+            let root = tmp_dir.path();
+            let mut names = vec![];
+            for (name, setup_cfg) in members {
+                let member_path = root.join("libs").join(name);
+                std::fs::create_dir_all(&member_path).unwrap();
+                std::fs::write(member_path.join("setup.py"), "setup()\n").unwrap();
+                std::fs::write(member_path.join("setup.cfg"), setup_cfg).unwrap();
+                names.push(format!("\"libs/{}\"", name));
+            }
+            let contents = format!("members = [{}]\n", names.join(", "));
+            std::fs::write(root.join(WORKSPACE_FILENAME), contents).unwrap();
+            Setup { tmp_dir }
+        }
+
+        fn member_path(&self, name: &str) -> PathBuf {
+            self.tmp_dir
+                .path()
+                .join("libs")
+                .join(name)
+                .canonicalize()
+                .unwrap()
+        }
+    }
+
+    #[test]
+    fn test_find_from_member() {
+        let setup = Setup::new(&[("foo", "[metadata]\nname = foo\n")]);
+        let workspace = Workspace::find(&setup.member_path("foo")).unwrap().unwrap();
+        assert_eq!(workspace.member_paths(), &[setup.member_path("foo")]);
+        assert_eq!(workspace.members[0].name, Some("foo".to_string()));
+    }
+
+    #[test]
+    fn test_shared_venv() {
+        let setup = Setup::new(&[("foo", "[metadata]\nname = foo\n")]);
+        let workspace = Workspace::find(setup.tmp_dir.path()).unwrap().unwrap();
+        assert!(!workspace.is_shared_by(&setup.member_path("foo")));
+
+        let contents = "members = [\"libs/foo\"]\nshared_venv = true\n";
+        std::fs::write(setup.tmp_dir.path().join(WORKSPACE_FILENAME), contents).unwrap();
+        let workspace = Workspace::find(setup.tmp_dir.path()).unwrap().unwrap();
+        assert!(workspace.is_shared_by(&setup.member_path("foo")));
+        assert!(workspace.is_shared_by(setup.tmp_dir.path()));
+        assert!(!workspace.is_shared_by(&setup.tmp_dir.path().join("libs")));
+    }
+
+    #[test]
+    fn test_missing_member() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let contents = "members = [\"no-such-dir\"]\n";
+        std::fs::write(tmp_dir.path().join(WORKSPACE_FILENAME), contents).unwrap();
+        let error = Workspace::read(tmp_dir.path()).unwrap_err();
+        assert!(error.to_string().contains("no-such-dir"));
+    }
+
+    #[test]
+    fn test_contains_with_a_broken_member() {
+        let setup = Setup::new(&[("foo", "[metadata]\nname = foo\n")]);
+        let root = setup.tmp_dir.path().canonicalize().unwrap();
+        let other_path = root.join("other");
+        std::fs::create_dir(&other_path).unwrap();
+        let contents = "members = [\"libs/foo\", \"no-such-dir\"]\n";
+        std::fs::write(root.join(WORKSPACE_FILENAME), contents).unwrap();
+        assert!(Workspace::find(&other_path).is_err());
+
+        assert_eq!(
+            Workspace::find_root(&other_path).unwrap(),
+            Some(root.clone())
+        );
+        assert!(Workspace::contains(&root, &root));
+        assert!(Workspace::contains(&root, &setup.member_path("foo")));
+        assert!(!Workspace::contains(&root, &other_path));
+    }
+
+    #[test]
+    fn test_local_dependencies() {
+        let setup = Setup::new(&[
+            (
+                "app",
+                "[metadata]\nname = app\n[options]\ninstall_requires =\n  attrs\n  Core_Lib>=1.0\n",
+            ),
+            (
+                "core",
+                "[metadata]\nname = core.lib\n[options]\ninstall_requires =\n  utils\n",
+            ),
+            ("utils", "[metadata]\nname = utils\n"),
+        ]);
+        let workspace = Workspace::read(setup.tmp_dir.path()).unwrap();
+        assert_eq!(
+            workspace.local_dependencies(&setup.member_path("app")),
+            &[setup.member_path("core"), setup.member_path("utils")]
+        );
+        assert!(workspace
+            .local_dependencies(&setup.member_path("utils"))
+            .is_empty());
+    }
+}
@@ -3,8 +3,6 @@ use std::path::PathBuf;
 use ignore::Walk;
 use structopt::StructOpt;
 
-use dmenv;
-
 #[derive(Debug)]
 pub struct TestApp {
     tmp_dir: tempdir::TempDir,
@@ -51,6 +49,11 @@ impl TestApp {
         std::fs::write(path, contents).unwrap();
     }
 
+    pub fn write_file(&self, name: &str, contents: &str) {
+        let path = self.path().join(name);
+        std::fs::write(path, contents).unwrap();
+    }
+
     pub fn remove_setup_py(&self) {
         self.remove_file("setup.py");
     }
@@ -78,7 +81,7 @@ impl TestApp {
     }
 
     pub fn assert_run_ok(&self, args: &[&str]) {
-        let args = to_string_args(&args);
+        let args = to_string_args(args);
         self.run(args).unwrap();
     }
 
@@ -101,7 +104,7 @@ impl TestApp {
     }
 
     pub fn assert_run_error(&self, args: &[&str]) -> String {
-        let args = to_string_args(&args);
+        let args = to_string_args(args);
         let res = self.run(args);
         res.unwrap_err().to_string()
     }
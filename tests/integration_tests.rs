@@ -1,5 +1,5 @@
 mod helpers;
-use crate::helpers::TestApp;
+use crate::helpers::{to_string_args, TestApp};
 
 #[test]
 fn show_venv_path() {
@@ -7,6 +7,48 @@ fn show_venv_path() {
     test_app.assert_run_ok(&["show:venv_path"]);
 }
 
+#[test]
+fn show_info_as_json() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["show:info", "--format", "json"]);
+    test_app.assert_run_ok(&["show:venv_path", "--format", "json"]);
+}
+
+#[test]
+fn show_pythons() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["show:pythons"]);
+    test_app.assert_run_ok(&["show:pythons", "--format", "json"]);
+}
+
+#[test]
+fn no_python_matching_requested_version() {
+    let test_app = TestApp::new();
+    let error = test_app.assert_run_error(&["--python", "1.0", "show:info"]);
+    assert!(error.contains("1.0"));
+}
+
+#[test]
+fn no_python_matching_project_requirement() {
+    let test_app = TestApp::new();
+    test_app.write_file(
+        "setup.cfg",
+        "[metadata]\nname = demo\n\n[options]\npython_requires = >=1.0, <2.0\n",
+    );
+    let error = test_app.assert_run_error(&["show:info"]);
+    assert!(error.contains(">=1.0, <2.0"));
+}
+
+#[test]
+fn invalid_project_requirement_is_ignored() {
+    let test_app = TestApp::new();
+    test_app.write_file(
+        "setup.cfg",
+        "[metadata]\nname = demo\n\n[options]\npython_requires = >=3.6.*\n",
+    );
+    test_app.assert_run_ok(&["show:info"]);
+}
+
 #[test]
 fn init_works() {
     let test_app = TestApp::new();
@@ -23,6 +65,26 @@ fn init_works() {
     test_app.assert_run_ok(&["lock"]);
 }
 
+#[test]
+fn init_pyproject() {
+    let test_app = TestApp::new();
+    test_app.remove_setup_py();
+    test_app.remove_setup_cfg();
+
+    test_app.assert_run_ok(&["init", "foo", "--pyproject"]);
+    test_app.assert_file("pyproject.toml");
+    test_app.assert_run_ok(&["show:info"]);
+}
+
+#[test]
+fn lock_complains_if_pyproject_has_no_project_table() {
+    let test_app = TestApp::new();
+    test_app.remove_setup_py();
+    test_app.write_file("pyproject.toml", "[tool.black]\nline-length = 100\n");
+    let error = test_app.assert_run_error(&["lock"]);
+    assert!(error.contains("pyproject.toml"));
+}
+
 #[test]
 fn bump_in_lock_simple() {
     let test_app = TestApp::new();
@@ -31,6 +93,123 @@ fn bump_in_lock_simple() {
     assert!(actual.contains("attrs==19.2.0"));
 }
 
+#[test]
+fn record_index_options_in_lock() {
+    let test_app = TestApp::new();
+    test_app.write_file(
+        "dmenv.toml",
+        "index_url = \"https://pypi.example.com/simple\"\nrecord_index_options = true\n",
+    );
+    test_app.assert_run_ok(&["--find-links", "wheels", "bump-in-lock", "attrs", "19.2.0"]);
+    let actual = test_app.read_dev_lock();
+    let lines: Vec<_> = actual.lines().skip(1).take(2).collect();
+    assert_eq!(
+        lines,
+        &[
+            "--index-url https://pypi.example.com/simple",
+            "--find-links wheels"
+        ]
+    );
+    assert!(actual.contains("attrs==19.2.0"));
+}
+
+#[test]
+fn record_index_options_keeps_other_options() {
+    let test_app = TestApp::new();
+    test_app.write_file(
+        "dmenv.toml",
+        "index_url = \"https://pypi.example.com/simple\"\nrecord_index_options = true\n",
+    );
+    let lock = test_app.read_dev_lock();
+    test_app.override_lock(&format!("-c constraints.txt\n-r base.txt\n{}", lock));
+    test_app.assert_run_ok(&["bump-in-lock", "attrs", "19.2.0"]);
+    let actual = test_app.read_dev_lock();
+    assert!(actual.contains("--index-url https://pypi.example.com/simple\n"));
+    assert!(actual.contains("-c constraints.txt\n-r base.txt\n"));
+    assert!(actual.contains("attrs==19.2.0"));
+}
+
+#[test]
+fn install_offline_with_missing_packages() {
+    let test_app = TestApp::new();
+    test_app.override_lock("attrs==19.1.0\npytest==4.5.0\n");
+    std::fs::create_dir(test_app.path().join("wheelhouse")).unwrap();
+    test_app.write_file("wheelhouse/attrs-19.1.0-py2.py3-none-any.whl", "");
+
+    let message = test_app.assert_run_error(&["install", "--offline"]);
+    assert!(message.contains("pytest==4.5.0"));
+    assert!(!message.contains("attrs"));
+}
+
+#[test]
+fn lock_name_from_project_config() {
+    let test_app = TestApp::new();
+    let lock = test_app.read_dev_lock();
+    test_app.write_file("dev.lock", &lock);
+    test_app.remove_dev_lock();
+    test_app.write_file("dmenv.toml", "dev_lock = \"dev.lock\"\n");
+
+    test_app.assert_run_ok(&["show:config"]);
+    test_app.assert_run_ok(&["bump-in-lock", "attrs", "19.2.0"]);
+    let actual = std::fs::read_to_string(test_app.path().join("dev.lock")).unwrap();
+    assert!(actual.contains("attrs==19.2.0"));
+}
+
+#[test]
+fn bump_in_lock_with_profile() {
+    let test_app = TestApp::new();
+    let lock = test_app.read_dev_lock();
+    test_app.write_file("docs.lock", &lock);
+    test_app.write_file("dmenv.toml", "[profiles.docs]\nextras = [\"docs\"]\n");
+
+    test_app.assert_run_ok(&["--profile", "docs", "bump-in-lock", "attrs", "19.2.0"]);
+    let actual = std::fs::read_to_string(test_app.path().join("docs.lock")).unwrap();
+    assert!(actual.contains("attrs==19.2.0"));
+    assert_eq!(test_app.read_dev_lock(), lock);
+}
+
+#[test]
+fn unknown_profile() {
+    let test_app = TestApp::new();
+    let error = test_app.assert_run_error(&["--profile", "docs", "show:info"]);
+    assert!(error.contains("docs"));
+}
+
+#[test]
+fn malformed_project_config() {
+    let test_app = TestApp::new();
+    test_app.write_file("dmenv.toml", "no_such_setting = true\n");
+    let error = test_app.assert_run_error(&["show:config", "--format", "json"]);
+    assert!(error.contains("dmenv.toml"));
+}
+
+#[test]
+fn bump_in_lock_dry_run() {
+    let test_app = TestApp::new();
+    let before = test_app.read_dev_lock();
+    test_app.assert_run_ok(&["bump-in-lock", "attrs", "19.2.0", "--dry-run"]);
+    test_app.assert_run_ok(&["bump-in-lock", "attrs", "19.2.0", "--dry-run", "--diff"]);
+    assert_eq!(test_app.read_dev_lock(), before);
+}
+
+#[test]
+fn lock_matrix() {
+    let test_app = TestApp::new();
+    test_app.write_file("linux.txt", "# sys_platform: linux\nattrs==19.3.0\n");
+    test_app.write_file("windows.txt", "# sys_platform: win32\nattrs==19.2.0\n");
+    let linux_path = test_app.path().join("linux.txt");
+    let windows_path = test_app.path().join("windows.txt");
+    test_app.assert_run_ok(&[
+        "lock",
+        "--matrix",
+        &linux_path.to_string_lossy(),
+        &windows_path.to_string_lossy(),
+    ]);
+    let actual = test_app.read_dev_lock();
+    assert!(actual.contains("attrs==19.2.0 ; sys_platform == 'win32'\n"));
+    assert!(actual.contains("attrs==19.3.0 ; sys_platform != 'win32'\n"));
+}
+
 #[test]
 fn init_does_not_overwrite_existing_setup_py() {
     let test_app = TestApp::new();
@@ -58,6 +237,16 @@ fn lock_workflow() {
     test_app.assert_run_ok(&["run", "--no-exec", "pytest"]);
 }
 
+#[test]
+fn lock_check() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["lock"]);
+    test_app.assert_run_ok(&["lock", "--check"]);
+    test_app.override_lock("pytest==4.3.0\n");
+    test_app.assert_run_error(&["lock", "--check"]);
+    assert_eq!(test_app.read_dev_lock(), "pytest==4.3.0\n");
+}
+
 #[test]
 fn production_workflow() {
     let test_app = TestApp::new();
@@ -104,6 +293,18 @@ fn install_without_lock() {
     test_app.assert_run_error(&["install"]);
 }
 
+#[test]
+fn lock_drift_without_virtualenv() {
+    let test_app = TestApp::new();
+    test_app.assert_run_error(&["show:lock-drift"]);
+}
+
+#[test]
+fn sync_without_virtualenv() {
+    let test_app = TestApp::new();
+    test_app.assert_run_error(&["sync"]);
+}
+
 #[test]
 fn run_without_virtualenv() {
     let test_app = TestApp::new();
@@ -146,3 +347,145 @@ fn test_tidy() {
     assert!(!lock_contents.contains("appdirs"));
     assert!(lock_contents.contains("attrs==19.2.0"));
 }
+
+#[test]
+fn stale_venv() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["create"]);
+    let venvs = std::fs::read_dir(test_app.path().join(".venv/dev")).unwrap();
+    for entry in venvs {
+        let record_path = entry.unwrap().path().join("dmenv-base-python.json");
+        let contents = std::fs::read_to_string(&record_path).unwrap();
+        let mut record: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        record["sha256"] = "bad-checksum".into();
+        std::fs::write(&record_path, record.to_string()).unwrap();
+    }
+    let error = test_app.assert_run_error(&["show:deps"]);
+    assert!(error.contains("is stale"));
+    // Those commands are not allowed to re-install anything
+    let error = test_app.assert_run_error(&["--recreate-if-stale", "freeze"]);
+    assert!(error.contains("is stale"));
+    let error = test_app.assert_run_error(&["--recreate-if-stale", "env"]);
+    assert!(error.contains("is stale"));
+}
+
+#[test]
+fn workspace_not_found() {
+    let test_app = TestApp::new();
+    let error = test_app.assert_run_error(&["--workspace", "lock"]);
+    assert!(error.contains("dmenv-workspace.toml"));
+}
+
+#[test]
+fn workspace_unsupported_command() {
+    let test_app = TestApp::new();
+    test_app.write_file("dmenv-workspace.toml", "members = [\".\"]\n");
+    let error = test_app.assert_run_error(&["--workspace", "show:deps"]);
+    assert!(error.contains("--workspace"));
+}
+
+#[test]
+fn env_requires_venv() {
+    let test_app = TestApp::new();
+    let error = test_app.assert_run_error(&["env", "--shell", "fish"]);
+    assert!(error.contains("does not exist"));
+}
+
+#[test]
+fn run_with_env_files() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["create"]);
+    test_app.write_file(".env", "GREETING=hello\nMESSAGE=\"$GREETING world\"\n");
+    test_app.write_file("other.env", "GREETING=bye\n");
+    let check = "import os, sys; sys.exit(os.environ['MESSAGE'] != 'hello world' or os.environ['GREETING'] != 'bye' or not os.environ['VIRTUAL_ENV'])";
+    test_app.assert_run_ok(&[
+        "run",
+        "--no-exec",
+        "--env-file",
+        &test_app.path().join("other.env").to_string_lossy(),
+        "--",
+        "python",
+        "-c",
+        check,
+    ]);
+    let error = test_app.assert_run_error(&[
+        "run",
+        "--no-exec",
+        "--env-file",
+        "no-such.env",
+        "--",
+        "python",
+        "--version",
+    ]);
+    assert!(error.contains("no-such.env"));
+}
+
+#[test]
+fn run_forwards_exit_code() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["create"]);
+    let args = &[
+        "run",
+        "--no-exec",
+        "--",
+        "python",
+        "-c",
+        "import sys; sys.exit(5)",
+    ];
+    let error = test_app.run(to_string_args(args)).unwrap_err();
+    assert_eq!(error.exit_code(), 5);
+    assert!(error.to_string().contains("exited with code 5"));
+}
+
+#[test]
+fn run_python_module_code_and_project_scripts() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["create"]);
+    test_app.assert_run_ok(&[
+        "run",
+        "--no-exec",
+        "-c",
+        "import sys; open(sys.argv[1], 'w')",
+        "from-code",
+    ]);
+    test_app.assert_file("from-code");
+
+    test_app.write_file("touch.py", "import sys\nopen(sys.argv[1], 'w')\n");
+    test_app.assert_run_ok(&["run", "--no-exec", "-m", "touch", "from-module"]);
+    test_app.assert_file("from-module");
+
+    // Note: `create` does not install the project
+    test_app.write_file(
+        "setup.cfg",
+        "[options.entry_points]\nconsole_scripts = touch-it = touch_it:main\n",
+    );
+    test_app.write_file(
+        "touch_it.py",
+        "import sys\ndef main():\n    open(sys.argv[1], 'w')\n",
+    );
+    test_app.assert_run_ok(&["run", "--no-exec", "touch-it", "from-script"]);
+    test_app.assert_file("from-script");
+}
+
+#[test]
+fn run_task_with_dependencies() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["create"]);
+    test_app.write_file(
+        "dmenv.toml",
+        r#"
+[tasks.prepare]
+run = "python -c \"open('prepared', 'w')\""
+
+[tasks.check]
+run = ["python -c \"import os, sys; sys.exit(not os.path.exists('prepared') or os.environ['MODE'] != 'ci')\""]
+env = { MODE = "ci" }
+depends = ["prepare"]
+"#,
+    );
+    test_app.assert_run_ok(&["task", "check"]);
+    assert!(test_app.path().join("prepared").exists());
+
+    let error = test_app.assert_run_error(&["task", "no-such-task"]);
+    assert!(error.contains("known tasks: check, prepare"));
+}